@@ -0,0 +1,78 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+extern crate maskerad_data_parser;
+
+use maskerad_data_parser::level_description::LevelDescription;
+use maskerad_data_parser::gameobject_builder::GameObjectBuilder;
+use maskerad_data_parser::transform_description::TransformDescription;
+use maskerad_data_parser::rotation_description::RotationDescription;
+use maskerad_data_parser::mesh_description::MeshDescription;
+use maskerad_data_parser::component_description::{LightDescription, LightKind};
+use std::time::{Duration, Instant};
+
+/*
+    Compares the TOML format and the binary format on generated levels, by saving and loading them:
+    cargo bench --bench binary_format
+*/
+
+const ITERATIONS: u32 = 3;
+
+// A level of rooms, each room containing a light and props with meshes.
+fn generate_level(gameobject_count: usize) -> LevelDescription {
+    let mut level = LevelDescription::new(format!("generated level of {} gameobjects", gameobject_count));
+    for index in 0..gameobject_count {
+        let position = [index as f64, (index % 7) as f64 * 0.5, -(index as f64) * 0.25];
+        let rotation = RotationDescription::Quaternion([0.0, 0.0, 0.0, 1.0]);
+        let mut gameobject = GameObjectBuilder::new(format!("gameobject_{}", index));
+        gameobject.add_transform(TransformDescription::new(position, rotation, [1.0, 1.0, 1.0]));
+
+        if index % 20 == 0 {
+            gameobject.add_tag("room");
+            let mut light = LightDescription::new(LightKind::Point);
+            light.add_intensity(2.5).add_range(10.0);
+            gameobject.add_light(light);
+        } else {
+            gameobject
+                .add_parent(format!("gameobject_{}", index - index % 20))
+                .add_tag("prop")
+                .add_mesh(MeshDescription::new(format!("asset://meshes/prop_{}.gltf", index % 50)));
+        }
+        level.add_gameobject(gameobject);
+    }
+    level
+}
+
+// The mean duration of the function.
+fn measure<T, F: FnMut() -> T>(mut function: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        function();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn milliseconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1000.0 + duration.subsec_nanos() as f64 / 1_000_000.0
+}
+
+fn main() {
+    for &gameobject_count in [10_000, 20_000, 40_000].iter() {
+        let level = generate_level(gameobject_count);
+        let toml_string = level.as_string_toml().unwrap();
+        let binary = level.as_binary().unwrap();
+
+        let toml_save = measure(|| level.as_string_toml().unwrap());
+        let toml_load = measure(|| LevelDescription::load_from_toml(&mut toml_string.as_bytes()).unwrap());
+        let binary_save = measure(|| level.as_binary().unwrap());
+        let binary_load = measure(|| LevelDescription::load_from_binary(&mut binary.as_slice()).unwrap());
+
+        println!("{} gameobjects:", gameobject_count);
+        println!("    toml:   {:>10} bytes, save {:>9.2} ms, load {:>9.2} ms", toml_string.len(), milliseconds(toml_save), milliseconds(toml_load));
+        println!("    binary: {:>10} bytes, save {:>9.2} ms, load {:>9.2} ms", binary.len(), milliseconds(binary_save), milliseconds(binary_load));
+    }
+}
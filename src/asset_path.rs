@@ -0,0 +1,169 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use data_parser_error::{DataParserError, DataParserResult};
+
+/*
+    The paths of the data files (mesh paths, gameobject references, prefabs) can be:
+    - relative to the file declaring them: "../meshes/rock.gltf",
+    - relative to the project root: "asset://meshes/rock.gltf",
+    - absolute: "/home/user/project/meshes/rock.gltf", which only works on one machine.
+
+    When a level is loaded from a file, the relative mesh paths of the files it includes are
+    rebased on the directory of the level, so all the relative paths of the loaded level are
+    relative to the level file. The asset paths are left untouched.
+
+    When saving, the absolute paths can be rewritten as asset paths if they are in the project
+    root, or as paths relative to the saved file otherwise.
+*/
+
+/// The prefix of the paths relative to the project root.
+pub const ASSET_SCHEME: &str = "asset://";
+
+/// Whether the path is relative to the project root.
+pub fn is_asset_path(path: &str) -> bool {
+    path.starts_with(ASSET_SCHEME)
+}
+
+/// Resolves the paths written in the data files, the asset paths being relative to the project root.
+#[derive(Debug, Clone, Default)]
+pub struct PathResolver {
+    project_root: Option<PathBuf>,
+}
+
+impl PathResolver {
+    /// A resolver without project root: the asset paths can't be resolved.
+    pub fn new() -> Self {
+        debug!("Creating a new PathResolver.");
+        PathResolver::default()
+    }
+
+    pub fn add_project_root<P: AsRef<Path>>(&mut self, project_root: P) -> &mut Self {
+        debug!("Adding a project root to the PathResolver.");
+        self.project_root = Some(project_root.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn project_root(&self) -> Option<&Path> {
+        self.project_root.as_deref()
+    }
+
+    /// The path of a file referenced in a data file, the relative paths being relative to the given directory.
+    pub fn resolve<P: AsRef<Path>>(&self, path: &str, directory: P) -> DataParserResult<PathBuf> {
+        if is_asset_path(path) {
+            let project_root = self.project_root.as_ref().ok_or_else(|| {
+                DataParserError::ReferenceError(format!("The path '{}' is relative to the project root, but no project root is configured.", path))
+            })?;
+            return Ok(project_root.join(&path[ASSET_SCHEME.len()..]));
+        }
+
+        Ok(directory.as_ref().join(path))
+    }
+
+    /// The path to write in a data file of the given directory: an absolute path becomes an asset path
+    /// if it is in the project root, or a path relative to the directory otherwise. The other paths,
+    /// and the absolute paths which can't be made relative, are kept.
+    pub fn portable<P: AsRef<Path>>(&self, path: &str, directory: P) -> String {
+        if is_asset_path(path) || !Path::new(path).is_absolute() {
+            return path.to_owned();
+        }
+
+        let file = normalize(Path::new(path));
+        if let Some(ref project_root) = self.project_root {
+            let mut roots = vec![normalize(absolute(project_root).as_path())];
+            if let Ok(canonical_root) = fs::canonicalize(project_root) {
+                roots.push(canonical_root);
+            }
+            for root in roots {
+                if let Ok(relative) = file.strip_prefix(root.as_path()) {
+                    return format!("{}{}", ASSET_SCHEME, join_components(relative));
+                }
+            }
+        }
+
+        let directory = normalize(absolute(directory.as_ref()).as_path());
+        relative_path(directory.as_path(), file.as_path()).unwrap_or_else(|| path.to_owned())
+    }
+}
+
+// The path relative to the working directory, if it is relative.
+pub(crate) fn absolute(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+    env::current_dir().map(|current_dir| current_dir.join(path)).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn join_components(path: &Path) -> String {
+    path.components().map(|component| component.as_os_str().to_string_lossy().into_owned()).collect::<Vec<String>>().join("/")
+}
+
+// Removes the '.' components of a path, and the '..' components following a normal component.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut components: Vec<Component> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir => match components.last() {
+                Some(&Component::Normal(_)) => {
+                    components.pop();
+                },
+                Some(&Component::RootDir) | Some(&Component::Prefix(_)) => {},
+                _ => components.push(component),
+            },
+            other => components.push(other),
+        }
+    }
+    components.iter().map(|component| component.as_os_str()).collect()
+}
+
+// The path of a file relative to a directory, with '/' separators. Both paths must be normalized,
+// and both absolute or both relative.
+pub(crate) fn relative_path(directory: &Path, file: &Path) -> Option<String> {
+    let directory_components: Vec<Component> = directory.components().collect();
+    let file_components: Vec<Component> = file.components().collect();
+    let common = directory_components
+        .iter()
+        .zip(file_components.iter())
+        .take_while(|&(a, b)| a == b)
+        .count();
+    // Without a common root (e.g. another drive), the file can't be referenced.
+    if common == 0 && (directory.has_root() || file.has_root()) {
+        return None;
+    }
+
+    let mut segments: Vec<String> = directory_components[common..].iter().map(|_| String::from("..")).collect();
+    for component in file_components[common..].iter() {
+        segments.push(component.as_os_str().to_string_lossy().into_owned());
+    }
+    Some(segments.join("/"))
+}
+
+#[cfg(test)]
+mod asset_path_test {
+    use super::*;
+
+    #[test]
+    fn relative_paths() {
+        assert_eq!(normalize(Path::new("/project/levels/../meshes/./rock.gltf")), PathBuf::from("/project/meshes/rock.gltf"));
+        assert_eq!(relative_path(Path::new("/project/levels"), Path::new("/project/meshes/rock.gltf")), Some(String::from("../meshes/rock.gltf")));
+        assert_eq!(relative_path(Path::new("/project"), Path::new("/project/rock.gltf")), Some(String::from("rock.gltf")));
+        assert_eq!(relative_path(Path::new("levels"), Path::new("meshes/rock.gltf")), Some(String::from("../meshes/rock.gltf")));
+
+        let mut resolver = PathResolver::new();
+        assert!(resolver.resolve("asset://meshes/rock.gltf", "/project/levels").is_err());
+        resolver.add_project_root("/project");
+        assert_eq!(resolver.resolve("asset://meshes/rock.gltf", "/elsewhere").unwrap(), PathBuf::from("/project/meshes/rock.gltf"));
+        assert_eq!(resolver.resolve("rock.gltf", "/project/levels").unwrap(), PathBuf::from("/project/levels/rock.gltf"));
+        assert_eq!(resolver.portable("/project/meshes/rock.gltf", "/project/levels"), "asset://meshes/rock.gltf");
+        assert_eq!(resolver.portable("/other/rock.gltf", "/other/levels"), "../rock.gltf");
+        assert_eq!(resolver.portable("rock.gltf", "/other/levels"), "rock.gltf");
+    }
+}
@@ -0,0 +1,65 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use data_parser_error::{DataParserError, DataParserResult};
+
+/*
+    The data files are saved atomically: the content is written to a temporary file in the
+    directory of the destination, flushed to the disk, and the temporary file is renamed over the
+    destination. If the program crashes while saving, the destination keeps its previous content,
+    and at worst a temporary file '.<file name>.<process id>.<counter>.tmp' is left behind.
+*/
+
+static TEMPORARY_FILES: AtomicUsize = AtomicUsize::new(0);
+
+fn temporary_path(path: &Path) -> PathBuf {
+    let file_name = path.file_name().map(|file_name| file_name.to_string_lossy().into_owned()).unwrap_or_default();
+    let counter = TEMPORARY_FILES.fetch_add(1, Ordering::SeqCst);
+    path.with_file_name(format!(".{}.{}.{}.tmp", file_name, process::id(), counter))
+}
+
+fn write_temporary_file<F>(temporary_path: &Path, path: &Path, write: F) -> DataParserResult<()> where
+    F: FnOnce(&mut dyn Write) -> DataParserResult<()>
+{
+    let file = File::create(temporary_path)?;
+    // The saved file keeps the permissions of the file it replaces.
+    if let Ok(metadata) = fs::metadata(path) {
+        file.set_permissions(metadata.permissions())?;
+    }
+
+    let mut writer = BufWriter::new(file);
+    write(&mut writer)?;
+    let file = writer.into_inner().map_err(|error| error.into_error())?;
+    file.sync_all()?;
+    Ok(())
+}
+
+// Saves a file atomically, its content being written by the function.
+pub(crate) fn save<F>(path: &Path, write: F) -> DataParserResult<()> where
+    F: FnOnce(&mut dyn Write) -> DataParserResult<()>
+{
+    let temporary_path = temporary_path(path);
+    trace!("Saving {} through the temporary file {}.", path.display(), temporary_path.display());
+
+    let result = write_temporary_file(temporary_path.as_path(), path, write)
+        .and_then(|_| fs::rename(temporary_path.as_path(), path).map_err(DataParserError::from));
+    if let Err(error) = result {
+        let _ = fs::remove_file(temporary_path.as_path());
+        return Err(error.with_context(format!("Could not save the file '{}'", path.display())));
+    }
+
+    // Persist the rename itself. Directories can't be opened as files on every platform.
+    if let Some(directory) = path.parent().and_then(|directory| File::open(directory).ok()) {
+        let _ = directory.sync_all();
+    }
+    Ok(())
+}
@@ -0,0 +1,704 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str;
+use toml;
+use toml::value::Table;
+use crc32fast;
+use data_parser_error::{DataParserError, DataParserResult};
+use level_description::LevelDescription;
+use gameobject_builder::GameObjectBuilder;
+use transform_description::TransformDescription;
+use rotation_description::{RotationDescription, AngleUnit, EulerOrder};
+use mesh_description::{MeshDescription, MeshSelection, GltfReference};
+use component_description::{LightDescription, LightKind, ColliderDescription, ScriptDescription};
+use unknown_key::DocumentKind;
+use migration;
+use data_format::{self, DataFormat};
+use atomic_file;
+
+/*
+    Binary file structure, the numbers being little-endian:
+    magic               4 bytes, "MKDP"
+    format version      u16, BINARY_FORMAT_VERSION
+    document kind       u8, 0 for a level, 1 for a gameobject
+    reserved            u8, 0
+    payload length      u64
+    payload checksum    u32, CRC-32 of the payload
+    payload
+
+    The payload is the document, field by field, in the order of the TOML format:
+    - strings are their length (u32) followed by their UTF-8 bytes,
+    - sequences are their length (u32) followed by their elements,
+    - optional values are a u8 (0 if absent, 1 if present) followed by the value,
+    - enums are a u8 tag followed by the fields of the variant,
+    - numbers are f64, u32 or i64, booleans are u8.
+
+    A level stores its gameobjects flattened, declaring their parent. A gameobject stores its
+    nested children.
+
+    The decoder rejects the payloads nesting values (arrays and tables) or children deeper than
+    MAX_NESTING_DEPTH, which would overflow its stack.
+*/
+
+/// The version of the binary format written by this crate.
+pub const BINARY_FORMAT_VERSION: u16 = 1;
+
+const MAGIC: &[u8; 4] = b"MKDP";
+const HEADER_LENGTH: usize = 20;
+/// The maximum depth of the nested values and children of a decoded document.
+pub const MAX_NESTING_DEPTH: usize = 128;
+
+fn error<T, S: Into<String>>(description: S) -> DataParserResult<T> {
+    Err(DataParserError::BinaryFormatError(description.into()))
+}
+
+fn document_tag(kind: DocumentKind) -> u8 {
+    match kind {
+        DocumentKind::Level => 0,
+        DocumentKind::GameObject => 1,
+    }
+}
+
+fn document_name(kind: DocumentKind) -> &'static str {
+    match kind {
+        DocumentKind::Level => "level",
+        DocumentKind::GameObject => "gameobject",
+    }
+}
+
+struct Encoder {
+    buffer: Vec<u8>,
+}
+
+impl Encoder {
+    fn u8(&mut self, value: u8) {
+        self.buffer.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn i64(&mut self, value: i64) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f64(&mut self, value: f64) {
+        self.buffer.extend_from_slice(&value.to_bits().to_le_bytes());
+    }
+
+    fn f64s(&mut self, values: &[f64]) {
+        for value in values {
+            self.f64(*value);
+        }
+    }
+
+    fn length(&mut self, length: usize) -> DataParserResult<()> {
+        if length > u32::MAX as usize {
+            return error(format!("A sequence of {} elements is too long for the binary format.", length));
+        }
+        self.u32(length as u32);
+        Ok(())
+    }
+
+    fn string(&mut self, value: &str) -> DataParserResult<()> {
+        self.length(value.len())?;
+        self.buffer.extend_from_slice(value.as_bytes());
+        Ok(())
+    }
+
+    fn option<T, F>(&mut self, value: Option<T>, mut encode: F) -> DataParserResult<()> where
+        F: FnMut(&mut Self, T) -> DataParserResult<()>
+    {
+        match value {
+            Some(value) => {
+                self.u8(1);
+                encode(self, value)
+            },
+            None => {
+                self.u8(0);
+                Ok(())
+            },
+        }
+    }
+
+    fn unit(&mut self, unit: AngleUnit) {
+        self.u8(match unit {
+            AngleUnit::Radians => 0,
+            AngleUnit::Degrees => 1,
+        });
+    }
+
+    fn transform(&mut self, transform: &TransformDescription) {
+        self.f64s(&transform.position());
+        match transform.rotation() {
+            &RotationDescription::Quaternion(ref quaternion) => {
+                self.u8(0);
+                self.f64s(quaternion);
+            },
+            &RotationDescription::Euler { ref angles, unit, order } => {
+                self.u8(1);
+                self.f64s(angles);
+                self.unit(unit);
+                self.u8(match order {
+                    EulerOrder::XYZ => 0,
+                    EulerOrder::XZY => 1,
+                    EulerOrder::YXZ => 2,
+                    EulerOrder::YZX => 3,
+                    EulerOrder::ZXY => 4,
+                    EulerOrder::ZYX => 5,
+                });
+            },
+            &RotationDescription::AxisAngle { ref axis, angle, unit } => {
+                self.u8(2);
+                self.f64s(axis);
+                self.f64(angle);
+                self.unit(unit);
+            },
+        }
+        self.f64s(&transform.scale());
+    }
+
+    fn reference(&mut self, reference: &GltfReference) -> DataParserResult<()> {
+        match reference {
+            &GltfReference::Index(index) => {
+                self.u8(0);
+                self.length(index)
+            },
+            &GltfReference::Name(ref name) => {
+                self.u8(1);
+                self.string(name)
+            },
+        }
+    }
+
+    fn mesh(&mut self, mesh: &MeshDescription) -> DataParserResult<()> {
+        self.string(mesh.path())?;
+        self.option(mesh.selection(), |encoder, selection| match selection {
+            &MeshSelection::Mesh { ref mesh, primitive } => {
+                encoder.u8(0);
+                encoder.reference(mesh)?;
+                encoder.option(primitive, |encoder, primitive| encoder.length(primitive))
+            },
+            &MeshSelection::Node(ref node) => {
+                encoder.u8(1);
+                encoder.reference(node)
+            },
+            &MeshSelection::Scene(ref scene) => {
+                encoder.u8(2);
+                encoder.reference(scene)
+            },
+        })
+    }
+
+    fn light(&mut self, light: &LightDescription) -> DataParserResult<()> {
+        self.u8(match light.kind() {
+            LightKind::Point => 0,
+            LightKind::Spot => 1,
+            LightKind::Directional => 2,
+        });
+        self.f64s(&light.color());
+        self.f64(light.intensity());
+        self.option(light.range(), |encoder, range| {
+            encoder.f64(range);
+            Ok(())
+        })
+    }
+
+    fn collider(&mut self, collider: &ColliderDescription) {
+        match collider {
+            &ColliderDescription::Box { ref half_extents } => {
+                self.u8(0);
+                self.f64s(half_extents);
+            },
+            &ColliderDescription::Sphere { radius } => {
+                self.u8(1);
+                self.f64(radius);
+            },
+            &ColliderDescription::Capsule { radius, height } => {
+                self.u8(2);
+                self.f64(radius);
+                self.f64(height);
+            },
+        }
+    }
+
+    fn table(&mut self, table: &Table) -> DataParserResult<()> {
+        self.length(table.len())?;
+        for (key, value) in table.iter() {
+            self.string(key)?;
+            self.value(value)?;
+        }
+        Ok(())
+    }
+
+    fn value(&mut self, value: &toml::Value) -> DataParserResult<()> {
+        match value {
+            &toml::Value::String(ref string) => {
+                self.u8(0);
+                self.string(string)
+            },
+            &toml::Value::Integer(integer) => {
+                self.u8(1);
+                self.i64(integer);
+                Ok(())
+            },
+            &toml::Value::Float(float) => {
+                self.u8(2);
+                self.f64(float);
+                Ok(())
+            },
+            &toml::Value::Boolean(boolean) => {
+                self.u8(3);
+                self.u8(boolean as u8);
+                Ok(())
+            },
+            &toml::Value::Datetime(ref datetime) => {
+                self.u8(4);
+                self.string(datetime.to_string().as_str())
+            },
+            &toml::Value::Array(ref array) => {
+                self.u8(5);
+                self.length(array.len())?;
+                for element in array.iter() {
+                    self.value(element)?;
+                }
+                Ok(())
+            },
+            &toml::Value::Table(ref table) => {
+                self.u8(6);
+                self.table(table)
+            },
+        }
+    }
+
+    fn gameobject(&mut self, gameobject: &GameObjectBuilder) -> DataParserResult<()> {
+        self.string(gameobject.id())?;
+        self.option(gameobject.parent(), |encoder, parent| encoder.string(parent))?;
+        self.length(gameobject.tags().len())?;
+        for tag in gameobject.tags() {
+            self.string(tag)?;
+        }
+        self.transform(gameobject.transform());
+        self.option(gameobject.mesh(), |encoder, mesh| encoder.mesh(mesh))?;
+        self.option(gameobject.light(), |encoder, light| encoder.light(light))?;
+        self.option(gameobject.collider(), |encoder, collider| {
+            encoder.collider(collider);
+            Ok(())
+        })?;
+        self.length(gameobject.scripts().len())?;
+        for script in gameobject.scripts() {
+            self.string(script.name())?;
+            self.table(script.parameters())?;
+        }
+        self.length(gameobject.children().len())?;
+        for child in gameobject.children() {
+            self.gameobject(child)?;
+        }
+        Ok(())
+    }
+
+    fn level(&mut self, level: &LevelDescription) -> DataParserResult<()> {
+        self.string(level.title())?;
+        self.length(level.slice().len())?;
+        for gameobject in level.slice() {
+            self.gameobject(gameobject)?;
+        }
+        Ok(())
+    }
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    position: usize,
+    depth: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn bytes(&mut self, length: usize) -> DataParserResult<&'a [u8]> {
+        if self.data.len() - self.position < length {
+            return error(format!("The payload ends unexpectedly at the byte {}.", self.position));
+        }
+        let bytes = &self.data[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> DataParserResult<u8> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    fn u32(&mut self) -> DataParserResult<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn i64(&mut self) -> DataParserResult<i64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(i64::from_le_bytes(bytes))
+    }
+
+    fn f64(&mut self) -> DataParserResult<f64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(f64::from_bits(u64::from_le_bytes(bytes)))
+    }
+
+    fn f64x3(&mut self) -> DataParserResult<[f64; 3]> {
+        Ok([self.f64()?, self.f64()?, self.f64()?])
+    }
+
+    fn length(&mut self) -> DataParserResult<usize> {
+        self.u32().map(|length| length as usize)
+    }
+
+    fn string(&mut self) -> DataParserResult<String> {
+        let length = self.length()?;
+        let position = self.position;
+        let bytes = self.bytes(length)?;
+        match str::from_utf8(bytes) {
+            Ok(string) => Ok(string.to_owned()),
+            Err(_) => error(format!("The string at the byte {} is not valid UTF-8.", position)),
+        }
+    }
+
+    fn option<T, F>(&mut self, mut decode: F) -> DataParserResult<Option<T>> where
+        F: FnMut(&mut Self) -> DataParserResult<T>
+    {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => decode(self).map(Some),
+            tag => self.invalid_tag("option", tag),
+        }
+    }
+
+    fn invalid_tag<T>(&self, name: &str, tag: u8) -> DataParserResult<T> {
+        error(format!("Invalid {} tag {} at the byte {}.", name, tag, self.position - 1))
+    }
+
+    fn unit(&mut self) -> DataParserResult<AngleUnit> {
+        match self.u8()? {
+            0 => Ok(AngleUnit::Radians),
+            1 => Ok(AngleUnit::Degrees),
+            tag => self.invalid_tag("angle unit", tag),
+        }
+    }
+
+    fn transform(&mut self) -> DataParserResult<TransformDescription> {
+        let position = self.f64x3()?;
+        let rotation = match self.u8()? {
+            0 => RotationDescription::Quaternion([self.f64()?, self.f64()?, self.f64()?, self.f64()?]),
+            1 => {
+                let angles = self.f64x3()?;
+                let unit = self.unit()?;
+                let order = match self.u8()? {
+                    0 => EulerOrder::XYZ,
+                    1 => EulerOrder::XZY,
+                    2 => EulerOrder::YXZ,
+                    3 => EulerOrder::YZX,
+                    4 => EulerOrder::ZXY,
+                    5 => EulerOrder::ZYX,
+                    tag => return self.invalid_tag("Euler order", tag),
+                };
+                RotationDescription::Euler { angles, unit, order }
+            },
+            2 => {
+                let axis = self.f64x3()?;
+                let angle = self.f64()?;
+                let unit = self.unit()?;
+                RotationDescription::AxisAngle { axis, angle, unit }
+            },
+            tag => return self.invalid_tag("rotation", tag),
+        };
+        let scale = self.f64x3()?;
+        Ok(TransformDescription::new(position, rotation, scale))
+    }
+
+    fn reference(&mut self) -> DataParserResult<GltfReference> {
+        match self.u8()? {
+            0 => self.length().map(GltfReference::Index),
+            1 => self.string().map(GltfReference::Name),
+            tag => self.invalid_tag("glTF reference", tag),
+        }
+    }
+
+    fn mesh(&mut self) -> DataParserResult<MeshDescription> {
+        let mut mesh = MeshDescription::new(self.string()?);
+        let selection = self.option(|decoder| match decoder.u8()? {
+            0 => {
+                let mesh = decoder.reference()?;
+                let primitive = decoder.option(|decoder| decoder.length())?;
+                Ok(MeshSelection::Mesh { mesh, primitive })
+            },
+            1 => decoder.reference().map(MeshSelection::Node),
+            2 => decoder.reference().map(MeshSelection::Scene),
+            tag => decoder.invalid_tag("mesh selection", tag),
+        })?;
+        mesh.add_selection(selection);
+        Ok(mesh)
+    }
+
+    fn light(&mut self) -> DataParserResult<LightDescription> {
+        let kind = match self.u8()? {
+            0 => LightKind::Point,
+            1 => LightKind::Spot,
+            2 => LightKind::Directional,
+            tag => return self.invalid_tag("light kind", tag),
+        };
+        let mut light = LightDescription::new(kind);
+        light
+            .add_color(self.f64x3()?)
+            .add_intensity(self.f64()?)
+            .add_range(self.option(|decoder| decoder.f64())?);
+        Ok(light)
+    }
+
+    fn collider(&mut self) -> DataParserResult<ColliderDescription> {
+        match self.u8()? {
+            0 => Ok(ColliderDescription::Box { half_extents: self.f64x3()? }),
+            1 => Ok(ColliderDescription::Sphere { radius: self.f64()? }),
+            2 => Ok(ColliderDescription::Capsule { radius: self.f64()?, height: self.f64()? }),
+            tag => self.invalid_tag("collider", tag),
+        }
+    }
+
+    // Decodes a nested value or gameobject, one level deeper.
+    fn nested<T, F>(&mut self, decode: F) -> DataParserResult<T> where
+        F: FnOnce(&mut Self) -> DataParserResult<T>
+    {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return error(format!("The payload nests values or gameobjects deeper than {} levels at the byte {}.", MAX_NESTING_DEPTH, self.position));
+        }
+        self.depth += 1;
+        let result = decode(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn table(&mut self) -> DataParserResult<Table> {
+        let length = self.length()?;
+        let mut table = Table::new();
+        for _ in 0..length {
+            let key = self.string()?;
+            let value = self.value()?;
+            table.insert(key, value);
+        }
+        Ok(table)
+    }
+
+    fn value(&mut self) -> DataParserResult<toml::Value> {
+        match self.u8()? {
+            0 => self.string().map(toml::Value::String),
+            1 => self.i64().map(toml::Value::Integer),
+            2 => self.f64().map(toml::Value::Float),
+            3 => self.u8().map(|boolean| toml::Value::Boolean(boolean != 0)),
+            4 => {
+                let datetime = self.string()?;
+                match datetime.parse() {
+                    Ok(datetime) => Ok(toml::Value::Datetime(datetime)),
+                    Err(_) => error(format!("Invalid datetime '{}'.", datetime)),
+                }
+            },
+            5 => {
+                let length = self.length()?;
+                let mut array = Vec::with_capacity(length.min(self.data.len()));
+                for _ in 0..length {
+                    array.push(self.nested(|decoder| decoder.value())?);
+                }
+                Ok(toml::Value::Array(array))
+            },
+            6 => self.nested(|decoder| decoder.table()).map(toml::Value::Table),
+            tag => self.invalid_tag("value", tag),
+        }
+    }
+
+    fn gameobject(&mut self) -> DataParserResult<GameObjectBuilder> {
+        let mut gameobject = GameObjectBuilder::new(self.string()?);
+        if let Some(parent) = self.option(|decoder| decoder.string())? {
+            gameobject.add_parent(parent);
+        }
+        for _ in 0..self.length()? {
+            gameobject.add_tag(self.string()?);
+        }
+
+        let transform = self.transform()?;
+        transform.check().map_err(|description| {
+            DataParserError::BinaryFormatError(format!("Gameobject '{}': {}", gameobject.id(), description))
+        })?;
+        gameobject.add_transform(transform);
+        let mesh = self.option(|decoder| decoder.mesh())?;
+        gameobject.add_mesh(mesh);
+
+        let light = self.option(|decoder| decoder.light())?;
+        let collider = self.option(|decoder| decoder.collider())?;
+        let components = light.as_ref().map_or(Ok(()), |light| light.check())
+            .and_then(|_| collider.as_ref().map_or(Ok(()), |collider| collider.check()));
+        components.map_err(|description| {
+            DataParserError::BinaryFormatError(format!("Gameobject '{}': {}", gameobject.id(), description))
+        })?;
+        gameobject.add_light(light).add_collider(collider);
+
+        for _ in 0..self.length()? {
+            let mut script = ScriptDescription::new(self.string()?);
+            for (name, value) in self.table()? {
+                script.add_parameter(name, value);
+            }
+            gameobject.add_script(script);
+        }
+        for _ in 0..self.length()? {
+            let child = self.nested(|decoder| decoder.gameobject())?;
+            gameobject.add_child(child);
+        }
+        Ok(gameobject)
+    }
+
+    fn level(&mut self) -> DataParserResult<LevelDescription> {
+        let mut level = LevelDescription::new(self.string()?);
+        for _ in 0..self.length()? {
+            let gameobject = self.gameobject()?;
+            level.add_gameobject(gameobject);
+        }
+        level.hierarchy()?;
+        Ok(level)
+    }
+}
+
+// Writes the header and the payload encoded by the function.
+fn encode<F>(kind: DocumentKind, encode_payload: F) -> DataParserResult<Vec<u8>> where
+    F: FnOnce(&mut Encoder) -> DataParserResult<()>
+{
+    let mut encoder = Encoder {
+        buffer: vec![0; HEADER_LENGTH],
+    };
+    encode_payload(&mut encoder)?;
+
+    let mut data = encoder.buffer;
+    let payload_length = (data.len() - HEADER_LENGTH) as u64;
+    let checksum = crc32fast::hash(&data[HEADER_LENGTH..]);
+    data[0..4].copy_from_slice(MAGIC);
+    data[4..6].copy_from_slice(&BINARY_FORMAT_VERSION.to_le_bytes());
+    data[6] = document_tag(kind);
+    data[7] = 0;
+    data[8..16].copy_from_slice(&payload_length.to_le_bytes());
+    data[16..20].copy_from_slice(&checksum.to_le_bytes());
+    Ok(data)
+}
+
+// The kind of the document stored in the binary data.
+fn read_kind(data: &[u8]) -> DataParserResult<DocumentKind> {
+    if data.len() < HEADER_LENGTH || &data[0..4] != MAGIC {
+        return error("The data doesn't start with the header of the binary format.");
+    }
+    match data[6] {
+        0 => Ok(DocumentKind::Level),
+        1 => Ok(DocumentKind::GameObject),
+        tag => error(format!("Unknown document kind {}.", tag)),
+    }
+}
+
+// Checks the header, and decodes the payload with the function.
+fn decode<T, F>(data: &[u8], kind: DocumentKind, decode_payload: F) -> DataParserResult<T> where
+    F: FnOnce(&mut Decoder) -> DataParserResult<T>
+{
+    let document_kind = read_kind(data)?;
+
+    let version = u16::from_le_bytes([data[4], data[5]]);
+    if version != BINARY_FORMAT_VERSION {
+        return error(format!("The binary format version {} is not supported, the supported version is {}.", version, BINARY_FORMAT_VERSION));
+    }
+    if document_kind != kind {
+        return error(format!("The data contains a {}, not a {}.", document_name(document_kind), document_name(kind)));
+    }
+
+    let mut length_bytes = [0; 8];
+    length_bytes.copy_from_slice(&data[8..16]);
+    let payload_length = u64::from_le_bytes(length_bytes);
+    let payload = &data[HEADER_LENGTH..];
+    if payload.len() as u64 != payload_length {
+        return error(format!("The payload has {} bytes, but the header declares {} bytes.", payload.len(), payload_length));
+    }
+    let checksum = u32::from_le_bytes([data[16], data[17], data[18], data[19]]);
+    if crc32fast::hash(payload) != checksum {
+        return error("The checksum of the payload doesn't match: the data is corrupted.");
+    }
+
+    let mut decoder = Decoder {
+        data: payload,
+        position: 0,
+        depth: 0,
+    };
+    let document = decode_payload(&mut decoder)?;
+    if decoder.position != payload.len() {
+        return error(format!("The payload has {} unexpected bytes at its end.", payload.len() - decoder.position));
+    }
+    Ok(document)
+}
+
+pub(crate) fn encode_level(level: &LevelDescription) -> DataParserResult<Vec<u8>> {
+    encode(DocumentKind::Level, |encoder| encoder.level(level))
+}
+
+pub(crate) fn decode_level(data: &[u8]) -> DataParserResult<LevelDescription> {
+    decode(data, DocumentKind::Level, |decoder| decoder.level())
+}
+
+pub(crate) fn encode_gameobject(gameobject: &GameObjectBuilder) -> DataParserResult<Vec<u8>> {
+    encode(DocumentKind::GameObject, |encoder| encoder.gameobject(gameobject))
+}
+
+pub(crate) fn decode_gameobject(data: &[u8]) -> DataParserResult<GameObjectBuilder> {
+    decode(data, DocumentKind::GameObject, |decoder| decoder.gameobject())
+}
+
+/// Converts a level or gameobject file, with its references and prefabs, to a binary file. The
+/// file can be in any text format (see data_format.rs).
+pub fn convert_toml_to_binary<P, Q>(toml_path: P, binary_path: Q) -> DataParserResult<()> where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    debug!("Converting the toml file {} to the binary file {}.", toml_path.as_ref().display(), binary_path.as_ref().display());
+
+    let mut content = String::new();
+    File::open(toml_path.as_ref())?.read_to_string(&mut content)?;
+    let document = data_format::parse(content.as_ref(), DataFormat::of_file(toml_path.as_ref())).map_err(|error| {
+        error.with_source(Some(toml_path.as_ref()), content.as_ref(), DataFormat::of_file(toml_path.as_ref()))
+    })?;
+
+    let data = match migration::document_kind(&document) {
+        DocumentKind::Level => LevelDescription::load_from_file(toml_path.as_ref())?.as_binary()?,
+        DocumentKind::GameObject => GameObjectBuilder::load_from_file(toml_path.as_ref())?.as_binary()?,
+    };
+    atomic_file::save(binary_path.as_ref(), |writer| {
+        writer.write_all(data.as_slice())?;
+        Ok(())
+    })
+}
+
+/// Converts a binary level or gameobject file to a TOML file.
+pub fn convert_binary_to_toml<P, Q>(binary_path: P, toml_path: Q) -> DataParserResult<()> where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    debug!("Converting the binary file {} to the toml file {}.", binary_path.as_ref().display(), toml_path.as_ref().display());
+
+    let mut data = Vec::new();
+    File::open(binary_path.as_ref())?.read_to_end(&mut data)?;
+    let toml_string = match read_kind(data.as_slice())? {
+        DocumentKind::Level => decode_level(data.as_slice())?.as_string_toml()?,
+        DocumentKind::GameObject => decode_gameobject(data.as_slice())?.as_string_toml()?,
+    };
+    atomic_file::save(toml_path.as_ref(), |writer| {
+        writer.write_all(toml_string.as_bytes())?;
+        Ok(())
+    })
+}
@@ -0,0 +1,167 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use toml;
+use toml::value::Table;
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use unknown_key::DocumentKind;
+use migration;
+
+/*
+    The canonical form of a document only depends on its content, so saving an unchanged level
+    gives the same bytes, and an edit only changes the lines of the edited values:
+
+    - the keys of every table are written in alphabetical order, the values of a table before its
+      sub-tables (e.g. format_version and title before the [[gameobjects]]),
+    - the gameobjects of a level are ordered by hierarchy: the root gameobjects sorted by id, each
+      one followed by its children, sorted by id, and their own children. The nested children of a
+      gameobject are sorted by id,
+    - the floats are rounded to CANONICAL_FLOAT_DECIMALS decimals, then written in the shortest
+      form reading back as the same float. The rotations and scales of the transforms aren't
+      rounded: a small rotation or scale would become null. -0.0 is written 0.0,
+    - each gameobject and each table is written in its own block, with its header.
+
+    The order of the tags and scripts is kept, the scripts being run in this order.
+*/
+
+/// The number of decimals of the floats of a canonical document.
+pub const CANONICAL_FLOAT_DECIMALS: usize = 6;
+
+// The values of a transform written as they are.
+const UNROUNDED_TRANSFORM_KEYS: &[&str] = &["rotation", "scale"];
+
+/// How a level or gameobject is written as text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerializationMode {
+    /// The gameobjects are written in the order of the description, with their floats as they are.
+    Verbatim,
+    /// The document is written in its canonical form, suited to version control.
+    Canonical,
+}
+
+impl Default for SerializationMode {
+    fn default() -> Self {
+        SerializationMode::Verbatim
+    }
+}
+
+fn canonical_float(float: f64) -> f64 {
+    if !float.is_finite() {
+        return float;
+    }
+    // Formatting rounds to the nearest decimal, where scaling the float would lose precision.
+    let rounded = format!("{:.*}", CANONICAL_FLOAT_DECIMALS, float).parse::<f64>().unwrap_or(float);
+    if rounded == 0.0 {
+        0.0
+    } else {
+        rounded
+    }
+}
+
+fn gameobject_id(gameobject: &toml::Value) -> &str {
+    gameobject.get("id").and_then(|id| id.as_str()).unwrap_or("")
+}
+
+fn sort_by_id(gameobjects: &mut [toml::Value]) {
+    gameobjects.sort_by(|first, second| gameobject_id(first).cmp(gameobject_id(second)));
+}
+
+// Orders the gameobjects of a level, stored after their parent: the roots, and the gameobjects
+// whose parent isn't in the level, come sorted by id, each one followed by its descendants.
+fn order_by_hierarchy(gameobjects: Vec<toml::Value>) -> Vec<toml::Value> {
+    let ids: HashSet<String> = gameobjects.iter().map(|gameobject| gameobject_id(gameobject).to_owned()).collect();
+    let mut roots = Vec::new();
+    let mut children: HashMap<String, Vec<toml::Value>> = HashMap::new();
+    for gameobject in gameobjects {
+        let parent = gameobject.get("parent").and_then(|parent| parent.as_str()).map(|parent| parent.to_owned());
+        match parent {
+            Some(ref parent) if ids.contains(parent) && parent.as_str() != gameobject_id(&gameobject) => {
+                children.entry(parent.clone()).or_default().push(gameobject);
+            },
+            _ => roots.push(gameobject),
+        }
+    }
+
+    // The gameobjects left to order are stacked, the next one last, so the depth of the hierarchy
+    // doesn't grow the call stack.
+    let mut ordered = Vec::new();
+    sort_by_id(&mut roots);
+    let mut pending: Vec<toml::Value> = roots.into_iter().rev().collect();
+    while let Some(gameobject) = pending.pop() {
+        let mut descendants = children.remove(gameobject_id(&gameobject)).unwrap_or_default();
+        sort_by_id(&mut descendants);
+        ordered.push(gameobject);
+        pending.extend(descendants.into_iter().rev());
+    }
+    // The gameobjects of a parenting cycle aren't reached from a root.
+    let mut unreached: Vec<toml::Value> = children.into_values().flatten().collect();
+    sort_by_id(&mut unreached);
+    ordered.extend(unreached);
+    ordered
+}
+
+// Sorts the nested children of a gameobject, and of its children.
+fn sort_children(gameobject: &mut toml::Value) {
+    if let Some(&mut toml::Value::Array(ref mut children)) = gameobject.get_mut("children") {
+        sort_by_id(children);
+        for child in children.iter_mut() {
+            sort_children(child);
+        }
+    }
+}
+
+// Sorts the keys of the tables of a value, found at the given key, and rounds its floats if asked.
+fn canonical_value(value: toml::Value, key: &str, round: bool) -> toml::Value {
+    match value {
+        toml::Value::Float(float) if round => toml::Value::Float(canonical_float(float)),
+        toml::Value::Float(float) => toml::Value::Float(if float == 0.0 { 0.0 } else { float }),
+        toml::Value::Array(array) => toml::Value::Array(array.into_iter().map(|value| canonical_value(value, key, round)).collect()),
+        toml::Value::Table(table) => {
+            let mut entries: Vec<(String, toml::Value)> = table.into_iter().collect();
+            entries.sort_by(|first, second| first.0.cmp(&second.0));
+            toml::Value::Table(entries.into_iter().map(|(entry_key, value)| {
+                let round = round && !(key == "transform" && UNROUNDED_TRANSFORM_KEYS.contains(&entry_key.as_str()));
+                let value = canonical_value(value, entry_key.as_str(), round);
+                (entry_key, value)
+            }).collect::<Table>())
+        },
+        other => other,
+    }
+}
+
+// The canonical form of a level or gameobject document.
+pub(crate) fn canonicalize(document: toml::Value) -> toml::Value {
+    let mut document = canonical_value(document, "", true);
+    match migration::document_kind(&document) {
+        DocumentKind::Level => {
+            if let Some(&mut toml::Value::Array(ref mut gameobjects)) = document.get_mut("gameobjects") {
+                *gameobjects = order_by_hierarchy(mem::take(gameobjects));
+                for gameobject in gameobjects.iter_mut() {
+                    sort_children(gameobject);
+                }
+            }
+        },
+        DocumentKind::GameObject => sort_children(&mut document),
+    }
+    document
+}
+
+#[cfg(test)]
+mod canonical_test {
+    use super::*;
+
+    #[test]
+    fn round_floats() {
+        assert_eq!(canonical_float(0.1 + 0.2), 0.3);
+        assert_eq!(canonical_float(0.1f32 as f64), 0.1);
+        assert_eq!(canonical_float(-0.0000001), 0.0);
+        assert!(canonical_float(-0.0000001).is_sign_positive());
+        assert_eq!(canonical_float(123456789.1234567), 123456789.123457);
+        assert_eq!(canonical_float(1e300), 1e300);
+    }
+}
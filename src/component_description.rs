@@ -0,0 +1,298 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use toml;
+use toml::value::Table;
+use serde_json;
+use gameobject_builder::GameObjectBuilder;
+use unknown_key;
+
+/*
+    Components of a gameobject:
+    tags = ["tag1", "tag2"]
+
+    [light]
+    kind = "point"              # "point", "spot" or "directional"
+    color = [r, g, b]           # optional, white by default
+    intensity = 1.0             # optional
+    range = 10.0                # optional
+
+    [collider]
+    shape = "box"               # with half_extents = [x, y, z]
+    shape = "sphere"            # with radius = r
+    shape = "capsule"           # with radius = r and height = h
+
+    [[scripts]]
+    name = "script name"
+
+    [scripts.parameters]
+    parameter = value
+    ...
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LightKind {
+    Point,
+    Spot,
+    Directional,
+}
+
+fn white() -> [f64; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct LightDescription {
+    kind: LightKind,
+    #[serde(default = "white")]
+    color: [f64; 3],
+    #[serde(default = "one")]
+    intensity: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    range: Option<f64>,
+}
+
+impl LightDescription {
+    pub fn new(kind: LightKind) -> Self {
+        debug!("Creating a new LightDescription.");
+        LightDescription {
+            kind,
+            color: white(),
+            intensity: one(),
+            range: None,
+        }
+    }
+
+    pub fn add_color(&mut self, color: [f64; 3]) -> &mut Self {
+        self.color = color;
+        self
+    }
+
+    pub fn add_intensity(&mut self, intensity: f64) -> &mut Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn add_range<R: Into<Option<f64>>>(&mut self, range: R) -> &mut Self {
+        self.range = range.into();
+        self
+    }
+
+    pub(crate) fn check(&self) -> Result<(), String> {
+        if self.color.iter().any(|component| !component.is_finite() || *component < 0.0) {
+            return Err(format!("light.color must contain positive numbers, found {:?}.", self.color));
+        }
+        if !self.intensity.is_finite() || self.intensity < 0.0 {
+            return Err(format!("light.intensity must be a positive number, found {}.", self.intensity));
+        }
+        match self.range {
+            Some(range) if !range.is_finite() || range <= 0.0 => {
+                Err(format!("light.range must be strictly positive, found {}.", range))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    pub fn kind(&self) -> LightKind {
+        self.kind
+    }
+
+    pub fn color(&self) -> [f64; 3] {
+        self.color
+    }
+
+    pub fn intensity(&self) -> f64 {
+        self.intensity
+    }
+
+    pub fn range(&self) -> Option<f64> {
+        self.range
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "shape", rename_all = "lowercase")]
+pub enum ColliderDescription {
+    Box {
+        half_extents: [f64; 3],
+    },
+    Sphere {
+        radius: f64,
+    },
+    Capsule {
+        radius: f64,
+        height: f64,
+    },
+}
+
+impl ColliderDescription {
+    pub(crate) fn check(&self) -> Result<(), String> {
+        let positive = |field: &str, value: f64| {
+            if value.is_finite() && value > 0.0 {
+                Ok(())
+            } else {
+                Err(format!("collider.{} must be strictly positive, found {}.", field, value))
+            }
+        };
+
+        match self {
+            &ColliderDescription::Box { half_extents } => {
+                for half_extent in half_extents.iter() {
+                    positive("half_extents", *half_extent)?;
+                }
+                Ok(())
+            },
+            &ColliderDescription::Sphere { radius } => positive("radius", radius),
+            &ColliderDescription::Capsule { radius, height } => {
+                positive("radius", radius)?;
+                positive("height", height)
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ScriptDescription {
+    name: String,
+    #[serde(default, skip_serializing_if = "Table::is_empty")]
+    parameters: Table,
+}
+
+impl ScriptDescription {
+    pub fn new<I: Into<String>>(name: I) -> Self {
+        debug!("Creating a new ScriptDescription.");
+        ScriptDescription {
+            name: name.into(),
+            parameters: Table::new(),
+        }
+    }
+
+    pub fn add_parameter<I: Into<String>, V: Into<toml::Value>>(&mut self, name: I, value: V) -> &mut Self {
+        self.parameters.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn parameters(&self) -> &Table {
+        &self.parameters
+    }
+}
+
+/// The component described by the value of a glTF extras key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentKind {
+    /// A string of comma separated tags, or an array of tags.
+    Tags,
+    /// An object describing a light.
+    Light,
+    /// An object describing a collider.
+    Collider,
+    /// A script named by the key, whose parameters are the values of an object.
+    Script,
+}
+
+/// Maps the keys of the extras of glTF nodes to components. The keys which aren't mapped are ignored.
+#[derive(Debug, Clone)]
+pub struct ExtrasMapping {
+    keys: Vec<(String, ComponentKind)>,
+}
+
+impl Default for ExtrasMapping {
+    fn default() -> Self {
+        let mut mapping = ExtrasMapping::new();
+        mapping
+            .add_key("tags", ComponentKind::Tags)
+            .add_key("light", ComponentKind::Light)
+            .add_key("collider", ComponentKind::Collider);
+        mapping
+    }
+}
+
+impl ExtrasMapping {
+    /// A mapping without keys.
+    pub fn new() -> Self {
+        ExtrasMapping {
+            keys: Vec::new(),
+        }
+    }
+
+    pub fn add_key<I: Into<String>>(&mut self, key: I, kind: ComponentKind) -> &mut Self {
+        let key = key.into();
+        self.keys.retain(|&(ref mapped_key, _)| *mapped_key != key);
+        self.keys.push((key, kind));
+        self
+    }
+
+    pub fn kind(&self, key: &str) -> Option<ComponentKind> {
+        self.keys.iter().find(|&&(ref mapped_key, _)| mapped_key == key).map(|&(_, kind)| kind)
+    }
+
+    // Adds the components described by the extras of a node to its gameobject.
+    pub(crate) fn apply(&self, extras: &serde_json::Value, gameobject: &mut GameObjectBuilder) -> Result<(), String> {
+        let extras = match extras.as_object() {
+            Some(extras) => extras,
+            None => return Ok(()),
+        };
+
+        for (key, value) in extras.iter() {
+            let kind = match self.kind(key.as_str()) {
+                Some(kind) => kind,
+                None => {
+                    trace!("The extras key {} isn't mapped to a component.", key);
+                    continue;
+                },
+            };
+            let error = |json_error: serde_json::Error| format!("extras.{}: {}", key, json_error);
+
+            match kind {
+                ComponentKind::Tags => {
+                    let tags: Vec<String> = match value.as_str() {
+                        Some(tags) => tags.split(',').map(|tag| tag.trim().to_owned()).filter(|tag| !tag.is_empty()).collect(),
+                        None => serde_json::from_value(value.clone()).map_err(error)?,
+                    };
+                    for tag in tags {
+                        gameobject.add_tag(tag);
+                    }
+                },
+                ComponentKind::Light => {
+                    // serde ignores the unknown keys, which the data files report.
+                    if let Some(message) = unknown_key::unknown_object_key(value, "light") {
+                        return Err(format!("extras.{}: {}", key, message));
+                    }
+                    let light: LightDescription = serde_json::from_value(value.clone()).map_err(error)?;
+                    light.check().map_err(|message| format!("extras.{}: {}", key, message))?;
+                    gameobject.add_light(light);
+                },
+                ComponentKind::Collider => {
+                    if let Some(message) = unknown_key::unknown_object_key(value, "collider") {
+                        return Err(format!("extras.{}: {}", key, message));
+                    }
+                    let collider: ColliderDescription = serde_json::from_value(value.clone()).map_err(error)?;
+                    collider.check().map_err(|message| format!("extras.{}: {}", key, message))?;
+                    gameobject.add_collider(collider);
+                },
+                ComponentKind::Script => {
+                    let parameters: Table = serde_json::from_value(value.clone()).map_err(error)?;
+                    gameobject.add_script(ScriptDescription {
+                        name: key.clone(),
+                        parameters,
+                    });
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
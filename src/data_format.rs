@@ -0,0 +1,293 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use toml;
+use serde::Serialize;
+use serde::ser::Error as SerializeError;
+use serde_json;
+use ron;
+use std::path::Path;
+use data_parser_error::{DataParserError, DataParserResult};
+use error_location::{self, ErrorLocation};
+use canonical::{self, SerializationMode};
+
+/*
+    The level and gameobject files can be written in TOML, JSON or RON, with the same keys:
+
+    JSON:
+    {
+      "format_version": 2,
+      "title": "level name",
+      "gameobjects": [
+        {
+          "id": "game object name",
+          "transform": {
+            "position": [x, y, z],
+            "rotation": [x, y, z],
+            "scale": [x, y, z]
+          }
+        }
+      ]
+    }
+
+    RON, the tables being written as structs or maps:
+    (
+        format_version: 2,
+        title: "level name",
+        gameobjects: [
+            (
+                id: "game object name",
+                transform: (
+                    position: [x, y, z],
+                    rotation: [x, y, z],
+                    scale: [x, y, z],
+                ),
+            ),
+        ],
+    )
+
+    The enum values are strings in every format (e.g. unit: "degrees", kind: "spot"), and the
+    datetimes of the script parameters are strings in JSON and RON.
+
+    Whatever its format, a document is parsed into a TOML value, which is then migrated, resolved
+    and validated the same way. The format of a file is given by its extension, the files with
+    another extension being TOML files. The references and prefabs can be in any format.
+*/
+
+/// The text formats of the level and gameobject files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    Toml,
+    Json,
+    Ron,
+}
+
+impl Default for DataFormat {
+    fn default() -> Self {
+        DataFormat::Toml
+    }
+}
+
+impl DataFormat {
+    /// The format of a file from its extension ("toml", "json" or "ron", whatever the case), if known.
+    pub fn from_extension<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_string_lossy().to_lowercase();
+        match extension.as_str() {
+            "toml" => Some(DataFormat::Toml),
+            "json" => Some(DataFormat::Json),
+            "ron" => Some(DataFormat::Ron),
+            _ => None,
+        }
+    }
+
+    /// The format of a file from its extension, TOML if the extension is unknown.
+    pub fn of_file<P: AsRef<Path>>(path: P) -> Self {
+        DataFormat::from_extension(path).unwrap_or_default()
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            &DataFormat::Toml => "toml",
+            &DataFormat::Json => "json",
+            &DataFormat::Ron => "ron",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            &DataFormat::Toml => "TOML",
+            &DataFormat::Json => "JSON",
+            &DataFormat::Ron => "RON",
+        }
+    }
+}
+
+// Parses a document. The errors are located in the source, but not in a file.
+pub(crate) fn parse(source: &str, format: DataFormat) -> DataParserResult<toml::Value> {
+    let value: toml::Value = match format {
+        DataFormat::Toml => return error_location::deserialize_str(source, &mut Vec::new()),
+        DataFormat::Json => serde_json::from_str(source).map_err(|error| {
+            let location = ErrorLocation::at_position(error.line(), error.column());
+            syntax_error(format, error.to_string(), location, source)
+        })?,
+        DataFormat::Ron => ron::from_str(source).map_err(|error| {
+            let location = ErrorLocation::at_position(error.position.line, error.position.col);
+            syntax_error(format, error.code.to_string(), location, source)
+        })?,
+    };
+
+    match value {
+        toml::Value::Table(_) => Ok(value),
+        other => {
+            let description = format!("The root of a {} document must be a table, found a {}.", format.name(), other.type_str());
+            Err(syntax_error(format, description, ErrorLocation::at_position(1, 1), source))
+        },
+    }
+}
+
+fn syntax_error(format: DataFormat, description: String, mut location: ErrorLocation, source: &str) -> DataParserError {
+    location.locate(source, format);
+    DataParserError::SyntaxError(format!("Could not parse the {} document: {}", format.name(), description), Box::new(location))
+}
+
+// Serializes a document. The JSON and RON documents list the keys of their tables in alphabetical order.
+pub(crate) fn to_string<T: Serialize>(document: &T, format: DataFormat) -> DataParserResult<String> {
+    to_string_with_mode(document, format, SerializationMode::Verbatim)
+}
+
+// Serializes a document, in its canonical form if asked (see canonical.rs).
+pub(crate) fn to_string_with_mode<T: Serialize>(document: &T, format: DataFormat, mode: SerializationMode) -> DataParserResult<String> {
+    if format == DataFormat::Toml && mode == SerializationMode::Verbatim {
+        return Ok(toml::to_string(document)?);
+    }
+
+    let mut value = toml::Value::try_from(document)?;
+    if mode == SerializationMode::Canonical {
+        value = canonical::canonicalize(value);
+    }
+    if format == DataFormat::Toml {
+        return Ok(toml::to_string(&value)?);
+    }
+
+    let mut output = String::new();
+    match format {
+        DataFormat::Json => {
+            let json = serde_json::to_string_pretty(&to_json(&value)?).map_err(|json_error| {
+                DataParserError::JsonError(String::from("Could not write the JSON document."), json_error)
+            })?;
+            output.push_str(json.as_str());
+        },
+        _ => write_ron(&value, 0, &mut output),
+    }
+    output.push('\n');
+    Ok(output)
+}
+
+pub(crate) fn to_json(value: &toml::Value) -> DataParserResult<serde_json::Value> {
+    Ok(match value {
+        &toml::Value::String(ref string) => serde_json::Value::String(string.clone()),
+        &toml::Value::Integer(integer) => serde_json::Value::from(integer),
+        &toml::Value::Float(float) => {
+            let number = serde_json::Number::from_f64(float).ok_or_else(|| {
+                let json_error: serde_json::Error = SerializeError::custom(format!("JSON can't represent the float {}", float));
+                DataParserError::JsonError(String::from("Could not write the JSON document."), json_error)
+            })?;
+            serde_json::Value::Number(number)
+        },
+        &toml::Value::Boolean(boolean) => serde_json::Value::Bool(boolean),
+        &toml::Value::Datetime(ref datetime) => serde_json::Value::String(datetime.to_string()),
+        &toml::Value::Array(ref array) => {
+            serde_json::Value::Array(array.iter().map(to_json).collect::<DataParserResult<_>>()?)
+        },
+        &toml::Value::Table(ref table) => {
+            let mut object = serde_json::Map::new();
+            for (key, value) in table.iter() {
+                object.insert(key.clone(), to_json(value)?);
+            }
+            serde_json::Value::Object(object)
+        },
+    })
+}
+
+// Whether the key can be a field of a RON struct.
+fn is_ron_identifier(key: &str) -> bool {
+    let mut characters = key.chars();
+    let starts_identifier = characters.next().map(|first| first.is_ascii_alphabetic() || first == '_').unwrap_or(false);
+    let keyword = ["true", "false", "Some", "None", "inf", "NaN"].contains(&key);
+    starts_identifier && !keyword && characters.all(|character| character.is_ascii_alphanumeric() || character == '_')
+}
+
+fn ron_string(string: &str) -> String {
+    ron::to_string(string).unwrap_or_else(|_| format!("{:?}", string))
+}
+
+fn indent(indentation: usize, output: &mut String) {
+    for _ in 0..indentation {
+        output.push_str("    ");
+    }
+}
+
+// Writes a value in RON: the tables are structs, or maps if one of their keys isn't an identifier,
+// and the arrays of scalars stay on one line.
+fn write_ron(value: &toml::Value, indentation: usize, output: &mut String) {
+    match value {
+        &toml::Value::String(ref string) => output.push_str(ron_string(string).as_str()),
+        &toml::Value::Integer(integer) => output.push_str(integer.to_string().as_str()),
+        // The Debug representation is the shortest one reading back as the same float, with a fraction.
+        &toml::Value::Float(float) => output.push_str(format!("{:?}", float).as_str()),
+        &toml::Value::Boolean(boolean) => output.push_str(if boolean { "true" } else { "false" }),
+        &toml::Value::Datetime(ref datetime) => output.push_str(ron_string(datetime.to_string().as_str()).as_str()),
+        &toml::Value::Array(ref array) => {
+            let scalars = array.iter().all(|element| !element.is_array() && !element.is_table());
+            output.push('[');
+            for (index, element) in array.iter().enumerate() {
+                if scalars {
+                    if index > 0 {
+                        output.push_str(", ");
+                    }
+                    write_ron(element, indentation, output);
+                } else {
+                    output.push('\n');
+                    indent(indentation + 1, output);
+                    write_ron(element, indentation + 1, output);
+                    output.push(',');
+                }
+            }
+            if !scalars && !array.is_empty() {
+                output.push('\n');
+                indent(indentation, output);
+            }
+            output.push(']');
+        },
+        &toml::Value::Table(ref table) => {
+            // An empty struct would be read as a unit value.
+            let is_struct = !table.is_empty() && table.keys().all(|key| is_ron_identifier(key));
+            output.push(if is_struct { '(' } else { '{' });
+            for (key, value) in table.iter() {
+                output.push('\n');
+                indent(indentation + 1, output);
+                if is_struct {
+                    output.push_str(key);
+                } else {
+                    output.push_str(ron_string(key).as_str());
+                }
+                output.push_str(": ");
+                write_ron(value, indentation + 1, output);
+                output.push(',');
+            }
+            if !table.is_empty() {
+                output.push('\n');
+                indent(indentation, output);
+            }
+            output.push(if is_struct { ')' } else { '}' });
+        },
+    }
+}
+
+#[cfg(test)]
+mod data_format_test {
+    use super::*;
+
+    #[test]
+    fn write_and_parse_ron() {
+        let source = "format_version = 2\ntitle = \"ron\"\nsizes = [1.0, 0.1, 1e300, -0.0]\n\"odd key\" = { empty = {}, list = [] }\n\n[[items]]\nname = \"a \\\"quoted\\\" name\"\n";
+        let value = parse(source, DataFormat::Toml).unwrap();
+
+        let mut ron_document = String::new();
+        write_ron(&value, 0, &mut ron_document);
+        assert!(ron_document.starts_with("{\n    \"format_version\": 2,\n    \"items\": [\n        (\n            name: \"a \\\"quoted\\\" name\",\n        ),\n    ],\n    \"odd key\": (\n        empty: {},\n        list: [],\n    ),"), "{}", ron_document);
+        assert!(ron_document.contains("\"sizes\": [1.0, 0.1, 1e300, -0.0],"), "{}", ron_document);
+        assert_eq!(parse(ron_document.as_str(), DataFormat::Ron).unwrap(), value);
+
+        let json_document = to_string(&value, DataFormat::Json).unwrap();
+        assert_eq!(parse(json_document.as_str(), DataFormat::Json).unwrap(), value);
+
+        assert_eq!(DataFormat::of_file("levels/level.RON"), DataFormat::Ron);
+        assert_eq!(DataFormat::of_file("levels/level.level"), DataFormat::Toml);
+        assert_eq!(DataFormat::from_extension("levels/level"), None);
+    }
+}
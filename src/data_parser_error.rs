@@ -10,15 +10,143 @@ use toml::ser::Error as SerializationError;
 use std::fmt;
 use std::error::Error;
 use gltf::Error as GltfError;
+use serde_json::Error as JsonError;
 use std::io::Error as IoError;
+use std::path::Path;
+use error_location::ErrorLocation;
+use data_format::DataFormat;
 
 
 #[derive(Debug)]
 pub enum DataParserError {
-    DeserializationError(String, DeserializationError),
+    DeserializationError(String, DeserializationError, Box<ErrorLocation>),
     SerializationError(String, SerializationError),
+    JsonError(String, JsonError),
     GltfError(String, GltfError),
     IoError(String, IoError),
+    ReferenceError(String),
+    BuildError(String),
+    InstantiationError(String, Vec<DataParserError>),
+    HierarchyError(String),
+    UnknownKeyError(String, Box<ErrorLocation>),
+    MigrationError(String),
+    BinaryFormatError(String),
+    SyntaxError(String, Box<ErrorLocation>),
+}
+
+impl DataParserError {
+    pub(crate) fn with_location(self, location: ErrorLocation) -> Self {
+        match self {
+            DataParserError::DeserializationError(description, error, _) => {
+                DataParserError::DeserializationError(description, error, Box::new(location))
+            },
+            other => other,
+        }
+    }
+
+    // Attach the file and the source of the document to a deserialization error which
+    // hasn't been located in another file yet.
+    pub(crate) fn with_source(self, file: Option<&Path>, source: &str, format: DataFormat) -> Self {
+        match self {
+            DataParserError::DeserializationError(description, error, mut location) => {
+                if location.file().is_none() && location.snippet().is_none() {
+                    if let Some(file) = file {
+                        location.set_file(file);
+                    }
+                    location.locate(source, format);
+                }
+                DataParserError::DeserializationError(description, error, location)
+            },
+            DataParserError::SyntaxError(description, mut location) => {
+                if location.file().is_none() && location.snippet().is_none() {
+                    if let Some(file) = file {
+                        location.set_file(file);
+                    }
+                    location.locate(source, format);
+                }
+                DataParserError::SyntaxError(description, location)
+            },
+            other => other,
+        }
+    }
+
+    /// Where the error occurred in a data file, if known.
+    pub fn location(&self) -> Option<&ErrorLocation> {
+        match self {
+            &DataParserError::DeserializationError(_, _, ref location) |
+            &DataParserError::UnknownKeyError(_, ref location) |
+            &DataParserError::SyntaxError(_, ref location) if !location.is_empty() => {
+                Some(location.as_ref())
+            },
+            _ => None,
+        }
+    }
+
+    /// A human-readable report of the error: its description, its location in the data file,
+    /// the offending line with a caret under the offending column, and its cause.
+    #[allow(deprecated)]
+    pub fn render(&self) -> String {
+        let mut report = format!("error: {}", self);
+        if let Some(location) = self.location() {
+            report.push_str(&format!("\n  --> {}", location));
+            if let Some(snippet) = location.render_snippet() {
+                for line in snippet.lines() {
+                    report.push_str(&format!("\n  {}", line));
+                }
+            }
+        }
+        if let Some(cause) = self.cause() {
+            report.push_str(&format!("\n  = {}", cause));
+        }
+        report
+    }
+
+    // Prefix the description of the error with some context (which file, which entry...),
+    // keeping the underlying cause untouched.
+    pub(crate) fn with_context<I: Into<String>>(self, context: I) -> Self {
+        let context = context.into();
+        match self {
+            DataParserError::DeserializationError(description, error, location) => {
+                DataParserError::DeserializationError(format!("{}: {}", context, description), error, location)
+            },
+            DataParserError::SerializationError(description, error) => {
+                DataParserError::SerializationError(format!("{}: {}", context, description), error)
+            },
+            DataParserError::JsonError(description, error) => {
+                DataParserError::JsonError(format!("{}: {}", context, description), error)
+            },
+            DataParserError::GltfError(description, error) => {
+                DataParserError::GltfError(format!("{}: {}", context, description), error)
+            },
+            DataParserError::IoError(description, error) => {
+                DataParserError::IoError(format!("{}: {}", context, description), error)
+            },
+            DataParserError::ReferenceError(description) => {
+                DataParserError::ReferenceError(format!("{}: {}", context, description))
+            },
+            DataParserError::BuildError(description) => {
+                DataParserError::BuildError(format!("{}: {}", context, description))
+            },
+            DataParserError::InstantiationError(description, errors) => {
+                DataParserError::InstantiationError(format!("{}: {}", context, description), errors)
+            },
+            DataParserError::HierarchyError(description) => {
+                DataParserError::HierarchyError(format!("{}: {}", context, description))
+            },
+            DataParserError::UnknownKeyError(description, location) => {
+                DataParserError::UnknownKeyError(format!("{}: {}", context, description), location)
+            },
+            DataParserError::MigrationError(description) => {
+                DataParserError::MigrationError(format!("{}: {}", context, description))
+            },
+            DataParserError::BinaryFormatError(description) => {
+                DataParserError::BinaryFormatError(format!("{}: {}", context, description))
+            },
+            DataParserError::SyntaxError(description, location) => {
+                DataParserError::SyntaxError(format!("{}: {}", context, description), location)
+            },
+        }
+    }
 }
 
 unsafe impl Send for DataParserError {}
@@ -30,8 +158,15 @@ impl fmt::Display for DataParserError {
             &DataParserError::SerializationError(ref description, _) => {
                 write!(f, "Serialization error: {}", description)
             },
-            &DataParserError::DeserializationError(ref description, _) => {
-                write!(f, "Deserialization error: {}", description)
+            &DataParserError::DeserializationError(ref description, _, ref location) => {
+                if location.is_empty() {
+                    write!(f, "Deserialization error: {}", description)
+                } else {
+                    write!(f, "Deserialization error: {} (at {})", description, location)
+                }
+            },
+            &DataParserError::JsonError(ref description, _) => {
+                write!(f, "Json error: {}", description)
             },
             &DataParserError::GltfError(ref description, _) => {
                 write!(f, "Gltf error: {}", description)
@@ -39,6 +174,42 @@ impl fmt::Display for DataParserError {
             &DataParserError::IoError(ref description, _) => {
                 write!(f, "Io error: {}", description)
             },
+            &DataParserError::ReferenceError(ref description) => {
+                write!(f, "Reference error: {}", description)
+            },
+            &DataParserError::BuildError(ref description) => {
+                write!(f, "Build error: {}", description)
+            },
+            &DataParserError::InstantiationError(ref description, ref errors) => {
+                write!(f, "Instantiation error: {}", description)?;
+                for error in errors.iter() {
+                    write!(f, "\n\t{}", error)?;
+                }
+                Ok(())
+            },
+            &DataParserError::HierarchyError(ref description) => {
+                write!(f, "Hierarchy error: {}", description)
+            },
+            &DataParserError::UnknownKeyError(ref description, ref location) => {
+                if location.is_empty() {
+                    write!(f, "Unknown key error: {}", description)
+                } else {
+                    write!(f, "Unknown key error: {} (at {})", description, location)
+                }
+            },
+            &DataParserError::MigrationError(ref description) => {
+                write!(f, "Migration error: {}", description)
+            },
+            &DataParserError::BinaryFormatError(ref description) => {
+                write!(f, "Binary format error: {}", description)
+            },
+            &DataParserError::SyntaxError(ref description, ref location) => {
+                if location.is_empty() {
+                    write!(f, "Syntax error: {}", description)
+                } else {
+                    write!(f, "Syntax error: {} (at {})", description, location)
+                }
+            },
         }
     }
 }
@@ -49,32 +220,86 @@ impl Error for DataParserError {
             &DataParserError::SerializationError(_, _) => {
                 "SerializationError"
             },
-            &DataParserError::DeserializationError(_, _) => {
+            &DataParserError::DeserializationError(_, _, _) => {
                 "DeserializationError"
             },
+            &DataParserError::JsonError(_, _) => {
+                "JsonError"
+            },
             &DataParserError::GltfError(_, _) => {
                 "GltfError"
             },
             &DataParserError::IoError(_, _) => {
                 "IoError"
-            }
+            },
+            &DataParserError::ReferenceError(_) => {
+                "ReferenceError"
+            },
+            &DataParserError::BuildError(_) => {
+                "BuildError"
+            },
+            &DataParserError::InstantiationError(_, _) => {
+                "InstantiationError"
+            },
+            &DataParserError::HierarchyError(_) => {
+                "HierarchyError"
+            },
+            &DataParserError::UnknownKeyError(_, _) => {
+                "UnknownKeyError"
+            },
+            &DataParserError::MigrationError(_) => {
+                "MigrationError"
+            },
+            &DataParserError::BinaryFormatError(_) => {
+                "BinaryFormatError"
+            },
+            &DataParserError::SyntaxError(_, _) => {
+                "SyntaxError"
+            },
         }
     }
 
-    fn cause(&self) -> Option<&Error> {
+    fn cause(&self) -> Option<&dyn Error> {
         match self {
-            &DataParserError::DeserializationError(_, ref deserialization_error) => {
+            &DataParserError::DeserializationError(_, ref deserialization_error, _) => {
                 Some(deserialization_error)
             },
             &DataParserError::SerializationError(_, ref serialization_error) => {
                 Some(serialization_error)
             },
+            &DataParserError::JsonError(_, ref json_error) => {
+                Some(json_error)
+            },
             &DataParserError::GltfError(_, ref gltf_error) => {
                 Some(gltf_error)
             },
             &DataParserError::IoError(_, ref io_error) => {
                 Some(io_error)
             },
+            &DataParserError::ReferenceError(_) => {
+                None
+            },
+            &DataParserError::BuildError(_) => {
+                None
+            },
+            &DataParserError::InstantiationError(_, ref errors) => {
+                errors.first().map(|error| error as &dyn Error)
+            },
+            &DataParserError::HierarchyError(_) => {
+                None
+            },
+            &DataParserError::UnknownKeyError(_, _) => {
+                None
+            },
+            &DataParserError::MigrationError(_) => {
+                None
+            },
+            &DataParserError::BinaryFormatError(_) => {
+                None
+            },
+            &DataParserError::SyntaxError(_, _) => {
+                None
+            },
         }
     }
 }
@@ -89,7 +314,8 @@ impl From<SerializationError> for DataParserError {
 
 impl From<DeserializationError> for DataParserError {
     fn from(error: DeserializationError) -> Self {
-        DataParserError::DeserializationError(format!("Error while deserializing the level as a Rust structure."), error)
+        let location = ErrorLocation::from_toml_error(&error);
+        DataParserError::DeserializationError(format!("Error while deserializing the level as a Rust structure."), error, Box::new(location))
     }
 }
 
@@ -0,0 +1,150 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+use data_parser_error::DataParserError;
+use error_location::ErrorLocation;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Severity::Warning => write!(f, "warning"),
+            &Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// The kind of problem reported by a diagnostic, with a stable code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticCode {
+    /// E001: the file is not a valid TOML, JSON or RON document.
+    InvalidToml,
+    /// E002: a value doesn't have the expected type, shape or range (e.g. a bad transform).
+    InvalidValue,
+    /// E003: several gameobjects of the level have the same id.
+    DuplicateId,
+    /// E004: the mesh file of a gameobject can't be found.
+    MissingMesh,
+    /// E005: a referenced file can't be found, or includes itself.
+    BrokenReference,
+    /// E006: the parent of a gameobject doesn't exist, or the parenting is cyclic.
+    InvalidHierarchy,
+    /// E007: any other error.
+    Other,
+    /// E008: the format version of a file is invalid, or not supported.
+    UnsupportedFormatVersion,
+    /// E009: the mesh file of a gameobject is not a valid glTF asset, or its buffers or images can't be found.
+    InvalidMesh,
+    /// W001: a key doesn't match any field, and is ignored.
+    UnknownKey,
+}
+
+impl DiagnosticCode {
+    pub fn code(&self) -> &'static str {
+        match self {
+            &DiagnosticCode::InvalidToml => "E001",
+            &DiagnosticCode::InvalidValue => "E002",
+            &DiagnosticCode::DuplicateId => "E003",
+            &DiagnosticCode::MissingMesh => "E004",
+            &DiagnosticCode::BrokenReference => "E005",
+            &DiagnosticCode::InvalidHierarchy => "E006",
+            &DiagnosticCode::Other => "E007",
+            &DiagnosticCode::UnsupportedFormatVersion => "E008",
+            &DiagnosticCode::InvalidMesh => "E009",
+            &DiagnosticCode::UnknownKey => "W001",
+        }
+    }
+}
+
+/// A problem found while validating a level.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    severity: Severity,
+    code: DiagnosticCode,
+    message: String,
+    location: ErrorLocation,
+}
+
+impl Diagnostic {
+    pub fn new<I: Into<String>>(severity: Severity, code: DiagnosticCode, message: I, location: ErrorLocation) -> Self {
+        Diagnostic {
+            severity,
+            code,
+            message: message.into(),
+            location,
+        }
+    }
+
+    pub fn from_error(error: &DataParserError) -> Self {
+        let code = match error {
+            &DataParserError::DeserializationError(_, _, ref location) => {
+                if location.key_path().is_some() {
+                    DiagnosticCode::InvalidValue
+                } else {
+                    DiagnosticCode::InvalidToml
+                }
+            },
+            &DataParserError::SyntaxError(_, _) => DiagnosticCode::InvalidToml,
+            &DataParserError::IoError(_, _) | &DataParserError::ReferenceError(_) => DiagnosticCode::BrokenReference,
+            &DataParserError::HierarchyError(_) => DiagnosticCode::InvalidHierarchy,
+            &DataParserError::UnknownKeyError(_, _) => DiagnosticCode::UnknownKey,
+            &DataParserError::MigrationError(_) => DiagnosticCode::UnsupportedFormatVersion,
+            &DataParserError::GltfError(_, _) => DiagnosticCode::InvalidMesh,
+            _ => DiagnosticCode::Other,
+        };
+
+        let message = match error {
+            &DataParserError::DeserializationError(ref description, ref deserialization_error, _) => {
+                format!("{} {}", description, deserialization_error)
+            },
+            other => other.to_string(),
+        };
+
+        Diagnostic::new(Severity::Error, code, message, error.location().cloned().unwrap_or_default())
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn code(&self) -> DiagnosticCode {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        self.message.as_str()
+    }
+
+    pub fn location(&self) -> &ErrorLocation {
+        &self.location
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code.code(), self.message)?;
+        if !self.location.is_empty() {
+            write!(f, "\n  --> {}", self.location)?;
+            if let Some(snippet) = self.location.render_snippet() {
+                for line in snippet.lines() {
+                    write!(f, "\n  {}", line)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,420 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use toml;
+use serde::de;
+use toml_edit::{self, DocumentMut, Item, Table, TableLike, ArrayOfTables, Array, InlineTable};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use data_parser_error::{DataParserError, DataParserResult};
+use error_location::ErrorLocation;
+use gameobject_builder::GameObjectBuilder;
+use transform_description::TransformDescription;
+use unknown_key::DocumentKind;
+use migration::{self, CURRENT_FORMAT_VERSION};
+use data_format::{self, DataFormat};
+use atomic_file;
+
+/*
+    A DocumentEditor applies changes to a level or gameobject TOML file, keeping everything else
+    as written: comments, key order, spacing, references and prefabs.
+
+    Only the gameobjects written in the document can be edited: the inline gameobjects of a level
+    (as [[gameobjects]] tables or inline tables), the root gameobject of a gameobject file, and
+    their children. The gameobjects of the referenced files are edited in their own files.
+
+    The changed values keep the comments and spacing around them, and a value replacing a table
+    written with a header (e.g. [gameobjects.transform.rotation]) is written as a table too. The
+    new gameobjects are appended after the last gameobject of the document, in the same style.
+    The documents written in an older format version are edited if their content is unchanged by
+    the migrations, the other ones are upgraded first with LevelDescription::migrate_file or
+    GameObjectBuilder::migrate_file.
+*/
+
+/// Edits a level or gameobject TOML document, preserving its comments and formatting.
+#[derive(Debug, Clone)]
+pub struct DocumentEditor {
+    document: DocumentMut,
+    kind: DocumentKind,
+}
+
+// A gameobject, or one of its tables, written as a table with a header or as an inline table.
+struct EditedTable<'a> {
+    table: &'a mut dyn TableLike,
+    inline: bool,
+}
+
+fn not_found(id: &str) -> DataParserError {
+    DataParserError::HierarchyError(format!("No gameobject '{}' is written in the document. The gameobjects of the referenced files must be edited in their own files.", id))
+}
+
+// A value of the document which hasn't the type of the format, reported as the loaders report it.
+fn invalid_structure(description: String) -> DataParserError {
+    let toml_error: toml::de::Error = de::Error::custom(description.as_str());
+    DataParserError::DeserializationError(description, toml_error, Box::default())
+}
+
+fn gameobject_id(table: &dyn TableLike) -> Option<&str> {
+    table.get("id").and_then(|id| id.as_str())
+}
+
+// The gameobject tables of an item holding gameobjects: an array of tables, or an array mixing
+// references and inline tables.
+fn gameobject_tables<'a>(item: &'a mut Item) -> Vec<EditedTable<'a>> {
+    match item {
+        &mut Item::ArrayOfTables(ref mut tables) => tables.iter_mut().map(|table| {
+            EditedTable {
+                table: table as &mut dyn TableLike,
+                inline: false,
+            }
+        }).collect(),
+        &mut Item::Value(toml_edit::Value::Array(ref mut array)) => array.iter_mut().filter_map(|value| {
+            value.as_inline_table_mut().map(|table| EditedTable {
+                table: table as &mut dyn TableLike,
+                inline: true,
+            })
+        }).collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Finds a gameobject by id, in the gameobject or its children.
+fn find<'a>(gameobject: EditedTable<'a>, id: &str) -> Option<EditedTable<'a>> {
+    if gameobject_id(gameobject.table) == Some(id) {
+        return Some(gameobject);
+    }
+
+    let children = gameobject.table.get_mut("children")?;
+    gameobject_tables(children).into_iter().filter_map(|child| find(child, id)).next()
+}
+
+// Visits the ids of the gameobject and of its children, in the order of the document.
+fn collect_ids(table: &dyn TableLike, ids: &mut Vec<String>) {
+    if let Some(id) = gameobject_id(table) {
+        ids.push(id.to_owned());
+    }
+    if let Some(children) = table.get("children") {
+        collect_ids_in(children, ids);
+    }
+}
+
+fn collect_ids_in(item: &Item, ids: &mut Vec<String>) {
+    match item {
+        &Item::ArrayOfTables(ref tables) => {
+            for table in tables.iter() {
+                collect_ids(table, ids);
+            }
+        },
+        &Item::Value(toml_edit::Value::Array(ref array)) => {
+            for table in array.iter().filter_map(|value| value.as_inline_table()) {
+                collect_ids(table, ids);
+            }
+        },
+        _ => {},
+    }
+}
+
+// Converts a value of the toml crate to a value of a toml_edit document.
+fn to_edit_value(value: &toml::Value) -> toml_edit::Value {
+    match value {
+        &toml::Value::String(ref string) => toml_edit::Value::from(string.as_str()),
+        &toml::Value::Integer(integer) => toml_edit::Value::from(integer),
+        &toml::Value::Float(float) => toml_edit::Value::from(float),
+        &toml::Value::Boolean(boolean) => toml_edit::Value::from(boolean),
+        &toml::Value::Datetime(ref datetime) => {
+            datetime.to_string().parse::<toml_edit::Value>().unwrap_or_else(|_| toml_edit::Value::from(datetime.to_string()))
+        },
+        &toml::Value::Array(ref array) => {
+            toml_edit::Value::Array(array.iter().map(to_edit_value).collect::<Array>())
+        },
+        &toml::Value::Table(ref table) => {
+            let mut inline_table = InlineTable::new();
+            for (key, value) in table.iter() {
+                inline_table.insert(key.as_str(), to_edit_value(value));
+            }
+            toml_edit::Value::InlineTable(inline_table)
+        },
+    }
+}
+
+// Copies a table parsed from another document, without its position in that document, so the
+// copy is written after the tables preceding it in its new document.
+fn detach(table: &Table) -> Table {
+    let mut detached = Table::new();
+    for (key, item) in table.iter() {
+        let detached_item = match item {
+            &Item::Table(ref table) => Item::Table(detach(table)),
+            &Item::ArrayOfTables(ref tables) => {
+                let mut detached_tables = ArrayOfTables::new();
+                for table in tables.iter() {
+                    detached_tables.push(detach(table));
+                }
+                Item::ArrayOfTables(detached_tables)
+            },
+            other => other.clone(),
+        };
+        detached.insert(key, detached_item);
+    }
+    detached
+}
+
+// Inserts a key after the last key of the table. In an inline table, the space before the closing
+// brace moves after the new key.
+fn insert_key(table: &mut EditedTable, key: &str, mut item: Item) {
+    if table.inline {
+        let suffix = table.table.iter_mut().last().and_then(|(_, last_item)| {
+            last_item.as_value_mut().map(|last_value| {
+                let suffix = last_value.decor().suffix().cloned();
+                last_value.decor_mut().set_suffix("");
+                suffix
+            })
+        });
+        if let (Some(Some(suffix)), Some(value)) = (suffix, item.as_value_mut()) {
+            value.decor_mut().set_prefix(" ");
+            value.decor_mut().set_suffix(suffix);
+        }
+    }
+    table.table.insert(key, item);
+}
+
+// Replaces the value of a key, keeping the comments and spacing around the previous value. A table
+// written with a header stays a table with a header.
+fn replace_value(table: &mut EditedTable, key: &str, value: toml_edit::Value) {
+    let item = match table.table.get_mut(key) {
+        Some(item) => item,
+        None => {
+            insert_key(table, key, Item::Value(value));
+            return;
+        },
+    };
+
+    let replacement = match (&*item, value) {
+        (&Item::Table(ref previous), toml_edit::Value::InlineTable(inline_table)) => {
+            let mut replacement = inline_table.into_table();
+            *replacement.decor_mut() = previous.decor().clone();
+            if let Some(position) = previous.position() {
+                replacement.set_position(position);
+            }
+            Item::Table(replacement)
+        },
+        (&Item::Value(ref previous), mut value) => {
+            *value.decor_mut() = previous.decor().clone();
+            Item::Value(value)
+        },
+        (_, value) => Item::Value(value),
+    };
+    *item = replacement;
+}
+
+// The table of a key of a gameobject, created if missing.
+fn sub_table<'a>(gameobject: &'a mut EditedTable, key: &str) -> DataParserResult<EditedTable<'a>> {
+    if gameobject.table.get(key).is_none() {
+        let table = if gameobject.inline {
+            let mut inline_table = InlineTable::new();
+            inline_table.fmt();
+            Item::Value(toml_edit::Value::InlineTable(inline_table))
+        } else {
+            Item::Table(Table::new())
+        };
+        insert_key(gameobject, key, table);
+    }
+
+    let id = gameobject_id(gameobject.table).unwrap_or("").to_owned();
+    let item = gameobject.table.get_mut(key).expect("the table was inserted");
+    let inline = item.is_inline_table();
+    match item.as_table_like_mut() {
+        Some(table) => Ok(EditedTable {
+            table,
+            inline,
+        }),
+        None => Err(invalid_structure(format!("Gameobject '{}': the key '{}' is not a table.", id, key))),
+    }
+}
+
+fn check_finite(id: &str, field: &str, values: &[f64]) -> DataParserResult<()> {
+    if values.iter().any(|value| !value.is_finite()) {
+        return Err(DataParserError::BuildError(format!("Gameobject '{}': the {} must be finite, found {:?}.", id, field, values)));
+    }
+    Ok(())
+}
+
+fn float_array(values: &[f64]) -> toml_edit::Value {
+    toml_edit::Value::Array(values.iter().cloned().collect::<Array>())
+}
+
+impl DocumentEditor {
+    /// Reads a level or gameobject TOML document to edit.
+    pub fn load_from_toml<R>(reader: &mut R) -> DataParserResult<Self> where
+        R: Read
+    {
+        debug!("Creating a DocumentEditor from toml file.");
+        let mut content = String::new();
+        reader.read_to_string(&mut content)?;
+        DocumentEditor::parse(content.as_ref())
+    }
+
+    /// Reads a level or gameobject TOML file to edit.
+    pub fn load_from_file<P>(path: P) -> DataParserResult<Self> where
+        P: AsRef<Path>
+    {
+        debug!("Creating a DocumentEditor from the toml file {}.", path.as_ref().display());
+        let mut content = String::new();
+        File::open(path.as_ref())?.read_to_string(&mut content)?;
+        DocumentEditor::parse(content.as_ref()).map_err(|error| error.with_source(Some(path.as_ref()), content.as_ref(), DataFormat::Toml))
+    }
+
+    fn parse(content: &str) -> DataParserResult<Self> {
+        let value = data_format::parse(content, DataFormat::Toml)?;
+        let kind = migration::document_kind(&value);
+        // The older documents are edited as long as their content is valid in the current format,
+        // keeping their format_version.
+        let (changed, version) = migration::migrations_change(&value, kind)?;
+        if changed {
+            return Err(DataParserError::MigrationError(format!("The document has the format version {} and must be upgraded to the version {} before being edited.", version, CURRENT_FORMAT_VERSION)));
+        }
+
+        let document = content.parse::<DocumentMut>().map_err(|toml_error| {
+            let mut location = ErrorLocation::at_offset(toml_error.span().map(|span| span.start).unwrap_or(0));
+            location.locate(content, DataFormat::Toml);
+            DataParserError::SyntaxError(format!("Could not parse the TOML document: {}", toml_error.message()), Box::new(location))
+        })?;
+
+        Ok(DocumentEditor {
+            document,
+            kind,
+        })
+    }
+
+    /// The ids of the gameobjects written in the document, in the order of the document.
+    pub fn ids(&self) -> Vec<String> {
+        let mut ids = Vec::new();
+        match self.kind {
+            DocumentKind::Level => {
+                if let Some(gameobjects) = self.document.get("gameobjects") {
+                    collect_ids_in(gameobjects, &mut ids);
+                }
+            },
+            DocumentKind::GameObject => collect_ids(self.document.as_table(), &mut ids),
+        }
+        ids
+    }
+
+    fn gameobject<'a>(&'a mut self, id: &str) -> DataParserResult<EditedTable<'a>> {
+        let found = match self.kind {
+            DocumentKind::Level => self.document.get_mut("gameobjects").and_then(|gameobjects| {
+                gameobject_tables(gameobjects).into_iter().filter_map(|gameobject| find(gameobject, id)).next()
+            }),
+            DocumentKind::GameObject => {
+                let root = EditedTable {
+                    table: self.document.as_table_mut() as &mut dyn TableLike,
+                    inline: false,
+                };
+                find(root, id)
+            },
+        };
+        found.ok_or_else(|| not_found(id))
+    }
+
+    /// Moves a gameobject, relative to its parent.
+    pub fn set_position(&mut self, id: &str, position: [f64; 3]) -> DataParserResult<&mut Self> {
+        debug!("Moving the gameobject {} in the edited document.", id);
+        check_finite(id, "position", &position)?;
+        {
+            let mut gameobject = self.gameobject(id)?;
+            let mut transform = sub_table(&mut gameobject, "transform")?;
+            replace_value(&mut transform, "position", float_array(&position));
+        }
+        Ok(self)
+    }
+
+    /// Replaces the position, rotation and scale of a gameobject. The rotation is written in the
+    /// form of the description.
+    pub fn set_transform(&mut self, id: &str, transform: &TransformDescription) -> DataParserResult<&mut Self> {
+        debug!("Changing the transform of the gameobject {} in the edited document.", id);
+        transform.check().map_err(|description| DataParserError::BuildError(format!("Gameobject '{}': {}", id, description)))?;
+        let rotation = toml::Value::try_from(transform.rotation())?;
+        {
+            let mut gameobject = self.gameobject(id)?;
+            let mut transform_table = sub_table(&mut gameobject, "transform")?;
+            replace_value(&mut transform_table, "position", float_array(&transform.position()));
+            replace_value(&mut transform_table, "rotation", to_edit_value(&rotation));
+            replace_value(&mut transform_table, "scale", float_array(&transform.scale()));
+        }
+        Ok(self)
+    }
+
+    /// Changes the path of the mesh of a gameobject, keeping the selection of the mesh in the asset.
+    pub fn set_mesh_path(&mut self, id: &str, path: &str) -> DataParserResult<&mut Self> {
+        debug!("Changing the mesh of the gameobject {} in the edited document.", id);
+        {
+            let mut gameobject = self.gameobject(id)?;
+            let mut mesh = sub_table(&mut gameobject, "mesh")?;
+            replace_value(&mut mesh, "path", toml_edit::Value::from(path));
+        }
+        Ok(self)
+    }
+
+    /// Appends a gameobject, and its children, to the gameobjects of a level. The new gameobject is
+    /// written as the other gameobjects of the level: with a [[gameobjects]] header, or as an inline table.
+    pub fn add_gameobject(&mut self, gameobject: &GameObjectBuilder) -> DataParserResult<&mut Self> {
+        debug!("Adding the gameobject {} to the edited document.", gameobject.id());
+        if self.kind != DocumentKind::Level {
+            return Err(DataParserError::ReferenceError(format!("The gameobject '{}' can't be added: the document is not a level.", gameobject.id())));
+        }
+        if self.ids().iter().any(|id| id == gameobject.id()) {
+            return Err(DataParserError::HierarchyError(format!("The gameobject '{}' can't be added: a gameobject of the level has this id.", gameobject.id())));
+        }
+
+        let gameobject_document = toml::to_string(gameobject)?.parse::<DocumentMut>().map_err(|toml_error| {
+            DataParserError::BuildError(format!("Could not write the gameobject '{}': {}", gameobject.id(), toml_error.message()))
+        })?;
+        let mut table = detach(gameobject_document.as_table());
+        match self.document.get_mut("gameobjects") {
+            Some(&mut Item::ArrayOfTables(ref mut tables)) => tables.push(table),
+            Some(&mut Item::Value(toml_edit::Value::Array(ref mut array))) => {
+                // The new gameobject starts on its own line if the previous one does.
+                let prefix = array.iter().last().and_then(|last| last.decor().prefix().cloned());
+                let mut inline_table = table.into_inline_table();
+                inline_table.fmt();
+                array.push(inline_table);
+                if let (Some(prefix), Some(value)) = (prefix, array.iter_mut().last()) {
+                    value.decor_mut().set_prefix(prefix);
+                }
+            },
+            Some(_) => return Err(invalid_structure(String::from("The gameobjects of the level are not an array."))),
+            None => {
+                table.set_implicit(false);
+                let mut tables = ArrayOfTables::new();
+                tables.push(table);
+                self.document.insert("gameobjects", Item::ArrayOfTables(tables));
+            },
+        }
+        Ok(self)
+    }
+
+    /// The edited document.
+    pub fn as_string(&self) -> String {
+        self.document.to_string()
+    }
+
+    /// Writes the edited document to the writer.
+    pub fn save<W>(&self, writer: &mut W) -> DataParserResult<()> where
+        W: Write + ?Sized
+    {
+        writer.write_all(self.as_string().as_bytes())?;
+        Ok(())
+    }
+
+    /// Saves the edited document at the given path, replacing the file atomically (see atomic_file.rs).
+    pub fn save_to_file<P>(&self, path: P) -> DataParserResult<()> where
+        P: AsRef<Path>
+    {
+        debug!("Saving the edited document to the file {}.", path.as_ref().display());
+        atomic_file::save(path.as_ref(), |writer| self.save(writer))
+    }
+}
@@ -0,0 +1,453 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use toml;
+use toml::de::Error as DeserializationError;
+use toml_edit::{self, Item, TableLike};
+use serde::de::{Deserialize, DeserializeOwned, Deserializer, MapAccess, Visitor};
+use serde_path_to_error;
+use serde_ignored;
+use serde_json;
+use ron;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::fmt;
+use data_parser_error::{DataParserError, DataParserResult};
+use data_format::DataFormat;
+
+/// Where an error occurred in a data file. The line and column start at 1.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ErrorLocation {
+    file: Option<PathBuf>,
+    line: Option<usize>,
+    column: Option<usize>,
+    key_path: Option<String>,
+    snippet: Option<String>,
+    // The byte offset reported by a parser, turned into a line and a column once the source is known.
+    offset: Option<usize>,
+}
+
+impl ErrorLocation {
+    pub(crate) fn from_toml_error(error: &DeserializationError) -> Self {
+        ErrorLocation {
+            offset: error.span().map(|span| span.start),
+            ..ErrorLocation::default()
+        }
+    }
+
+    // The location of an error reported at a byte offset of the source.
+    pub(crate) fn at_offset(offset: usize) -> Self {
+        ErrorLocation {
+            offset: Some(offset),
+            ..ErrorLocation::default()
+        }
+    }
+
+    // The location of an error reported by the JSON or RON parser, the line and column starting at 1.
+    pub(crate) fn at_position(line: usize, column: usize) -> Self {
+        let mut location = ErrorLocation::default();
+        if line > 0 {
+            location.line = Some(line);
+            location.column = Some(column.max(1));
+        }
+        location
+    }
+
+    // The location of a key path in a document.
+    pub(crate) fn at_key_path<I: Into<String>>(file: Option<&Path>, key_path: I, source: &str, format: DataFormat) -> Self {
+        let mut location = ErrorLocation::default();
+        if let Some(file) = file {
+            location.set_file(file);
+        }
+        location.set_key_path(key_path);
+        location.locate(source, format);
+        location
+    }
+
+    pub(crate) fn set_file(&mut self, file: &Path) {
+        if self.file.is_none() {
+            self.file = Some(file.to_path_buf());
+        }
+    }
+
+    pub(crate) fn set_key_path<I: Into<String>>(&mut self, key_path: I) {
+        let key_path = key_path.into();
+        self.key_path = if key_path.is_empty() { None } else { Some(key_path) };
+    }
+
+    // Find the line of the key path in the source, falling back to the offset reported by the
+    // parser, and keep the offending line.
+    pub(crate) fn locate(&mut self, source: &str, format: DataFormat) {
+        if self.snippet.is_some() {
+            return;
+        }
+
+        let offset = self.key_path.as_ref().and_then(|key_path| find_key_path(source, format, key_path)).or(self.offset);
+        if let Some(offset) = offset {
+            let (line, column) = line_column(source, offset);
+            self.line = Some(line);
+            self.column = Some(column);
+        }
+
+        if let Some(line) = self.line {
+            self.snippet = source.lines().nth(line - 1).map(|snippet| snippet.to_owned());
+        }
+    }
+
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    pub fn column(&self) -> Option<usize> {
+        self.column
+    }
+
+    /// The dotted key path of the offending value, e.g. `gameobjects[3].transform.scale`.
+    pub fn key_path(&self) -> Option<&str> {
+        self.key_path.as_deref()
+    }
+
+    /// The offending line of the file.
+    pub fn snippet(&self) -> Option<&str> {
+        self.snippet.as_deref()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.file.is_none() && self.line.is_none() && self.key_path.is_none()
+    }
+
+    /// The offending line of the file, with a caret under the offending column.
+    pub fn render_snippet(&self) -> Option<String> {
+        let (line, snippet) = match (self.line, self.snippet.as_ref()) {
+            (Some(line), Some(snippet)) => (line, snippet),
+            _ => return None,
+        };
+        let gutter = " ".repeat(line.to_string().len());
+        let caret_offset = " ".repeat(self.column.unwrap_or(1) - 1);
+
+        Some(format!("{} |\n{} | {}\n{} | {}^", gutter, line, snippet, gutter, caret_offset))
+    }
+}
+
+impl fmt::Display for ErrorLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.file {
+            Some(ref file) => write!(f, "{}", file.display())?,
+            None => write!(f, "<input>")?,
+        }
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        if let Some(ref key_path) = self.key_path {
+            write!(f, " (key '{}')", key_path)?;
+        }
+        Ok(())
+    }
+}
+
+// The byte offset of the value of each key path of a TOML document: the offset of its key, or of
+// the header of its table. The [[array of tables]] get their index, e.g. 'gameobjects[1].transform.scale'
+// for the scale of the second [[gameobjects]].
+fn toml_key_paths(table: &dyn TableLike, table_key_path: &str, key_paths: &mut Vec<(String, usize)>) {
+    for (key, item) in table.iter() {
+        let key_path = join_key_paths(table_key_path, key);
+        let key_span = table.get_key_value(key).and_then(|(key, _)| key.span());
+        match item {
+            &Item::Table(ref child) => {
+                if let Some(span) = child.span().or(key_span) {
+                    key_paths.push((key_path.clone(), span.start));
+                }
+                toml_key_paths(child, key_path.as_str(), key_paths);
+            },
+            &Item::ArrayOfTables(ref array) => {
+                for (index, child) in array.iter().enumerate() {
+                    let element_key_path = format!("{}[{}]", key_path, index);
+                    if let Some(span) = child.span() {
+                        key_paths.push((element_key_path.clone(), span.start));
+                    }
+                    toml_key_paths(child, element_key_path.as_str(), key_paths);
+                }
+            },
+            &Item::Value(ref value) => {
+                if let Some(span) = key_span.or_else(|| value.span()) {
+                    key_paths.push((key_path.clone(), span.start));
+                }
+                toml_value_key_paths(value, key_path.as_str(), key_paths);
+            },
+            &Item::None => {},
+        }
+    }
+}
+
+// The byte offset of the elements of the inline arrays and tables of a TOML document.
+fn toml_value_key_paths(value: &toml_edit::Value, key_path: &str, key_paths: &mut Vec<(String, usize)>) {
+    match value {
+        &toml_edit::Value::Array(ref array) => {
+            for (index, element) in array.iter().enumerate() {
+                let element_key_path = format!("{}[{}]", key_path, index);
+                if let Some(span) = element.span() {
+                    key_paths.push((element_key_path.clone(), span.start));
+                }
+                toml_value_key_paths(element, element_key_path.as_str(), key_paths);
+            }
+        },
+        &toml_edit::Value::InlineTable(ref table) => toml_key_paths(table, key_path, key_paths),
+        _ => {},
+    }
+}
+
+// The raw values of the JSON and RON parsers, which borrow their text from the document.
+trait RawText {
+    fn text(&self) -> &str;
+    fn parse<'a, T: Deserialize<'a>>(text: &'a str) -> Option<T>;
+}
+
+impl RawText for serde_json::value::RawValue {
+    fn text(&self) -> &str {
+        self.get()
+    }
+
+    fn parse<'a, T: Deserialize<'a>>(text: &'a str) -> Option<T> {
+        serde_json::from_str(text).ok()
+    }
+}
+
+impl RawText for ron::value::RawValue {
+    // The raw values of RON keep the whitespace and comments around them.
+    fn text(&self) -> &str {
+        self.trim().get_ron()
+    }
+
+    fn parse<'a, T: Deserialize<'a>>(text: &'a str) -> Option<T> {
+        ron::from_str(text).ok()
+    }
+}
+
+// The entries of a JSON object or of a RON struct or map, with the raw text of their values.
+struct RawTable<'a, R: ?Sized + 'a>(Vec<(String, &'a R)>);
+
+impl<'de: 'a, 'a, R: ?Sized + 'a> Deserialize<'de> for RawTable<'a, R> where
+    &'a R: Deserialize<'de>
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RawTableVisitor<'a, R: ?Sized + 'a>(PhantomData<&'a R>);
+
+        impl<'de: 'a, 'a, R: ?Sized + 'a> Visitor<'de> for RawTableVisitor<'a, R> where
+            &'a R: Deserialize<'de>
+        {
+            type Value = RawTable<'a, R>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a table")
+            }
+
+            fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, M::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(RawTable(entries))
+            }
+        }
+
+        // The RON structs are only recognized as tables by deserialize_any.
+        deserializer.deserialize_any(RawTableVisitor(PhantomData))
+    }
+}
+
+// The byte offset of the value of each key path of a JSON or RON document, found by parsing the
+// tables and arrays again from the raw text of their values.
+fn raw_key_paths<'a, R>(source: &str, text: &'a str, key_path: &str, key_paths: &mut Vec<(String, usize)>) where
+    R: RawText + ?Sized + 'a,
+    &'a R: Deserialize<'a>,
+{
+    if !key_path.is_empty() {
+        key_paths.push((key_path.to_owned(), text.as_ptr() as usize - source.as_ptr() as usize));
+    }
+
+    if let Some(RawTable(entries)) = R::parse::<RawTable<R>>(text) {
+        for (key, value) in entries {
+            raw_key_paths::<R>(source, value.text(), join_key_paths(key_path, key.as_str()).as_str(), key_paths);
+        }
+    } else if let Some(elements) = R::parse::<Vec<&R>>(text) {
+        for (index, element) in elements.into_iter().enumerate() {
+            raw_key_paths::<R>(source, element.text(), format!("{}[{}]", key_path, index).as_str(), key_paths);
+        }
+    }
+}
+
+fn key_paths(source: &str, format: DataFormat) -> Vec<(String, usize)> {
+    let mut key_paths = Vec::new();
+    match format {
+        DataFormat::Toml => {
+            if let Ok(document) = toml_edit::Document::parse(source) {
+                toml_key_paths(document.as_table(), "", &mut key_paths);
+            }
+        },
+        DataFormat::Json => raw_key_paths::<serde_json::value::RawValue>(source, source, "", &mut key_paths),
+        DataFormat::Ron => raw_key_paths::<ron::value::RawValue>(source, source, "", &mut key_paths),
+    }
+    key_paths
+}
+
+// The byte offset of the key path, or of its closest parent found in the source.
+fn find_key_path(source: &str, format: DataFormat, key_path: &str) -> Option<usize> {
+    let mut best: Option<(usize, usize)> = None;
+
+    for (path, offset) in key_paths(source, format) {
+        let matches = key_path == path || (key_path.starts_with(path.as_str()) && {
+            let next = key_path[path.len()..].chars().next();
+            next == Some('.') || next == Some('[')
+        });
+
+        if matches && best.map(|(length, _)| path.len() > length).unwrap_or(true) {
+            best = Some((path.len(), offset));
+        }
+    }
+
+    best.map(|(_, offset)| offset)
+}
+
+// The line and column of a byte offset of the source, starting at 1.
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let line_start = source[..offset].rfind('\n').map(|index| index + 1).unwrap_or(0);
+    let line = source[..offset].matches('\n').count() + 1;
+    let column = source[line_start..offset].chars().count() + 1;
+    (line, column)
+}
+
+fn key_path_to_string<E>(error: &serde_path_to_error::Error<E>) -> String {
+    let key_path = error.path().to_string();
+    // The root of the document is displayed as '.'.
+    if key_path == "." || key_path == "?" {
+        String::new()
+    } else {
+        key_path
+    }
+}
+
+// The key path of an ignored key, in the same format as the paths of serde_path_to_error.
+fn ignored_key_path(path: &serde_ignored::Path) -> String {
+    match path {
+        &serde_ignored::Path::Root => String::new(),
+        &serde_ignored::Path::Seq { parent, index } => format!("{}[{}]", ignored_key_path(parent), index),
+        &serde_ignored::Path::Map { parent, ref key } => {
+            let parent_path = ignored_key_path(parent);
+            if parent_path.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", parent_path, key)
+            }
+        },
+        &serde_ignored::Path::Some { parent } |
+        &serde_ignored::Path::NewtypeStruct { parent } |
+        &serde_ignored::Path::NewtypeVariant { parent } => ignored_key_path(parent),
+    }
+}
+
+fn join_key_paths(key_path: &str, relative_key_path: &str) -> String {
+    if key_path.is_empty() || relative_key_path.is_empty() || relative_key_path.starts_with('[') {
+        format!("{}{}", key_path, relative_key_path)
+    } else {
+        format!("{}.{}", key_path, relative_key_path)
+    }
+}
+
+// Deserialize a TOML document, locating the errors in the document. The key paths of
+// the keys which don't match any field are pushed in unknown_keys.
+pub(crate) fn deserialize_str<T>(source: &str, unknown_keys: &mut Vec<String>) -> DataParserResult<T> where
+    T: DeserializeOwned
+{
+    let deserializer = toml::Deserializer::parse(source).map_err(|error| located_toml_error(error, String::new(), source))?;
+    let mut callback = |path: serde_ignored::Path| unknown_keys.push(ignored_key_path(&path));
+    let ignored_deserializer = serde_ignored::Deserializer::new(deserializer, &mut callback);
+
+    serde_path_to_error::deserialize(ignored_deserializer).map_err(|error| {
+        let key_path = key_path_to_string(&error);
+        located_toml_error(error.into_inner(), key_path, source)
+    })
+}
+
+// The snippet of the source is rendered by the location, not by the error of the TOML parser.
+fn located_toml_error(mut error: DeserializationError, key_path: String, source: &str) -> DataParserError {
+    error.set_input(None);
+    let mut location = ErrorLocation::from_toml_error(&error);
+    location.set_key_path(key_path);
+    location.locate(source, DataFormat::Toml);
+    DataParserError::from(error).with_location(location)
+}
+
+// Deserialize a TOML value, found at the given key path of its document. The error can be
+// located afterwards with DataParserError::with_source. The key paths of the keys which don't
+// match any field are pushed in unknown_keys.
+pub(crate) fn deserialize_value<T>(value: toml::Value, key_path: &str, unknown_keys: &mut Vec<String>) -> DataParserResult<T> where
+    T: DeserializeOwned
+{
+    let mut callback = |path: serde_ignored::Path| unknown_keys.push(join_key_paths(key_path, ignored_key_path(&path).as_str()));
+    let ignored_deserializer = serde_ignored::Deserializer::new(value, &mut callback);
+
+    serde_path_to_error::deserialize(ignored_deserializer).map_err(|error| {
+        let full_key_path = join_key_paths(key_path, key_path_to_string(&error).as_str());
+        let mut location = ErrorLocation::default();
+        location.set_key_path(full_key_path);
+        DataParserError::from(error.into_inner()).with_location(location)
+    })
+}
+
+#[cfg(test)]
+mod error_location_test {
+    use super::*;
+
+    fn position(source: &str, format: DataFormat, key_path: &str) -> Option<(usize, usize)> {
+        find_key_path(source, format, key_path).map(|offset| line_column(source, offset))
+    }
+
+    #[test]
+    fn find_key_paths() {
+        let source = "title = \"level\"\n\n[[gameobjects]]\nid = \"a\"\n\n[[gameobjects]]\nid = \"b\"\n\n[gameobjects.transform]\n  scale = [1.0]\n\n[[gameobjects.children]]\nid = \"c\"\n";
+
+        assert_eq!(position(source, DataFormat::Toml, "title"), Some((1, 1)));
+        assert_eq!(position(source, DataFormat::Toml, "gameobjects[0]"), Some((3, 1)));
+        assert_eq!(position(source, DataFormat::Toml, "gameobjects[1].transform.scale"), Some((10, 3)));
+        assert_eq!(position(source, DataFormat::Toml, "gameobjects[1].transform.position"), Some((9, 1)));
+        assert_eq!(position(source, DataFormat::Toml, "gameobjects[1].children[0].id"), Some((13, 1)));
+        assert_eq!(position(source, DataFormat::Toml, "unknown"), None);
+    }
+
+    #[test]
+    fn find_key_paths_after_multiline_arrays() {
+        let source = "[[gameobjects]]\nid = \"a\"\ntags = [\n  \"first\",\n  [\"nested\"],\n]\nmesh = { path = \"a.gltf\", lod = [\n  1,\n] }\n\n[[gameobjects]]\nid = \"b\"\n";
+
+        assert_eq!(position(source, DataFormat::Toml, "gameobjects[0].tags[1]"), Some((5, 3)));
+        assert_eq!(position(source, DataFormat::Toml, "gameobjects[0].mesh.lod"), Some((7, 27)));
+        assert_eq!(position(source, DataFormat::Toml, "gameobjects[1].id"), Some((12, 1)));
+    }
+
+    #[test]
+    fn find_key_paths_in_json_and_ron() {
+        let json = "{\n  \"title\": \"level\",\n  \"gameobjects\": [\n    {\n      \"id\": \"a\",\n      \"transform\": { \"scale\": [1.0] }\n    }\n  ]\n}\n";
+        assert_eq!(position(json, DataFormat::Json, "title"), Some((2, 12)));
+        assert_eq!(position(json, DataFormat::Json, "gameobjects[0].transform.scale"), Some((6, 31)));
+        assert_eq!(position(json, DataFormat::Json, "gameobjects[0].transform.position"), Some((6, 20)));
+
+        let ron = "(\n    title: \"level\",\n    gameobjects: [\n        (\n            id: \"a\", // the first one\n            transform: (scale: [1.0]),\n        ),\n    ],\n)\n";
+        assert_eq!(position(ron, DataFormat::Ron, "title"), Some((2, 12)));
+        assert_eq!(position(ron, DataFormat::Ron, "gameobjects[0].transform.scale"), Some((6, 32)));
+        assert_eq!(position(ron, DataFormat::Ron, "gameobjects[0].transform"), Some((6, 24)));
+    }
+}
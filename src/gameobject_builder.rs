@@ -12,50 +12,311 @@ use std::io::{Read, Write};
 
 use maskerad_gameobject_model::gameobject::GameObject;
 use mesh_description::MeshDescription;
-use transform_description::TransformDescription;
+use component_description::{LightDescription, ColliderDescription, ScriptDescription};
+use transform_description::{TransformDescription, TransformData};
+use serde::{Deserialize, Deserializer};
+use serde::de;
+use reference_resolver::ReferenceResolver;
+use error_location::{self, ErrorLocation};
+use diagnostic::Diagnostic;
+use unknown_key::{self, UnknownKeyMode, DocumentKind};
+use migration::{self, Versioned};
+use data_format::{self, DataFormat};
+use canonical::SerializationMode;
+use atomic_file;
+use asset_path::PathResolver;
+use virtual_filesystem::{VirtualFilesystem, NativeFilesystem};
+use binary_format;
 
 
 
 /*
     Gameobject file structure:
+    format_version = 2
     id = "game object name"
 
     [transform]
     position = [x, y, z]
-    rot = [x, y, z]
+    rotation = [x, y, z]
     scale = [x, y, z]
 
     [mesh]
     path = "path to mesh"
     ...
+
+    A gameobject can have tags, a light, a collider and scripts (see component_description):
+    tags = ["tag1", "tag2"]
+
+    [light]
+    ...
+
+    A gameobject can declare its parent by id:
+    parent = "parent game object name"
+
+    Or nest its children:
+    [[children]]
+    id = "child game object name"
+
+    [children.transform]
+    ...
 */
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone)]
 pub struct GameObjectBuilder {
     id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
     transform: TransformDescription,
     mesh: Option<MeshDescription>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    light: Option<LightDescription>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    collider: Option<ColliderDescription>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    scripts: Vec<ScriptDescription>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<GameObjectBuilder>,
+}
+
+// The gameobject, as written in the file, before its transform is validated.
+#[derive(Deserialize, Debug)]
+struct GameObjectData {
+    id: String,
+    parent: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    transform: TransformData,
+    mesh: Option<MeshDescription>,
+    light: Option<LightDescription>,
+    collider: Option<ColliderDescription>,
+    #[serde(default)]
+    scripts: Vec<ScriptDescription>,
+    #[serde(default)]
+    children: Vec<GameObjectBuilder>,
+    prefab: Option<String>,
+}
+
+impl<'de> Deserialize<'de> for GameObjectBuilder {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where
+        D: Deserializer<'de>
+    {
+        let GameObjectData { id, parent, tags, transform, mesh, light, collider, scripts, mut children, prefab } = GameObjectData::deserialize(deserializer)?;
+        if let Some(prefab) = prefab {
+            return Err(de::Error::custom(format!("Gameobject '{}' is an instance of the prefab '{}', which can only be resolved when the file is loaded with load_from_file.", id, prefab)));
+        }
+
+        let transform = transform.validate().map_err(|message| {
+            de::Error::custom(format!("Gameobject '{}': {}", id, message))
+        })?;
+
+        let components = light.as_ref().map_or(Ok(()), |light| light.check())
+            .and_then(|_| collider.as_ref().map_or(Ok(()), |collider| collider.check()));
+        components.map_err(|message| {
+            de::Error::custom(format!("Gameobject '{}': {}", id, message))
+        })?;
+
+        for child in children.iter_mut() {
+            match child.parent {
+                Some(ref child_parent) if *child_parent != id => {
+                    return Err(de::Error::custom(format!("Gameobject '{}' is nested in '{}', but declares '{}' as its parent.", child.id, id, child_parent)));
+                },
+                _ => {},
+            }
+            child.parent = Some(id.clone());
+        }
+
+        Ok(GameObjectBuilder {
+            id,
+            parent,
+            tags,
+            transform,
+            mesh,
+            light,
+            collider,
+            scripts,
+            children,
+        })
+    }
 }
 
 impl GameObjectBuilder {
     pub fn load_from_toml<R>(reader: &mut R) -> DataParserResult<Self> where
         R: Read
     {
-        debug!("Creating a GameObjectBuilder from toml file.");
+        GameObjectBuilder::load_from_toml_with_mode(reader, UnknownKeyMode::Lenient).map(|(gameobject, _)| gameobject)
+    }
+
+    /// Loads a gameobject, handling the unknown keys according to the mode. The unknown keys are
+    /// returned as warnings in lenient mode.
+    pub fn load_from_toml_with_mode<R>(reader: &mut R, mode: UnknownKeyMode) -> DataParserResult<(Self, Vec<Diagnostic>)> where
+        R: Read
+    {
+        GameObjectBuilder::load_from_reader_with_mode(reader, DataFormat::Toml, mode)
+    }
+
+    /// Loads a gameobject written in the given format (see data_format.rs).
+    pub fn load_from_reader<R>(reader: &mut R, format: DataFormat) -> DataParserResult<Self> where
+        R: Read
+    {
+        GameObjectBuilder::load_from_reader_with_mode(reader, format, UnknownKeyMode::Lenient).map(|(gameobject, _)| gameobject)
+    }
+
+    /// Loads a gameobject written in the given format, handling the unknown keys according to the mode.
+    pub fn load_from_reader_with_mode<R>(reader: &mut R, format: DataFormat, mode: UnknownKeyMode) -> DataParserResult<(Self, Vec<Diagnostic>)> where
+        R: Read
+    {
+        debug!("Creating a GameObjectBuilder from a {:?} document.", format);
 
         let mut content = String::new();
         trace!("Reading content of the bufreader in string...");
         reader.read_to_string(&mut content)?;
 
-        toml::from_str(content.as_ref()).map_err(|deserialization_error| {
-            DataParserError::from(deserialization_error)
-        })
+        let mut unknown_keys = Vec::new();
+        let result = data_format::parse(content.as_ref(), format)
+            .and_then(|value| migration::migrate(value, DocumentKind::GameObject))
+            .and_then(|(value, _)| {
+                GameObjectBuilder::from_value(value, "", &mut unknown_keys).map_err(|error| {
+                    error.with_source(None, content.as_ref(), format)
+                })
+            });
+        let unknown_keys = unknown_keys
+            .into_iter()
+            .map(|key_path| unknown_key::diagnostic(DocumentKind::GameObject, ErrorLocation::at_key_path(None, key_path, content.as_ref(), format)))
+            .collect();
+        unknown_key::apply_mode(mode, result, unknown_keys)
+    }
+
+    // Deserializes a gameobject found at the given key path of its document, pushing the key paths
+    // of its unknown keys in unknown_keys.
+    pub(crate) fn from_value(value: toml::Value, key_path: &str, unknown_keys: &mut Vec<String>) -> DataParserResult<Self> {
+        unknown_key::buffered_unknown_keys(&value, key_path, unknown_keys);
+        error_location::deserialize_value(value, key_path, unknown_keys)
+    }
+
+    /// Loads a gameobject written in the binary format (see binary_format.rs).
+    pub fn load_from_binary<R>(reader: &mut R) -> DataParserResult<Self> where
+        R: Read
+    {
+        debug!("Creating a GameObjectBuilder from binary data.");
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        binary_format::decode_gameobject(data.as_slice())
+    }
+
+    pub fn load_from_file<P>(path: P) -> DataParserResult<Self> where
+        P: AsRef<Path>
+    {
+        GameObjectBuilder::load_from_file_with_mode(path, UnknownKeyMode::Lenient).map(|(gameobject, _)| gameobject)
+    }
+
+    /// Loads a gameobject file and its prefab, handling the unknown keys according to the mode.
+    /// The unknown keys are returned as warnings in lenient mode.
+    pub fn load_from_file_with_mode<P>(path: P, mode: UnknownKeyMode) -> DataParserResult<(Self, Vec<Diagnostic>)> where
+        P: AsRef<Path>
+    {
+        GameObjectBuilder::load_from_file_with_resolver(path, mode, &PathResolver::default())
+    }
+
+    /// Loads a gameobject file and its prefab, the asset paths being resolved by the PathResolver.
+    /// The relative mesh paths of the loaded gameobject are relative to the gameobject file.
+    pub fn load_from_file_with_resolver<P>(path: P, mode: UnknownKeyMode, paths: &PathResolver) -> DataParserResult<(Self, Vec<Diagnostic>)> where
+        P: AsRef<Path>
+    {
+        GameObjectBuilder::load_from_filesystem(&NativeFilesystem, path, mode, paths)
+    }
+
+    /// Loads a gameobject file and its prefab from the filesystem (see virtual_filesystem.rs).
+    pub fn load_from_filesystem<F, P>(filesystem: &F, path: P, mode: UnknownKeyMode, paths: &PathResolver) -> DataParserResult<(Self, Vec<Diagnostic>)> where
+        F: VirtualFilesystem,
+        P: AsRef<Path>,
+    {
+        debug!("Creating a GameObjectBuilder from the toml file {}.", path.as_ref().display());
+        let mut resolver = ReferenceResolver::new(filesystem, paths);
+        let result = resolver.resolve_gameobject(path.as_ref());
+        unknown_key::apply_mode(mode, result, resolver.unknown_keys)
+    }
+
+    /// Upgrades a gameobject file to the current format version, rewriting it if it had an older version.
+    /// Its prefab is not upgraded. Returns whether the file was rewritten.
+    pub fn migrate_file<P>(path: P) -> DataParserResult<bool> where
+        P: AsRef<Path>
+    {
+        migration::migrate_file(path.as_ref(), DocumentKind::GameObject)
     }
 
     pub fn as_string_toml(&self) -> DataParserResult<String> {
-        debug!("Getting a string representation of the GameObjectBuilder.");
-        let toml_string = toml::to_string(&self)?;
-        Ok(toml_string)
+        self.as_string(DataFormat::Toml)
+    }
+
+    /// The string representation of the gameobject in TOML, in its canonical form if asked (see canonical.rs).
+    pub fn as_string_toml_with_mode(&self, mode: SerializationMode) -> DataParserResult<String> {
+        self.as_string_with_mode(DataFormat::Toml, mode)
+    }
+
+    /// The string representation of the gameobject in the given format (see data_format.rs).
+    pub fn as_string(&self, format: DataFormat) -> DataParserResult<String> {
+        self.as_string_with_mode(format, SerializationMode::Verbatim)
+    }
+
+    /// The string representation of the gameobject in the given format and serialization mode.
+    pub fn as_string_with_mode(&self, format: DataFormat, mode: SerializationMode) -> DataParserResult<String> {
+        debug!("Getting a {:?} representation of the GameObjectBuilder, in the {:?} mode.", format, mode);
+        data_format::to_string_with_mode(&Versioned::new(self), format, mode)
+    }
+
+    /// Writes the gameobject to the writer, in the given format.
+    pub fn save<W>(&self, writer: &mut W, format: DataFormat) -> DataParserResult<()> where
+        W: Write + ?Sized
+    {
+        self.save_with_mode(writer, format, SerializationMode::Verbatim)
+    }
+
+    /// Writes the gameobject to the writer, in the given format and serialization mode.
+    pub fn save_with_mode<W>(&self, writer: &mut W, format: DataFormat, mode: SerializationMode) -> DataParserResult<()> where
+        W: Write + ?Sized
+    {
+        debug!("Writing the GameObjectBuilder as a {:?} document.", format);
+        let document = self.as_string_with_mode(format, mode)?;
+        writer.write_all(document.as_bytes())?;
+        Ok(())
+    }
+
+    /// Saves the gameobject at the given path, in the format of the extension of the file. The file is
+    /// replaced atomically: if the saving fails, the previous file is left untouched.
+    pub fn save_to_file<P>(&self, path: P) -> DataParserResult<()> where
+        P: AsRef<Path>
+    {
+        self.save_to_file_with_mode(path, SerializationMode::Verbatim)
+    }
+
+    /// Saves the gameobject at the given path, in the format of the extension of the file and the given
+    /// serialization mode.
+    pub fn save_to_file_with_mode<P>(&self, path: P, mode: SerializationMode) -> DataParserResult<()> where
+        P: AsRef<Path>
+    {
+        debug!("Saving the GameObjectBuilder to the file {}.", path.as_ref().display());
+        let format = DataFormat::of_file(path.as_ref());
+        atomic_file::save(path.as_ref(), |writer| self.save_with_mode(writer, format, mode))
+    }
+
+    /// The gameobject in the binary format (see binary_format.rs).
+    pub fn as_binary(&self) -> DataParserResult<Vec<u8>> {
+        debug!("Getting a binary representation of the GameObjectBuilder.");
+        binary_format::encode_gameobject(self)
+    }
+
+    /// The string representation of the gameobject to save at the given path, the absolute mesh paths
+    /// being rewritten as asset paths or paths relative to the file.
+    pub fn as_string_toml_portable<P>(&self, path: P, paths: &PathResolver) -> DataParserResult<String> where
+        P: AsRef<Path>
+    {
+        debug!("Getting a portable string representation of the GameObjectBuilder.");
+        let directory = path.as_ref().parent().unwrap_or(Path::new(""));
+        let mut gameobject = self.clone();
+        gameobject.map_mesh_paths(&mut |mesh_path| paths.portable(mesh_path, directory));
+        gameobject.as_string_toml()
     }
 
 
@@ -65,11 +326,31 @@ impl GameObjectBuilder {
         debug!("Creating a new GameObjectBuilder.");
         GameObjectBuilder {
             id: id.into(),
+            parent: None,
+            tags: Vec::new(),
             transform: TransformDescription::default(),
             mesh: None,
+            light: None,
+            collider: None,
+            scripts: Vec::new(),
+            children: Vec::new(),
         }
     }
 
+    pub fn add_parent<I: Into<String>>(&mut self, parent: I) -> &mut Self {
+        debug!("Adding a parent to the GameObjectBuilder.");
+        self.parent = Some(parent.into());
+        self
+    }
+
+    pub fn add_child<I: Into<GameObjectBuilder>>(&mut self, child: I) -> &mut Self {
+        debug!("Adding a child GameObjectBuilder to the GameObjectBuilder.");
+        let mut child = child.into();
+        child.parent = Some(self.id.clone());
+        self.children.push(child);
+        self
+    }
+
     pub fn add_transform<M: Into<TransformDescription>>(&mut self, transform: M) -> &mut Self {
         debug!("Adding a TransformDescription to the GameObjectBuilder.");
         self.transform = transform.into();
@@ -82,20 +363,96 @@ impl GameObjectBuilder {
         self
     }
 
-    //TODO: the gameobject takes care of creating all the component and give them to the resource manager ?
-    //TODO: the service locator ?
-    pub fn build(&self) {
-        unimplemented!()
+    pub fn add_tag<I: Into<String>>(&mut self, tag: I) -> &mut Self {
+        debug!("Adding a tag to the GameObjectBuilder.");
+        let tag = tag.into();
+        if !self.tags.contains(&tag) {
+            self.tags.push(tag);
+        }
+        self
+    }
+
+    pub fn add_light<L: Into<Option<LightDescription>>>(&mut self, light: L) -> &mut Self {
+        debug!("Adding a LightDescription to the GameObjectBuilder.");
+        self.light = light.into();
+        self
+    }
+
+    pub fn add_collider<C: Into<Option<ColliderDescription>>>(&mut self, collider: C) -> &mut Self {
+        debug!("Adding a ColliderDescription to the GameObjectBuilder.");
+        self.collider = collider.into();
+        self
+    }
+
+    pub fn add_script(&mut self, script: ScriptDescription) -> &mut Self {
+        debug!("Adding a ScriptDescription to the GameObjectBuilder.");
+        self.scripts.push(script);
+        self
+    }
+
+    pub fn build(&self) -> DataParserResult<GameObject> {
+        debug!("Creating a GameObject from the GameObjectBuilder.");
+        if self.id.trim().is_empty() {
+            return Err(DataParserError::BuildError(format!("A gameobject must have a non-empty id.")));
+        }
+
+        let transform = self.transform.build().map_err(|error| {
+            error.with_context(format!("Gameobject '{}'", self.id))
+        })?;
+
+        // The runtime gameobject has no components yet: building it would silently drop them.
+        let mut components = Vec::new();
+        if !self.tags.is_empty() {
+            components.push("tags");
+        }
+        if self.light.is_some() {
+            components.push("a light");
+        }
+        if self.collider.is_some() {
+            components.push("a collider");
+        }
+        if !self.scripts.is_empty() {
+            components.push("scripts");
+        }
+        if !components.is_empty() {
+            return Err(DataParserError::BuildError(format!("The gameobject '{}' has {}, but only the transform and the mesh of a gameobject can be built.", self.id, components.join(", "))));
+        }
+
+        let mut gameobject = GameObject::new(self.id.as_str(), transform);
+
+        if let Some(ref mesh) = self.mesh {
+            let mesh = mesh.build().map_err(|error| {
+                error.with_context(format!("Gameobject '{}'", self.id))
+            })?;
+            gameobject.add_mesh(mesh);
+        }
+
+        Ok(gameobject)
     }
 
     //TODO: we need to refine all that stuff, it fucking sucks.
     pub fn get_mesh_resource(&self) -> Option<String> {
-        match self.mesh {
-            Some(ref mesh) => {
-                Some(mesh.path().to_owned())
-            },
-            None => None,
-        }
+        self.mesh.as_ref().map(|mesh| mesh.path().to_owned())
+    }
+
+    pub fn mesh(&self) -> Option<&MeshDescription> {
+        self.mesh.as_ref()
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn light(&self) -> Option<&LightDescription> {
+        self.light.as_ref()
+    }
+
+    pub fn collider(&self) -> Option<&ColliderDescription> {
+        self.collider.as_ref()
+    }
+
+    pub fn scripts(&self) -> &[ScriptDescription] {
+        &self.scripts
     }
 
     pub fn id(&self) -> &str {
@@ -105,14 +462,38 @@ impl GameObjectBuilder {
     pub fn transform(&self) -> &TransformDescription {
         &self.transform
     }
+
+    pub fn parent(&self) -> Option<&str> {
+        self.parent.as_deref()
+    }
+
+    pub fn children(&self) -> &[GameObjectBuilder] {
+        &self.children
+    }
+
+    // Replaces the mesh paths of the GameObjectBuilder and of its nested children.
+    pub(crate) fn map_mesh_paths<F>(&mut self, function: &mut F) where
+        F: FnMut(&str) -> String
+    {
+        if let Some(ref mut mesh) = self.mesh {
+            let path = function(mesh.path());
+            mesh.set_path(path);
+        }
+        for child in self.children.iter_mut() {
+            child.map_mesh_paths(function);
+        }
+    }
+
+    // Moves the nested children out of the GameObjectBuilder, their parent being already set.
+    pub(crate) fn take_children(&mut self) -> Vec<GameObjectBuilder> {
+        ::std::mem::take(&mut self.children)
+    }
 }
 
 
 
 #[cfg(test)]
 mod gameobject_description_test {
-    use super::*;
-
     /*
     #[test]
     fn deserialize_gameobject_builder() {
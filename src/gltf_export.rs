@@ -0,0 +1,362 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use gltf;
+use gltf::json::{self, validation};
+use gltf::json::root::Index;
+use base64;
+use std::path::{Path, PathBuf};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use data_parser_error::{DataParserError, DataParserResult};
+use level_description::LevelDescription;
+use gameobject_builder::GameObjectBuilder;
+use mesh_description::{MeshSelection, GltfReference};
+use asset_path::{self, PathResolver};
+use atomic_file;
+
+/*
+    A level is exported as a glTF document with one scene, the title of the level:
+    - each gameobject becomes a node named by its id, with the local transform of the gameobject,
+      and the children of the gameobject as children,
+    - the mesh of a gameobject is copied from its glTF asset: a selected mesh (or primitive) becomes
+      the mesh of the node, a selected node or scene (or the default scene of the asset) is copied
+      as children of the node,
+    - the materials, cameras, skins and animations of the assets are not exported.
+*/
+
+/// How the buffers of the meshes are written in an exported glTF document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GltfExportMode {
+    /// The buffers are embedded in the document, which can be moved alone.
+    Embed,
+    /// The buffers stored in external files are referenced by a path relative to the document.
+    /// The other buffers are embedded.
+    Reference,
+}
+
+impl Default for GltfExportMode {
+    fn default() -> Self {
+        GltfExportMode::Embed
+    }
+}
+
+// A glTF asset used by the meshes of the level, with the content of its buffers.
+struct SourceAsset {
+    path: PathBuf,
+    root: json::Root,
+    buffers: Vec<Vec<u8>>,
+}
+
+fn empty_node(name: Option<String>) -> json::Node {
+    json::Node {
+        camera: None,
+        children: None,
+        extensions: None,
+        extras: Default::default(),
+        matrix: None,
+        mesh: None,
+        name,
+        rotation: None,
+        scale: None,
+        translation: None,
+        skin: None,
+        weights: None,
+    }
+}
+
+fn data_uri(data: &[u8]) -> String {
+    format!("data:application/octet-stream;base64,{}", base64::encode(data))
+}
+
+fn find<T, F>(items: &[T], reference: &GltfReference, name: F) -> Option<usize> where
+    F: Fn(&T) -> Option<&str>
+{
+    items.iter().enumerate().position(|(index, item)| reference.matches(index, name(item)))
+}
+
+struct Exporter<'a> {
+    root: json::Root,
+    mode: GltfExportMode,
+    assets_directory: &'a Path,
+    paths: &'a PathResolver,
+    output_directory: PathBuf,
+    assets: Vec<SourceAsset>,
+    buffers: HashMap<(usize, usize), u32>,
+    views: HashMap<(usize, usize), u32>,
+    accessors: HashMap<(usize, usize), u32>,
+    meshes: HashMap<(usize, usize, Option<usize>), u32>,
+    // The nodes being copied, with their ancestors.
+    copied_nodes: HashSet<(usize, usize)>,
+}
+
+impl<'a> Exporter<'a> {
+    fn load_asset(&mut self, path: PathBuf) -> DataParserResult<usize> {
+        if let Some(index) = self.assets.iter().position(|asset| asset.path == path) {
+            return Ok(index);
+        }
+
+        let (document, buffers, _) = gltf::import(path.as_path()).map_err(|gltf_error| {
+            DataParserError::GltfError(format!("Could not import the glTF asset '{}'.", path.display()), gltf_error)
+        })?;
+
+        self.assets.push(SourceAsset {
+            path,
+            root: document.into_json(),
+            buffers: buffers.into_iter().map(|buffer| buffer.0).collect(),
+        });
+        Ok(self.assets.len() - 1)
+    }
+
+    fn copy_buffer(&mut self, asset: usize, index: Index<json::Buffer>) -> Index<json::Buffer> {
+        if let Some(&copy) = self.buffers.get(&(asset, index.value())) {
+            return Index::new(copy);
+        }
+
+        let source = &self.assets[asset];
+        let buffer = &source.root.buffers[index.value()];
+        let data = &source.buffers[index.value()][..buffer.byte_length as usize];
+        let uri = match (self.mode, buffer.uri.as_ref()) {
+            (GltfExportMode::Reference, Some(uri)) if !uri.starts_with("data:") => {
+                let buffer_path = source.path.parent().unwrap_or(Path::new("")).join(uri);
+                fs::canonicalize(buffer_path)
+                    .ok()
+                    .and_then(|buffer_path| asset_path::relative_path(self.output_directory.as_path(), buffer_path.as_path()))
+                    .unwrap_or_else(|| data_uri(data))
+            },
+            _ => data_uri(data),
+        };
+
+        self.root.buffers.push(json::Buffer {
+            byte_length: buffer.byte_length,
+            name: buffer.name.clone(),
+            uri: Some(uri),
+            extensions: None,
+            extras: Default::default(),
+        });
+        let copy = (self.root.buffers.len() - 1) as u32;
+        self.buffers.insert((asset, index.value()), copy);
+        Index::new(copy)
+    }
+
+    fn copy_view(&mut self, asset: usize, index: Index<json::buffer::View>) -> Index<json::buffer::View> {
+        if let Some(&copy) = self.views.get(&(asset, index.value())) {
+            return Index::new(copy);
+        }
+
+        let mut view = self.assets[asset].root.buffer_views[index.value()].clone();
+        view.buffer = self.copy_buffer(asset, view.buffer);
+        self.root.buffer_views.push(view);
+        let copy = (self.root.buffer_views.len() - 1) as u32;
+        self.views.insert((asset, index.value()), copy);
+        Index::new(copy)
+    }
+
+    fn copy_accessor(&mut self, asset: usize, index: Index<json::Accessor>) -> Index<json::Accessor> {
+        if let Some(&copy) = self.accessors.get(&(asset, index.value())) {
+            return Index::new(copy);
+        }
+
+        let mut accessor = self.assets[asset].root.accessors[index.value()].clone();
+        accessor.buffer_view = accessor.buffer_view.map(|view| self.copy_view(asset, view));
+        if let Some(ref mut sparse) = accessor.sparse {
+            sparse.indices.buffer_view = self.copy_view(asset, sparse.indices.buffer_view);
+            sparse.values.buffer_view = self.copy_view(asset, sparse.values.buffer_view);
+        }
+        self.root.accessors.push(accessor);
+        let copy = (self.root.accessors.len() - 1) as u32;
+        self.accessors.insert((asset, index.value()), copy);
+        Index::new(copy)
+    }
+
+    // Copies a mesh without its materials, or only one of its primitives.
+    fn copy_mesh(&mut self, asset: usize, index: usize, primitive: Option<usize>) -> Index<json::Mesh> {
+        if let Some(&copy) = self.meshes.get(&(asset, index, primitive)) {
+            return Index::new(copy);
+        }
+
+        let mut mesh = self.assets[asset].root.meshes[index].clone();
+        if let Some(primitive) = primitive {
+            mesh.primitives = vec![mesh.primitives[primitive].clone()];
+        }
+
+        for primitive in mesh.primitives.iter_mut() {
+            primitive.material = None;
+            for accessor in primitive.attributes.values_mut() {
+                *accessor = self.copy_accessor(asset, *accessor);
+            }
+            primitive.indices = primitive.indices.map(|accessor| self.copy_accessor(asset, accessor));
+            if let Some(ref mut targets) = primitive.targets {
+                for target in targets.iter_mut() {
+                    target.positions = target.positions.map(|accessor| self.copy_accessor(asset, accessor));
+                    target.normals = target.normals.map(|accessor| self.copy_accessor(asset, accessor));
+                    target.tangents = target.tangents.map(|accessor| self.copy_accessor(asset, accessor));
+                }
+            }
+        }
+
+        self.root.meshes.push(mesh);
+        let copy = (self.root.meshes.len() - 1) as u32;
+        self.meshes.insert((asset, index, primitive), copy);
+        Index::new(copy)
+    }
+
+    // Copies a node of an asset, with its mesh and its children.
+    fn copy_node(&mut self, asset: usize, index: usize) -> DataParserResult<Index<json::Node>> {
+        if !self.copied_nodes.insert((asset, index)) {
+            let gltf_error = gltf::Error::Validation(vec![(json::Path::new().field("nodes").index(index), validation::Error::Invalid)]);
+            return Err(DataParserError::GltfError(format!("The node {} of the glTF asset '{}' is one of its own descendants.", index, self.assets[asset].path.display()), gltf_error));
+        }
+        let source_node = self.assets[asset].root.nodes[index].clone();
+
+        let mut node = empty_node(source_node.name);
+        node.matrix = source_node.matrix;
+        node.translation = source_node.translation;
+        node.rotation = source_node.rotation;
+        node.scale = source_node.scale;
+        node.mesh = source_node.mesh.map(|mesh| self.copy_mesh(asset, mesh.value(), None));
+        if let Some(children) = source_node.children {
+            let mut copied_children = Vec::with_capacity(children.len());
+            for child in children.iter() {
+                copied_children.push(self.copy_node(asset, child.value())?);
+            }
+            node.children = Some(copied_children);
+        }
+        self.copied_nodes.remove(&(asset, index));
+
+        self.root.nodes.push(node);
+        Ok(Index::new((self.root.nodes.len() - 1) as u32))
+    }
+
+    // Sets the mesh of the node of a gameobject, or adds the copied nodes to its children.
+    fn export_mesh(&mut self, gameobject: &GameObjectBuilder, children: &mut Vec<Index<json::Node>>) -> DataParserResult<Option<Index<json::Mesh>>> {
+        let mesh = match gameobject.mesh() {
+            Some(mesh) => mesh,
+            None => return Ok(None),
+        };
+        let asset_path = self.paths.resolve(mesh.path(), self.assets_directory)?;
+        let mut resolved_mesh = mesh.clone();
+        resolved_mesh.set_path(asset_path.to_string_lossy().into_owned());
+        resolved_mesh.check_asset("")?;
+        let asset = self.load_asset(asset_path)?;
+
+        let missing = |kind: &str, field: &str, reference: &GltfReference| {
+            let gltf_error = gltf::Error::Validation(vec![(json::Path::new().field(field), validation::Error::Missing)]);
+            DataParserError::GltfError(format!("The glTF asset '{}' has no {} {}.", mesh.path(), kind, reference), gltf_error)
+        };
+
+        match mesh.selection() {
+            Some(&MeshSelection::Mesh { ref mesh, primitive }) => {
+                let index = find(&self.assets[asset].root.meshes, mesh, |mesh| mesh.name.as_deref())
+                    .ok_or_else(|| missing("mesh", "meshes", mesh))?;
+                return Ok(Some(self.copy_mesh(asset, index, primitive)));
+            },
+            Some(&MeshSelection::Node(ref node)) => {
+                let index = find(&self.assets[asset].root.nodes, node, |node| node.name.as_deref())
+                    .ok_or_else(|| missing("node", "nodes", node))?;
+                children.push(self.copy_node(asset, index)?);
+            },
+            Some(&MeshSelection::Scene(ref scene)) => {
+                let index = find(&self.assets[asset].root.scenes, scene, |scene| scene.name.as_deref())
+                    .ok_or_else(|| missing("scene", "scenes", scene))?;
+                self.copy_scene(asset, index, children)?;
+            },
+            None => {
+                let default_scene = self.assets[asset].root.scene.map(|scene| scene.value());
+                match default_scene.or_else(|| if self.assets[asset].root.scenes.is_empty() { None } else { Some(0) }) {
+                    Some(index) => self.copy_scene(asset, index, children)?,
+                    // Without scene, all the meshes of the asset are used.
+                    None => {
+                        for index in 0..self.assets[asset].root.meshes.len() {
+                            let mut node = empty_node(self.assets[asset].root.meshes[index].name.clone());
+                            node.mesh = Some(self.copy_mesh(asset, index, None));
+                            self.root.nodes.push(node);
+                            children.push(Index::new((self.root.nodes.len() - 1) as u32));
+                        }
+                    },
+                }
+            },
+        }
+
+        Ok(None)
+    }
+
+    fn copy_scene(&mut self, asset: usize, index: usize, children: &mut Vec<Index<json::Node>>) -> DataParserResult<()> {
+        let nodes = self.assets[asset].root.scenes[index].nodes.clone();
+        for node in nodes {
+            children.push(self.copy_node(asset, node.value())?);
+        }
+        Ok(())
+    }
+}
+
+// Exports a level as a glTF document. The mesh paths are relative to the assets directory, the
+// asset paths being resolved by the PathResolver.
+pub(crate) fn export_level(level: &LevelDescription, path: &Path, assets_directory: &Path, mode: GltfExportMode, paths: &PathResolver) -> DataParserResult<()> {
+    debug!("Exporting the level {} as the glTF document {}.", level.title(), path.display());
+    let hierarchy = level.hierarchy()?;
+
+    let output_directory = match path.parent() {
+        Some(directory) if directory != Path::new("") => fs::canonicalize(directory)?,
+        _ => fs::canonicalize(".")?,
+    };
+
+    let mut exporter = Exporter {
+        root: json::Root::default(),
+        mode,
+        assets_directory,
+        paths,
+        output_directory,
+        assets: Vec::new(),
+        buffers: HashMap::new(),
+        views: HashMap::new(),
+        accessors: HashMap::new(),
+        meshes: HashMap::new(),
+        copied_nodes: HashSet::new(),
+    };
+    exporter.root.asset.generator = Some(String::from("maskerad_data_parser"));
+
+    // The node of each gameobject has the index of the gameobject.
+    for gameobject in level.slice() {
+        exporter.root.nodes.push(empty_node(Some(gameobject.id().to_owned())));
+    }
+
+    for (index, hierarchy_node) in hierarchy.nodes().iter().enumerate() {
+        let gameobject = hierarchy_node.gameobject();
+        let mut children: Vec<Index<json::Node>> = hierarchy_node.children().iter().map(|&child| Index::new(child as u32)).collect();
+        let mesh = exporter.export_mesh(gameobject, &mut children).map_err(|error| {
+            error.with_context(format!("Gameobject '{}'", gameobject.id()))
+        })?;
+
+        let transform = gameobject.transform();
+        let position = transform.position();
+        let rotation = transform.quaternion();
+        let scale = transform.scale();
+
+        let node = &mut exporter.root.nodes[index];
+        node.translation = Some([position[0] as f32, position[1] as f32, position[2] as f32]);
+        node.rotation = Some(json::scene::UnitQuaternion([rotation[0] as f32, rotation[1] as f32, rotation[2] as f32, rotation[3] as f32]));
+        node.scale = Some([scale[0] as f32, scale[1] as f32, scale[2] as f32]);
+        node.mesh = mesh;
+        if !children.is_empty() {
+            node.children = Some(children);
+        }
+    }
+
+    exporter.root.scenes.push(json::Scene {
+        extensions: None,
+        extras: Default::default(),
+        name: Some(level.title().to_owned()),
+        nodes: hierarchy.roots().into_iter().map(|root| Index::new(root as u32)).collect(),
+    });
+    exporter.root.scene = Some(Index::new(0));
+
+    let document = exporter.root.to_string_pretty().map_err(|json_error| {
+        DataParserError::GltfError(format!("Could not write the level '{}' as a glTF document.", level.title()), gltf::Error::Deserialize(json_error))
+    })?;
+    atomic_file::save(path, |writer| writer.write_all(document.as_bytes()).map_err(DataParserError::from))
+}
@@ -0,0 +1,180 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use gltf::{self, Gltf};
+use serde_json;
+use gltf::json::{self, validation};
+use std::path::Path;
+use std::collections::HashSet;
+use data_parser_error::{DataParserError, DataParserResult};
+use level_description::LevelDescription;
+use gameobject_builder::GameObjectBuilder;
+use transform_description::TransformDescription;
+use mesh_description::{MeshDescription, MeshSelection, GltfReference};
+use component_description::ExtrasMapping;
+use asset_path::{self, PathResolver};
+
+/*
+    A glTF scene is imported as a level:
+    - the title of the level is the name of the scene, or the name of the file,
+    - each node becomes a gameobject, whose id is the name of the node (or 'node<index>' if the
+      node has no name, suffixed by '_<index>', or the next free number, if the id is already used),
+    - the transform of the node becomes the local transform of the gameobject, and must be valid
+      (finite values, no zero scale),
+    - the mesh of the node is referenced by the path of the file, written as a data file of the
+      level directory would (see asset_path.rs), selecting the mesh by name (or by index if its
+      name is missing or not unique) if the asset has several meshes,
+    - the extras of the node become the components of its gameobject, according to the
+      ExtrasMapping,
+    - the children of a node become the children of its gameobject.
+*/
+
+fn invalid_node(asset_path: &Path, index: usize, description: &str) -> DataParserError {
+    let gltf_error = gltf::Error::Validation(vec![(json::Path::new().field("nodes").index(index), validation::Error::Invalid)]);
+    DataParserError::GltfError(format!("The node {} of the glTF asset '{}' {}.", index, asset_path.display(), description), gltf_error)
+}
+
+fn invalid_transform(asset_path: &Path, index: usize, description: &str) -> DataParserError {
+    let gltf_error = gltf::Error::Validation(vec![(json::Path::new().field("nodes").index(index), validation::Error::Invalid)]);
+    DataParserError::GltfError(format!("The transform of the node {} of the glTF asset '{}' is invalid: {}", index, asset_path.display(), description), gltf_error)
+}
+
+fn invalid_extras(asset_path: &Path, index: usize, description: &str) -> DataParserError {
+    let gltf_error = gltf::Error::Validation(vec![(json::Path::new().field("nodes").index(index).field("extras"), validation::Error::Invalid)]);
+    DataParserError::GltfError(format!("The extras of the node {} of the glTF asset '{}' are invalid: {}", index, asset_path.display(), description), gltf_error)
+}
+
+struct SceneImporter<'a> {
+    asset: &'a Gltf,
+    asset_path: &'a Path,
+    // The path of the asset, as written in the mesh descriptions.
+    mesh_path: String,
+    mapping: &'a ExtrasMapping,
+    used_ids: HashSet<String>,
+    visited_nodes: HashSet<usize>,
+}
+
+impl<'a> SceneImporter<'a> {
+    fn id(&mut self, node: &gltf::Node) -> String {
+        let name = match node.name() {
+            Some(name) if !name.trim().is_empty() => name.to_owned(),
+            _ => format!("node{}", node.index()),
+        };
+        // A suffixed id can be the name of another node.
+        let mut id = name.clone();
+        let mut suffix = node.index();
+        while self.used_ids.contains(&id) {
+            id = format!("{}_{}", name, suffix);
+            suffix += 1;
+        }
+        self.used_ids.insert(id.clone());
+        id
+    }
+
+    fn mesh(&self, mesh: &gltf::Mesh) -> MeshDescription {
+        let mut mesh_description = MeshDescription::new(self.mesh_path.as_str());
+        // The only mesh of the asset is the whole asset, which the runtime can build.
+        if self.asset.meshes().len() == 1 {
+            return mesh_description;
+        }
+
+        let reference = match mesh.name() {
+            Some(name) if self.asset.meshes().filter(|other| other.name() == Some(name)).count() == 1 => {
+                GltfReference::Name(name.to_owned())
+            },
+            _ => GltfReference::Index(mesh.index()),
+        };
+
+        mesh_description.add_selection(MeshSelection::Mesh {
+            mesh: reference,
+            primitive: None,
+        });
+        mesh_description
+    }
+
+    fn gameobject(&mut self, node: &gltf::Node) -> DataParserResult<GameObjectBuilder> {
+        if !self.visited_nodes.insert(node.index()) {
+            return Err(invalid_node(self.asset_path, node.index(), "is reachable several times from the scene"));
+        }
+
+        let (translation, rotation, scale) = node.transform().decomposed();
+        let transform = TransformDescription::new(
+            [f64::from(translation[0]), f64::from(translation[1]), f64::from(translation[2])],
+            [f64::from(rotation[0]), f64::from(rotation[1]), f64::from(rotation[2]), f64::from(rotation[3])],
+            [f64::from(scale[0]), f64::from(scale[1]), f64::from(scale[2])],
+        );
+        transform.check().map_err(|message| invalid_transform(self.asset_path, node.index(), message.as_str()))?;
+
+        let mut gameobject = GameObjectBuilder::new(self.id(node));
+        gameobject.add_transform(transform);
+        if let Some(mesh) = node.mesh() {
+            gameobject.add_mesh(self.mesh(&mesh));
+        }
+        if let Some(ref extras) = *node.extras() {
+            let extras: serde_json::Value = serde_json::from_str(extras.get()).map_err(|json_error| {
+                invalid_extras(self.asset_path, node.index(), json_error.to_string().as_str())
+            })?;
+            self.mapping.apply(&extras, &mut gameobject).map_err(|message| {
+                invalid_extras(self.asset_path, node.index(), message.as_str())
+            })?;
+        }
+
+        for child in node.children() {
+            let child = self.gameobject(&child)?;
+            gameobject.add_child(child);
+        }
+
+        Ok(gameobject)
+    }
+}
+
+// Imports a scene of a glTF asset as a level to save in the level directory. Without a scene
+// reference, the default scene of the asset is imported, or its first scene.
+pub(crate) fn import_scene(asset_path: &Path, scene: Option<&GltfReference>, level_directory: &Path, mapping: &ExtrasMapping, paths: &PathResolver) -> DataParserResult<LevelDescription> {
+    debug!("Importing a scene of the glTF asset {}.", asset_path.display());
+
+    let asset = Gltf::open(asset_path).map_err(|gltf_error| {
+        DataParserError::GltfError(format!("Could not open the glTF asset '{}'.", asset_path.display()), gltf_error)
+    })?;
+
+    let selected_scene = match scene {
+        Some(reference) => asset.scenes().find(|candidate| match reference {
+            &GltfReference::Index(index) => candidate.index() == index,
+            &GltfReference::Name(ref name) => candidate.name() == Some(name.as_str()),
+        }),
+        None => asset.default_scene().or_else(|| asset.scenes().next()),
+    };
+    let selected_scene = selected_scene.ok_or_else(|| {
+        let description = match scene {
+            Some(reference) => format!("The glTF asset '{}' has no scene {}.", asset_path.display(), reference),
+            None => format!("The glTF asset '{}' has no scene.", asset_path.display()),
+        };
+        DataParserError::GltfError(description, gltf::Error::Validation(vec![(json::Path::new().field("scenes"), validation::Error::Missing)]))
+    })?;
+
+    let title = match selected_scene.name() {
+        Some(name) => name.to_owned(),
+        None => asset_path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default(),
+    };
+
+    let mut importer = SceneImporter {
+        asset: &asset,
+        asset_path,
+        mesh_path: paths.portable(asset_path::absolute(asset_path).to_string_lossy().as_ref(), level_directory),
+        mapping,
+        used_ids: HashSet::new(),
+        visited_nodes: HashSet::new(),
+    };
+
+    let mut level = LevelDescription::new(title);
+    for node in selected_scene.nodes() {
+        let gameobject = importer.gameobject(&node)?;
+        level.add_gameobject(gameobject);
+    }
+
+    Ok(level)
+}
@@ -5,16 +5,33 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use toml;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use data_parser_error::{DataParserError, DataParserResult};
-use std::path::Path;
-use gltf::Gltf;
+use std::path::{Path, PathBuf};
 use std::io::{Write, Read};
 use gameobject_builder::GameObjectBuilder;
+use level_instance::{LevelInstance, InstantiationMode};
+use level_hierarchy::LevelHierarchy;
+use level_diff::{self, LevelDiff};
+use reference_resolver::{ReferenceResolver, LevelData, GameObjectEntry};
+use error_location::{self, ErrorLocation};
+use diagnostic::{Diagnostic, DiagnosticCode, Severity};
+use unknown_key::{self, UnknownKeyMode, DocumentKind};
+use migration::{self, Versioned};
+use data_format::{self, DataFormat};
+use canonical::SerializationMode;
+use atomic_file;
+use mesh_description::GltfReference;
+use component_description::ExtrasMapping;
+use gltf_import;
+use binary_format;
+use gltf_export::{self, GltfExportMode};
+use asset_path::PathResolver;
+use virtual_filesystem::{VirtualFilesystem, NativeFilesystem};
 
 /*
     Level file structure:
+    format_version = 2
     title = "level name"
 
     gameobjects = [
@@ -23,12 +40,36 @@ use gameobject_builder::GameObjectBuilder;
         ...
     ]
 
+    Or, to mix inline gameobjects and references:
+    title = "level name"
+
+    [[gameobjects]]
+    path = "path to gameobject1"
+
+    [[gameobjects]]
+    id = "gameobject2"
+
+    [gameobjects.transform]
+    ...
+
+    Paths are relative to the level file, or to the project root when they start with asset://
+    (see asset_path.rs). A reference can point to a gameobject file, or to another level file,
+    whose gameobjects will be included.
+
+    An inline gameobject can be an instance of a prefab (see reference_resolver.rs). When loaded with
+    LevelDescription::load_from_file, the level is flattened: the references and prefabs are replaced
+    by plain GameObjectBuilders, and saving the level writes them inline.
+
+    The files written in an older format version are upgraded when loaded (see migration.rs).
+
+    The children nested in a gameobject are stored in the level right after their parent,
+    declaring the id of their parent.
 */
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct LevelDescription {
     title: String,
-    gameobjects: Vec<GameObjectBuilder>, //TODO: Vec<GameObjectBuilder> ?
+    gameobjects: Vec<GameObjectBuilder>,
 }
 
 impl AsRef<LevelDescription> for LevelDescription {
@@ -37,24 +78,346 @@ impl AsRef<LevelDescription> for LevelDescription {
     }
 }
 
+// Pushes the file and key path of a gameobject and of its children, in the order they are
+// stored in a level.
+fn flatten_origins<'a>(gameobject: &GameObjectBuilder, file: &'a Path, key_path: String, origins: &mut Vec<(&'a Path, String)>) {
+    for (index, child) in gameobject.children().iter().enumerate() {
+        let child_key_path = if key_path.is_empty() { format!("children[{}]", index) } else { format!("{}.children[{}]", key_path, index) };
+        if index == 0 {
+            origins.push((file, key_path.clone()));
+        }
+        flatten_origins(child, file, child_key_path, origins);
+    }
+    if gameobject.children().is_empty() {
+        origins.push((file, key_path));
+    }
+}
+
 impl LevelDescription {
     pub fn load_from_toml<R>(reader: &mut R) -> DataParserResult<Self> where
         R: Read
     {
-        debug!("Creating a LevelDescription from toml file.");
+        LevelDescription::load_from_toml_with_mode(reader, UnknownKeyMode::Lenient).map(|(level, _)| level)
+    }
+
+    /// Loads a level, handling the unknown keys according to the mode. The unknown keys are
+    /// returned as warnings in lenient mode.
+    pub fn load_from_toml_with_mode<R>(reader: &mut R, mode: UnknownKeyMode) -> DataParserResult<(Self, Vec<Diagnostic>)> where
+        R: Read
+    {
+        LevelDescription::load_from_reader_with_mode(reader, DataFormat::Toml, mode)
+    }
+
+    /// Loads a level written in the given format (see data_format.rs).
+    pub fn load_from_reader<R>(reader: &mut R, format: DataFormat) -> DataParserResult<Self> where
+        R: Read
+    {
+        LevelDescription::load_from_reader_with_mode(reader, format, UnknownKeyMode::Lenient).map(|(level, _)| level)
+    }
+
+    /// Loads a level written in the given format, handling the unknown keys according to the mode.
+    pub fn load_from_reader_with_mode<R>(reader: &mut R, format: DataFormat, mode: UnknownKeyMode) -> DataParserResult<(Self, Vec<Diagnostic>)> where
+        R: Read
+    {
+        debug!("Creating a LevelDescription from a {:?} document.", format);
 
         let mut content = String::new();
         trace!("Reading content of the reader in string...");
         reader.read_to_string(&mut content)?;
-        toml::from_str(content.as_ref()).map_err(|deserialization_error| {
-            DataParserError::from(deserialization_error)
-        })
+
+        let mut unknown_keys = Vec::new();
+        let result = LevelDescription::parse(content.as_ref(), format, &mut unknown_keys);
+        let unknown_keys = unknown_keys
+            .into_iter()
+            .map(|key_path| unknown_key::diagnostic(DocumentKind::Level, ErrorLocation::at_key_path(None, key_path, content.as_ref(), format)))
+            .collect();
+        unknown_key::apply_mode(mode, result, unknown_keys)
+    }
+
+    fn parse(content: &str, format: DataFormat, unknown_keys: &mut Vec<String>) -> DataParserResult<Self> {
+        let value = data_format::parse(content, format)?;
+        let (value, _) = migration::migrate(value, DocumentKind::Level)?;
+        let level_data: LevelData = error_location::deserialize_value(value, "", unknown_keys).map_err(|error| {
+            error.with_source(None, content, format)
+        })?;
+
+        let mut level = LevelDescription::new(level_data.title);
+        for (index, entry) in level_data.gameobjects.into_iter().enumerate() {
+            match GameObjectEntry::from_value(entry) {
+                GameObjectEntry::Inline(value) => {
+                    let gameobject = GameObjectBuilder::from_value(value, format!("gameobjects[{}]", index).as_str(), unknown_keys).map_err(|error| {
+                        error.with_context(format!("Level entry gameobjects[{}]", index)).with_source(None, content, format)
+                    })?;
+                    level.add_gameobject(gameobject);
+                },
+                GameObjectEntry::Reference(reference) => {
+                    return Err(DataParserError::ReferenceError(format!("Level entry gameobjects[{}] references '{}', but references can only be resolved when the level is loaded with LevelDescription::load_from_file.", index, reference)));
+                },
+            }
+        }
+
+        level.hierarchy()?;
+        Ok(level)
+    }
+
+    /// Loads a level written in the binary format (see binary_format.rs).
+    pub fn load_from_binary<R>(reader: &mut R) -> DataParserResult<Self> where
+        R: Read
+    {
+        debug!("Creating a LevelDescription from binary data.");
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        binary_format::decode_level(data.as_slice())
+    }
+
+    pub fn load_from_file<P>(path: P) -> DataParserResult<Self> where
+        P: AsRef<Path>
+    {
+        LevelDescription::load_from_file_with_mode(path, UnknownKeyMode::Lenient).map(|(level, _)| level)
+    }
+
+    /// Loads a level file and the files it references, handling the unknown keys according to the mode.
+    /// The unknown keys are returned as warnings in lenient mode.
+    pub fn load_from_file_with_mode<P>(path: P, mode: UnknownKeyMode) -> DataParserResult<(Self, Vec<Diagnostic>)> where
+        P: AsRef<Path>
+    {
+        LevelDescription::load_from_file_with_resolver(path, mode, &PathResolver::default())
+    }
+
+    /// Loads a level file and the files it references, the asset paths being resolved by the PathResolver.
+    /// The relative mesh paths of the loaded level are relative to the level file.
+    pub fn load_from_file_with_resolver<P>(path: P, mode: UnknownKeyMode, paths: &PathResolver) -> DataParserResult<(Self, Vec<Diagnostic>)> where
+        P: AsRef<Path>
+    {
+        LevelDescription::load_from_filesystem(&NativeFilesystem, path, mode, paths)
+    }
+
+    /// Loads a level file and the files it references from the filesystem (see virtual_filesystem.rs).
+    pub fn load_from_filesystem<F, P>(filesystem: &F, path: P, mode: UnknownKeyMode, paths: &PathResolver) -> DataParserResult<(Self, Vec<Diagnostic>)> where
+        F: VirtualFilesystem,
+        P: AsRef<Path>,
+    {
+        debug!("Creating a LevelDescription from the toml file {}.", path.as_ref().display());
+
+        let mut resolver = ReferenceResolver::new(filesystem, paths);
+        let result = resolver.resolve_level(path.as_ref()).and_then(|title| {
+            let mut level = LevelDescription::new(title);
+            for gameobject in resolver.gameobjects.drain(..) {
+                level.add_gameobject(gameobject);
+            }
+            level.hierarchy()?;
+            Ok(level)
+        });
+        unknown_key::apply_mode(mode, result, resolver.unknown_keys)
+    }
+
+    /// Loads the level file and reports all the problems found, instead of stopping at the first one.
+    pub fn validate_file<P>(path: P) -> Vec<Diagnostic> where
+        P: AsRef<Path>
+    {
+        LevelDescription::validate_file_with_resolver(path, &PathResolver::default())
+    }
+
+    /// Validates the level file, the asset paths being resolved by the PathResolver.
+    pub fn validate_file_with_resolver<P>(path: P, paths: &PathResolver) -> Vec<Diagnostic> where
+        P: AsRef<Path>
+    {
+        LevelDescription::validate_in_filesystem(&NativeFilesystem, path, paths)
+    }
+
+    /// Validates a level file, reading it, the files it references and the meshes from the filesystem.
+    pub fn validate_in_filesystem<F, P>(filesystem: &F, path: P, paths: &PathResolver) -> Vec<Diagnostic> where
+        F: VirtualFilesystem,
+        P: AsRef<Path>,
+    {
+        debug!("Validating the level file {}.", path.as_ref().display());
+
+        let mut diagnostics = Vec::new();
+        let mut resolver = ReferenceResolver::collecting(filesystem, paths);
+        if let Err(error) = resolver.resolve_level(path.as_ref()) {
+            diagnostics.push(Diagnostic::from_error(&error));
+        }
+
+        for error in resolver.errors.take().unwrap_or_default() {
+            diagnostics.push(Diagnostic::from_error(&error));
+        }
+
+        diagnostics.append(&mut resolver.unknown_keys);
+
+        // The mesh paths have been rebased on the directory of the level.
+        let level_directory = path.as_ref().parent().unwrap_or(Path::new(""));
+        for (gameobject, origin) in resolver.gameobjects.iter().zip(resolver.origins.iter()) {
+            let source = resolver.sources.get(&origin.file).map(|source| source.as_str()).unwrap_or("");
+            let location = MeshLocation {
+                filesystem,
+                directory: level_directory,
+                file: origin.file.as_path(),
+                source,
+            };
+            check_meshes(gameobject, &location, origin.key_path.as_str(), paths, &mut diagnostics);
+        }
+
+        // The file and key path of each gameobject, in the order of the level.
+        let mut origins = Vec::new();
+        for (gameobject, origin) in resolver.gameobjects.iter().zip(resolver.origins.iter()) {
+            flatten_origins(gameobject, origin.file.as_path(), origin.key_path.clone(), &mut origins);
+        }
+
+        let mut level = LevelDescription::new("");
+        for gameobject in resolver.gameobjects {
+            level.add_gameobject(gameobject);
+        }
+
+        let mut id_counts: HashMap<&str, usize> = HashMap::new();
+        for gameobject in level.gameobjects.iter() {
+            *id_counts.entry(gameobject.id()).or_insert(0) += 1;
+        }
+        // The duplicate id is located at its second occurrence.
+        let mut seen_ids: HashSet<&str> = HashSet::new();
+        for (gameobject, &(file, ref key_path)) in level.gameobjects.iter().zip(origins.iter()) {
+            let id = gameobject.id();
+            if seen_ids.insert(id) {
+                continue;
+            }
+            if let Some(count) = id_counts.remove(id) {
+                let message = format!("The id '{}' is used by {} gameobjects.", id, count);
+                let id_key_path = if key_path.is_empty() { String::from("id") } else { format!("{}.id", key_path) };
+                let source = resolver.sources.get(file).map(|source| source.as_str()).unwrap_or("");
+                let location = ErrorLocation::at_key_path(Some(file), id_key_path, source, DataFormat::of_file(file));
+                diagnostics.push(Diagnostic::new(Severity::Error, DiagnosticCode::DuplicateId, message, location));
+            }
+        }
+
+        if let Err(error) = level.hierarchy() {
+            diagnostics.push(Diagnostic::from_error(&error));
+        }
+
+        diagnostics
+    }
+
+    /// Upgrades a level file to the current format version, rewriting it if it had an older version.
+    /// The files it references are not upgraded. Returns whether the file was rewritten.
+    pub fn migrate_file<P>(path: P) -> DataParserResult<bool> where
+        P: AsRef<Path>
+    {
+        migration::migrate_file(path.as_ref(), DocumentKind::Level)
+    }
+
+    /// Imports a scene of a glTF asset as a level, each node becoming a gameobject (see gltf_import.rs).
+    /// Without a scene, the default scene of the asset is imported. The mesh paths are relative to
+    /// the level directory, where the level is meant to be saved.
+    pub fn import_gltf<P, S, D>(path: P, scene: S, level_directory: D) -> DataParserResult<Self> where
+        P: AsRef<Path>,
+        S: Into<Option<GltfReference>>,
+        D: AsRef<Path>,
+    {
+        LevelDescription::import_gltf_with_mapping(path, scene, level_directory, &ExtrasMapping::default(), &PathResolver::default())
+    }
+
+    /// Imports a scene of a glTF asset as a level, the extras of the nodes becoming components of
+    /// the gameobjects according to the mapping. The mesh paths are asset paths if the asset is in
+    /// the project root, or relative to the level directory otherwise.
+    pub fn import_gltf_with_mapping<P, S, D>(path: P, scene: S, level_directory: D, mapping: &ExtrasMapping, paths: &PathResolver) -> DataParserResult<Self> where
+        P: AsRef<Path>,
+        S: Into<Option<GltfReference>>,
+        D: AsRef<Path>,
+    {
+        let scene = scene.into();
+        let level = gltf_import::import_scene(path.as_ref(), scene.as_ref(), level_directory.as_ref(), mapping, paths)?;
+        level.hierarchy()?;
+        Ok(level)
+    }
+
+    /// Exports the level as a glTF document, to preview it in a glTF viewer (see gltf_export.rs).
+    /// The mesh paths are relative to the assets directory.
+    pub fn export_gltf<P, D>(&self, path: P, assets_directory: D, mode: GltfExportMode) -> DataParserResult<()> where
+        P: AsRef<Path>,
+        D: AsRef<Path>,
+    {
+        LevelDescription::export_gltf_with_resolver(self, path, assets_directory, mode, &PathResolver::default())
+    }
+
+    /// Exports the level as a glTF document, the asset paths being resolved by the PathResolver.
+    pub fn export_gltf_with_resolver<P, D>(&self, path: P, assets_directory: D, mode: GltfExportMode, paths: &PathResolver) -> DataParserResult<()> where
+        P: AsRef<Path>,
+        D: AsRef<Path>,
+    {
+        gltf_export::export_level(self, path.as_ref(), assets_directory.as_ref(), mode, paths)
     }
 
     pub fn as_string_toml(&self) -> DataParserResult<String> {
-        debug!("Getting a string representation of the LevelDescription.");
-        let toml_string = toml::to_string(&self)?;
-        Ok(toml_string)
+        self.as_string(DataFormat::Toml)
+    }
+
+    /// The string representation of the level in TOML, in its canonical form if asked (see canonical.rs).
+    pub fn as_string_toml_with_mode(&self, mode: SerializationMode) -> DataParserResult<String> {
+        self.as_string_with_mode(DataFormat::Toml, mode)
+    }
+
+    /// The string representation of the level in the given format (see data_format.rs).
+    pub fn as_string(&self, format: DataFormat) -> DataParserResult<String> {
+        self.as_string_with_mode(format, SerializationMode::Verbatim)
+    }
+
+    /// The string representation of the level in the given format and serialization mode.
+    pub fn as_string_with_mode(&self, format: DataFormat, mode: SerializationMode) -> DataParserResult<String> {
+        debug!("Getting a {:?} representation of the LevelDescription, in the {:?} mode.", format, mode);
+        data_format::to_string_with_mode(&Versioned::new(self), format, mode)
+    }
+
+    /// Writes the level to the writer, in the given format.
+    pub fn save<W>(&self, writer: &mut W, format: DataFormat) -> DataParserResult<()> where
+        W: Write + ?Sized
+    {
+        self.save_with_mode(writer, format, SerializationMode::Verbatim)
+    }
+
+    /// Writes the level to the writer, in the given format and serialization mode.
+    pub fn save_with_mode<W>(&self, writer: &mut W, format: DataFormat, mode: SerializationMode) -> DataParserResult<()> where
+        W: Write + ?Sized
+    {
+        debug!("Writing the LevelDescription as a {:?} document.", format);
+        let document = self.as_string_with_mode(format, mode)?;
+        writer.write_all(document.as_bytes())?;
+        Ok(())
+    }
+
+    /// Saves the level at the given path, in the format of the extension of the file. The file is
+    /// replaced atomically: if the saving fails, the previous file is left untouched.
+    pub fn save_to_file<P>(&self, path: P) -> DataParserResult<()> where
+        P: AsRef<Path>
+    {
+        self.save_to_file_with_mode(path, SerializationMode::Verbatim)
+    }
+
+    /// Saves the level at the given path, in the format of the extension of the file and the given
+    /// serialization mode.
+    pub fn save_to_file_with_mode<P>(&self, path: P, mode: SerializationMode) -> DataParserResult<()> where
+        P: AsRef<Path>
+    {
+        debug!("Saving the LevelDescription to the file {}.", path.as_ref().display());
+        let format = DataFormat::of_file(path.as_ref());
+        atomic_file::save(path.as_ref(), |writer| self.save_with_mode(writer, format, mode))
+    }
+
+    /// The level in the binary format (see binary_format.rs).
+    pub fn as_binary(&self) -> DataParserResult<Vec<u8>> {
+        debug!("Getting a binary representation of the LevelDescription.");
+        binary_format::encode_level(self)
+    }
+
+    /// The string representation of the level to save at the given path, the absolute mesh paths
+    /// being rewritten as asset paths or paths relative to the file.
+    pub fn as_string_toml_portable<P>(&self, path: P, paths: &PathResolver) -> DataParserResult<String> where
+        P: AsRef<Path>
+    {
+        debug!("Getting a portable string representation of the LevelDescription.");
+        let directory = path.as_ref().parent().unwrap_or(Path::new(""));
+        let mut level = self.clone();
+        for gameobject in level.gameobjects.iter_mut() {
+            gameobject.map_mesh_paths(&mut |mesh_path| paths.portable(mesh_path, directory));
+        }
+        level.as_string_toml()
     }
 
     pub fn new<I: Into<String>>(title: I) -> Self {
@@ -67,7 +430,12 @@ impl LevelDescription {
 
     pub fn add_gameobject<I: Into<GameObjectBuilder>>(&mut self, obj: I) -> &mut Self {
         debug!("Adding a GameObjectBuilder to the LevelDescription.");
-        self.gameobjects.push(obj.into());
+        let mut gameobject = obj.into();
+        let children = gameobject.take_children();
+        self.gameobjects.push(gameobject);
+        for child in children {
+            self.add_gameobject(child);
+        }
         self
     }
 
@@ -79,14 +447,86 @@ impl LevelDescription {
         debug!("Getting an immutable slice over all the GameObjectBuilders in the LevelDescription.");
         &self.gameobjects
     }
+
+    /// The differences between this level and a new version of it, the gameobjects being matched by
+    /// id (see level_diff.rs).
+    pub fn diff<'a>(&'a self, other: &'a LevelDescription) -> DataParserResult<LevelDiff<'a>> {
+        level_diff::diff(self, other)
+    }
+
+    pub fn hierarchy<'a>(&'a self) -> DataParserResult<LevelHierarchy<'a>> {
+        debug!("Getting the hierarchy of the GameObjectBuilders in the LevelDescription.");
+        LevelHierarchy::new(&self.gameobjects)
+    }
+
+    pub fn instantiate(&self, mode: InstantiationMode) -> DataParserResult<LevelInstance> {
+        debug!("Instantiating all the GameObjectBuilders in the LevelDescription.");
+        let mut instance = LevelInstance::new(self.title.as_str());
+
+        for gameobject_builder in self.gameobjects.iter() {
+            match gameobject_builder.build() {
+                Ok(gameobject) => {
+                    instance.add_gameobject(gameobject_builder.id(), gameobject);
+                },
+                Err(error) => {
+                    warn!("Could not build the gameobject {}: {}", gameobject_builder.id(), error);
+                    instance.add_failure(gameobject_builder.id(), error);
+                },
+            }
+        }
+
+        if mode == InstantiationMode::Strict && !instance.is_complete() {
+            let description = format!("{} of the {} gameobjects of the level '{}' could not be built.", instance.failures().len(), self.gameobjects.len(), self.title);
+            let (_, failures) = instance.into_parts();
+            let errors = failures.into_iter().map(|(_, error)| error).collect();
+            return Err(DataParserError::InstantiationError(description, errors));
+        }
+
+        Ok(instance)
+    }
+}
+
+// Where the meshes of a gameobject are checked: the directory their paths are relative to, and the
+// file declaring the gameobject.
+struct MeshLocation<'a> {
+    filesystem: &'a dyn VirtualFilesystem,
+    directory: &'a Path,
+    file: &'a Path,
+    source: &'a str,
 }
 
+// Reports the meshes of a gameobject and of its children which can't be found or aren't valid glTF
+// assets.
+fn check_meshes(gameobject: &GameObjectBuilder, mesh_location: &MeshLocation, key_path: &str, paths: &PathResolver, diagnostics: &mut Vec<Diagnostic>) {
+    if let Some(mesh) = gameobject.mesh() {
+        let mesh_key_path = if key_path.is_empty() { String::from("mesh.path") } else { format!("{}.mesh.path", key_path) };
+        let location = ErrorLocation::at_key_path(Some(mesh_location.file), mesh_key_path, mesh_location.source, DataFormat::of_file(mesh_location.file));
+
+        let full_path: Option<PathBuf> = paths.resolve(mesh.path(), mesh_location.directory).ok();
+        match full_path {
+            Some(ref full_path) if !mesh.path().trim().is_empty() && mesh_location.filesystem.is_file(full_path) => {
+                let mut resolved_mesh = mesh.clone();
+                resolved_mesh.set_path(full_path.to_string_lossy().into_owned());
+                if let Err(error) = resolved_mesh.check_asset_in("", mesh_location.filesystem) {
+                    let message = format!("Gameobject '{}': {}", gameobject.id(), error);
+                    diagnostics.push(Diagnostic::new(Severity::Error, DiagnosticCode::InvalidMesh, message, location));
+                }
+            },
+            _ => {
+                let message = format!("Gameobject '{}': the mesh '{}' could not be found.", gameobject.id(), mesh.path());
+                diagnostics.push(Diagnostic::new(Severity::Error, DiagnosticCode::MissingMesh, message, location));
+            },
+        }
+    }
+
+    for (index, child) in gameobject.children().iter().enumerate() {
+        let child_key_path = if key_path.is_empty() { format!("children[{}]", index) } else { format!("{}.children[{}]", key_path, index) };
+        check_meshes(child, mesh_location, child_key_path.as_str(), paths, diagnostics);
+    }
+}
 
 #[cfg(test)]
 mod level_file_test {
-    use super::*;
-    use std::path::PathBuf;
-
     /*
     #[test]
     fn test_deserialization() {
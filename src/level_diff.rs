@@ -0,0 +1,392 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use toml;
+use serde_json;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use data_parser_error::{DataParserError, DataParserResult};
+use data_format;
+use gameobject_builder::GameObjectBuilder;
+use level_description::LevelDescription;
+
+/*
+    The difference between two versions of a level, the gameobjects being matched by id:
+
+    title: "courtyard" -> "courtyard at night"
+    + bench
+    - statue
+    ~ fountain
+        transform.position: [4.0, 0.0, -2.5] -> [5.0, 0.0, -2.5] (delta [1.0, 0.0, 0.0])
+        transform.rotation: [0.0, 0.0, 0.0] -> { euler = [0.0, 90.0, 0.0], order = "XYZ", unit = "degrees" } (rotated by 90°)
+        mesh.path: "fountain.gltf" -> "fountain_v2.gltf"
+        light: none -> { color = [1.0, 1.0, 1.0], intensity = 1.0, kind = "point" }
+
+    The changed fields are given by their key path, as written in the gameobject files. The
+    scripts are compared by index (scripts[0].parameters.speed), and a value appears or
+    disappears when its key is added or removed (none). Rotations written in another form are
+    reported as changed, the angle between the rotations being 0°.
+
+    The JSON form of a difference:
+    {
+      "title": { "before": "courtyard", "after": "courtyard at night" },
+      "added": [{ "id": "bench", ... }],
+      "removed": [{ "id": "statue", ... }],
+      "modified": [
+        {
+          "id": "fountain",
+          "rotation_angle": 90.0,
+          "changes": [{ "path": "transform.position", "before": [4.0, 0.0, -2.5], "after": [5.0, 0.0, -2.5], "delta": [1.0, 0.0, 0.0] }]
+        }
+      ]
+    }
+    The title is null when unchanged, as are the missing values, the deltas and rotation angles.
+*/
+
+/// A value of a gameobject which changed, by its key path (e.g. `transform.position`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    path: String,
+    before: Option<toml::Value>,
+    after: Option<toml::Value>,
+}
+
+fn number(value: &toml::Value) -> Option<f64> {
+    match value {
+        &toml::Value::Float(float) => Some(float),
+        &toml::Value::Integer(integer) => Some(integer as f64),
+        _ => None,
+    }
+}
+
+impl FieldChange {
+    pub fn path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    /// The previous value, none if the key was added.
+    pub fn before(&self) -> Option<&toml::Value> {
+        self.before.as_ref()
+    }
+
+    /// The new value, none if the key was removed.
+    pub fn after(&self) -> Option<&toml::Value> {
+        self.after.as_ref()
+    }
+
+    /// The difference between the new and previous values, component by component, if both are
+    /// numbers or arrays of numbers of the same length.
+    pub fn delta(&self) -> Option<Vec<f64>> {
+        match (self.before.as_ref()?, self.after.as_ref()?) {
+            (&toml::Value::Array(ref before), &toml::Value::Array(ref after)) if before.len() == after.len() => {
+                before.iter().zip(after.iter()).map(|(before, after)| Some(number(after)? - number(before)?)).collect()
+            },
+            (before, after) => Some(vec![number(after)? - number(before)?]),
+        }
+    }
+}
+
+/// The changes of a gameobject found in both levels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameObjectDiff {
+    id: String,
+    changes: Vec<FieldChange>,
+    rotation_angle: Option<f64>,
+}
+
+impl GameObjectDiff {
+    pub fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    pub fn changes(&self) -> &[FieldChange] {
+        &self.changes
+    }
+
+    /// The change of the field at the key path, if it changed.
+    pub fn change(&self, path: &str) -> Option<&FieldChange> {
+        self.changes.iter().find(|change| change.path == path)
+    }
+
+    /// The angle between the previous and new rotations, in degrees, if the rotation changed.
+    pub fn rotation_angle(&self) -> Option<f64> {
+        self.rotation_angle
+    }
+}
+
+/// The differences between two levels, the gameobjects being matched by id.
+#[derive(Debug, Clone)]
+pub struct LevelDiff<'a> {
+    title: Option<(&'a str, &'a str)>,
+    added: Vec<&'a GameObjectBuilder>,
+    removed: Vec<&'a GameObjectBuilder>,
+    modified: Vec<GameObjectDiff>,
+}
+
+// The gameobjects of a level by id, refusing the levels where an id is used several times.
+fn by_id(level: &LevelDescription) -> DataParserResult<HashMap<&str, &GameObjectBuilder>> {
+    let mut gameobjects = HashMap::new();
+    for gameobject in level.slice() {
+        if gameobjects.insert(gameobject.id(), gameobject).is_some() {
+            return Err(DataParserError::HierarchyError(format!("The levels can't be compared: several gameobjects of the level '{}' have the id '{}'.", level.title(), gameobject.id())));
+        }
+    }
+    Ok(gameobjects)
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+// Whether the key path is the rotation of the transform, or one of its keys.
+fn is_rotation_path(path: &str) -> bool {
+    path == "transform.rotation" || path.starts_with("transform.rotation.")
+}
+
+fn is_array_of_tables(array: &[toml::Value]) -> bool {
+    !array.is_empty() && array.iter().all(|value| value.is_table())
+}
+
+// Pushes the changed values between two values at the key path: the tables and arrays of tables
+// are compared key by key and index by index, the other values as a whole.
+fn compare(path: &str, before: Option<&toml::Value>, after: Option<&toml::Value>, changes: &mut Vec<FieldChange>) {
+    match (before, after) {
+        (Some(&toml::Value::Table(ref before)), Some(&toml::Value::Table(ref after))) => {
+            let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+            for key in keys {
+                compare(join_path(path, key).as_str(), before.get(key), after.get(key), changes);
+            }
+        },
+        (Some(&toml::Value::Array(ref before)), Some(&toml::Value::Array(ref after))) if is_array_of_tables(before) && is_array_of_tables(after) => {
+            for index in 0..before.len().max(after.len()) {
+                compare(format!("{}[{}]", path, index).as_str(), before.get(index), after.get(index), changes);
+            }
+        },
+        (before, after) => {
+            if before != after {
+                changes.push(FieldChange {
+                    path: path.to_owned(),
+                    before: before.cloned(),
+                    after: after.cloned(),
+                });
+            }
+        },
+    }
+}
+
+// The angle between two unit quaternions, in degrees.
+fn angle_between(before: [f64; 4], after: [f64; 4]) -> f64 {
+    let dot = before.iter().zip(after.iter()).map(|(before, after)| before * after).sum::<f64>().abs();
+    (2.0 * dot.min(1.0).acos()).to_degrees()
+}
+
+fn compare_gameobjects(before: &GameObjectBuilder, after: &GameObjectBuilder) -> DataParserResult<Option<GameObjectDiff>> {
+    let mut changes = Vec::new();
+    compare("", Some(&toml::Value::try_from(before)?), Some(&toml::Value::try_from(after)?), &mut changes);
+    if changes.is_empty() {
+        return Ok(None);
+    }
+
+    let rotation_changed = changes.iter().any(|change| is_rotation_path(change.path.as_str()));
+    let rotation_angle = if rotation_changed {
+        Some(angle_between(before.transform().quaternion(), after.transform().quaternion()))
+    } else {
+        None
+    };
+    Ok(Some(GameObjectDiff {
+        id: after.id().to_owned(),
+        changes,
+        rotation_angle,
+    }))
+}
+
+// Compares two levels. The added and modified gameobjects are in the order of the new level, the
+// removed ones in the order of the previous level.
+pub(crate) fn diff<'a>(before: &'a LevelDescription, after: &'a LevelDescription) -> DataParserResult<LevelDiff<'a>> {
+    debug!("Comparing the levels '{}' and '{}'.", before.title(), after.title());
+    let before_gameobjects = by_id(before)?;
+    let after_gameobjects = by_id(after)?;
+
+    let mut level_diff = LevelDiff {
+        title: if before.title() != after.title() { Some((before.title(), after.title())) } else { None },
+        added: Vec::new(),
+        removed: Vec::new(),
+        modified: Vec::new(),
+    };
+    for gameobject in after.slice() {
+        match before_gameobjects.get(gameobject.id()) {
+            Some(previous) => {
+                if let Some(gameobject_diff) = compare_gameobjects(previous, gameobject)? {
+                    level_diff.modified.push(gameobject_diff);
+                }
+            },
+            None => level_diff.added.push(gameobject),
+        }
+    }
+    level_diff.removed = before.slice().iter().filter(|gameobject| !after_gameobjects.contains_key(gameobject.id())).collect();
+    Ok(level_diff)
+}
+
+// A value on one line, in the TOML syntax.
+fn inline(value: Option<&toml::Value>) -> String {
+    match value {
+        None => String::from("none"),
+        Some(&toml::Value::Array(ref array)) => {
+            let elements: Vec<String> = array.iter().map(|element| inline(Some(element))).collect();
+            format!("[{}]", elements.join(", "))
+        },
+        Some(&toml::Value::Table(ref table)) => {
+            let entries: Vec<String> = table.iter().map(|(key, value)| format!("{} = {}", key, inline(Some(value)))).collect();
+            format!("{{ {} }}", entries.join(", "))
+        },
+        Some(other) => other.to_string(),
+    }
+}
+
+// An angle in degrees, with at most 3 decimals.
+fn format_angle(angle: f64) -> String {
+    let angle = format!("{:.3}", angle);
+    angle.trim_end_matches('0').trim_end_matches('.').to_owned()
+}
+
+fn json_value(value: Option<&toml::Value>) -> DataParserResult<serde_json::Value> {
+    match value {
+        Some(value) => data_format::to_json(value),
+        None => Ok(serde_json::Value::Null),
+    }
+}
+
+fn json_numbers(numbers: Option<Vec<f64>>) -> serde_json::Value {
+    match numbers {
+        Some(numbers) => serde_json::Value::Array(numbers.into_iter().map(json_number).collect()),
+        None => serde_json::Value::Null,
+    }
+}
+
+// A number, null if JSON can't represent it.
+fn json_number(number: f64) -> serde_json::Value {
+    serde_json::Number::from_f64(number).map(serde_json::Value::Number).unwrap_or(serde_json::Value::Null)
+}
+
+fn json_object(entries: Vec<(&str, serde_json::Value)>) -> serde_json::Value {
+    serde_json::Value::Object(entries.into_iter().map(|(key, value)| (key.to_owned(), value)).collect())
+}
+
+impl<'a> LevelDiff<'a> {
+    /// The previous and new titles of the level, if the title changed.
+    pub fn title(&self) -> Option<(&str, &str)> {
+        self.title
+    }
+
+    /// The gameobjects of the new level which are not in the previous one.
+    pub fn added(&self) -> &[&'a GameObjectBuilder] {
+        &self.added
+    }
+
+    /// The gameobjects of the previous level which are not in the new one.
+    pub fn removed(&self) -> &[&'a GameObjectBuilder] {
+        &self.removed
+    }
+
+    /// The gameobjects found in both levels, which changed.
+    pub fn modified(&self) -> &[GameObjectDiff] {
+        &self.modified
+    }
+
+    /// The changes of a gameobject found in both levels, if it changed.
+    pub fn gameobject(&self, id: &str) -> Option<&GameObjectDiff> {
+        self.modified.iter().find(|gameobject_diff| gameobject_diff.id == id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+
+    /// The difference in JSON (see the format at the top of level_diff.rs).
+    pub fn as_json(&self) -> DataParserResult<serde_json::Value> {
+        let title = match self.title {
+            Some((before, after)) => json_object(vec![
+                ("before", serde_json::Value::from(before)),
+                ("after", serde_json::Value::from(after)),
+            ]),
+            None => serde_json::Value::Null,
+        };
+        let gameobjects = |gameobjects: &[&GameObjectBuilder]| -> DataParserResult<serde_json::Value> {
+            let values = gameobjects.iter().map(|gameobject| data_format::to_json(&toml::Value::try_from(gameobject)?)).collect::<DataParserResult<_>>()?;
+            Ok(serde_json::Value::Array(values))
+        };
+
+        let mut modified = Vec::new();
+        for gameobject_diff in self.modified.iter() {
+            let mut changes = Vec::new();
+            for change in gameobject_diff.changes.iter() {
+                changes.push(json_object(vec![
+                    ("path", serde_json::Value::from(change.path.as_str())),
+                    ("before", json_value(change.before())?),
+                    ("after", json_value(change.after())?),
+                    ("delta", json_numbers(change.delta())),
+                ]));
+            }
+            modified.push(json_object(vec![
+                ("id", serde_json::Value::from(gameobject_diff.id.as_str())),
+                ("rotation_angle", gameobject_diff.rotation_angle.map(json_number).unwrap_or(serde_json::Value::Null)),
+                ("changes", serde_json::Value::Array(changes)),
+            ]));
+        }
+
+        Ok(json_object(vec![
+            ("title", title),
+            ("added", gameobjects(&self.added)?),
+            ("removed", gameobjects(&self.removed)?),
+            ("modified", serde_json::Value::Array(modified)),
+        ]))
+    }
+
+    /// The difference as a JSON string.
+    pub fn as_string_json(&self) -> DataParserResult<String> {
+        serde_json::to_string_pretty(&self.as_json()?).map_err(|json_error| {
+            DataParserError::JsonError(String::from("Could not write the difference in JSON."), json_error)
+        })
+    }
+}
+
+impl<'a> fmt::Display for LevelDiff<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "No difference.");
+        }
+
+        let mut lines = Vec::new();
+        if let Some((before, after)) = self.title {
+            lines.push(format!("title: {:?} -> {:?}", before, after));
+        }
+        for gameobject in self.added.iter() {
+            lines.push(format!("+ {}", gameobject.id()));
+        }
+        for gameobject in self.removed.iter() {
+            lines.push(format!("- {}", gameobject.id()));
+        }
+        for gameobject_diff in self.modified.iter() {
+            lines.push(format!("~ {}", gameobject_diff.id));
+            for change in gameobject_diff.changes.iter() {
+                let mut line = format!("    {}: {} -> {}", change.path, inline(change.before()), inline(change.after()));
+                let rotation_angle = gameobject_diff.rotation_angle.filter(|_| is_rotation_path(change.path.as_str()));
+                if let Some(angle) = rotation_angle {
+                    line.push_str(format!(" (rotated by {}°)", format_angle(angle)).as_str());
+                } else if let Some(delta) = change.delta() {
+                    line.push_str(format!(" (delta {})", inline(Some(&toml::Value::from(delta)))).as_str());
+                }
+                lines.push(line);
+            }
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
@@ -0,0 +1,235 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::collections::HashMap;
+use data_parser_error::{DataParserError, DataParserResult};
+use gameobject_builder::GameObjectBuilder;
+use transform_description::TransformDescription;
+use rotation_description::multiply;
+
+/// A transform relative to the root of the level, the rotation being a unit quaternion [x, y, z, w].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldTransform {
+    position: [f64; 3],
+    rotation: [f64; 4],
+    scale: [f64; 3],
+}
+
+impl<'a> From<&'a TransformDescription> for WorldTransform {
+    fn from(transform: &'a TransformDescription) -> Self {
+        WorldTransform {
+            position: transform.position(),
+            rotation: transform.quaternion(),
+            scale: transform.scale(),
+        }
+    }
+}
+
+// Rotates a vector by a unit quaternion [x, y, z, w].
+fn rotate(quaternion: [f64; 4], vector: [f64; 3]) -> [f64; 3] {
+    let (x, y, z, w) = (quaternion[0], quaternion[1], quaternion[2], quaternion[3]);
+    // t = 2 * (q.xyz × v)
+    let t = [
+        2.0 * (y * vector[2] - z * vector[1]),
+        2.0 * (z * vector[0] - x * vector[2]),
+        2.0 * (x * vector[1] - y * vector[0]),
+    ];
+    // v + w * t + q.xyz × t
+    [
+        vector[0] + w * t[0] + (y * t[2] - z * t[1]),
+        vector[1] + w * t[1] + (z * t[0] - x * t[2]),
+        vector[2] + w * t[2] + (x * t[1] - y * t[0]),
+    ]
+}
+
+impl WorldTransform {
+    // The world transform of a child, given its local transform. Shearing, caused by
+    // a non-uniform scale combined with a rotation, is not represented.
+    fn compose(&self, local: &TransformDescription) -> Self {
+        let local_position = local.position();
+        let local_scale = local.scale();
+        let scaled_position = [
+            local_position[0] * self.scale[0],
+            local_position[1] * self.scale[1],
+            local_position[2] * self.scale[2],
+        ];
+        let rotated_position = rotate(self.rotation, scaled_position);
+
+        WorldTransform {
+            position: [
+                self.position[0] + rotated_position[0],
+                self.position[1] + rotated_position[1],
+                self.position[2] + rotated_position[2],
+            ],
+            rotation: multiply(self.rotation, local.quaternion()),
+            scale: [
+                self.scale[0] * local_scale[0],
+                self.scale[1] * local_scale[1],
+                self.scale[2] * local_scale[2],
+            ],
+        }
+    }
+
+    pub fn position(&self) -> [f64; 3] {
+        self.position
+    }
+
+    pub fn rotation(&self) -> [f64; 4] {
+        self.rotation
+    }
+
+    pub fn scale(&self) -> [f64; 3] {
+        self.scale
+    }
+}
+
+/// A gameobject of the level, with its place in the hierarchy.
+#[derive(Debug)]
+pub struct HierarchyNode<'a> {
+    gameobject: &'a GameObjectBuilder,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    world_transform: WorldTransform,
+}
+
+impl<'a> HierarchyNode<'a> {
+    pub fn gameobject(&self) -> &'a GameObjectBuilder {
+        self.gameobject
+    }
+
+    pub fn id(&self) -> &'a str {
+        self.gameobject.id()
+    }
+
+    /// The index of the parent node, if any.
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    /// The indices of the children nodes.
+    pub fn children(&self) -> &[usize] {
+        &self.children
+    }
+
+    pub fn local_transform(&self) -> &'a TransformDescription {
+        self.gameobject.transform()
+    }
+
+    pub fn world_transform(&self) -> &WorldTransform {
+        &self.world_transform
+    }
+}
+
+/// The parent/child relationships between the gameobjects of a level. The nodes are in the same
+/// order as the gameobjects of the LevelDescription.
+#[derive(Debug)]
+pub struct LevelHierarchy<'a> {
+    nodes: Vec<HierarchyNode<'a>>,
+}
+
+impl<'a> LevelHierarchy<'a> {
+    pub fn new(gameobjects: &'a [GameObjectBuilder]) -> DataParserResult<Self> {
+        debug!("Creating a new LevelHierarchy.");
+        let mut indices: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (index, gameobject) in gameobjects.iter().enumerate() {
+            indices.entry(gameobject.id()).or_default().push(index);
+        }
+
+        let mut parents = Vec::with_capacity(gameobjects.len());
+        for gameobject in gameobjects.iter() {
+            let parent = match gameobject.parent() {
+                Some(parent_id) => {
+                    match indices.get(parent_id) {
+                        Some(parent_indices) if parent_indices.len() == 1 => Some(parent_indices[0]),
+                        Some(_) => {
+                            return Err(DataParserError::HierarchyError(format!("Gameobject '{}' declares '{}' as its parent, but several gameobjects have this id.", gameobject.id(), parent_id)));
+                        },
+                        None => {
+                            return Err(DataParserError::HierarchyError(format!("Gameobject '{}' declares '{}' as its parent, but no gameobject has this id.", gameobject.id(), parent_id)));
+                        },
+                    }
+                },
+                None => None,
+            };
+            parents.push(parent);
+        }
+
+        // Compute the world transforms from the roots, walking up the parents of each gameobject
+        // until a computed ancestor is found.
+        let mut world_transforms: Vec<Option<WorldTransform>> = vec![None; gameobjects.len()];
+        for index in 0..gameobjects.len() {
+            let mut path = Vec::new();
+            let mut current = Some(index);
+
+            while let Some(current_index) = current {
+                if world_transforms[current_index].is_some() {
+                    break;
+                }
+                if path.contains(&current_index) {
+                    let cycle: Vec<&str> = path.iter().map(|&path_index: &usize| gameobjects[path_index].id()).collect();
+                    return Err(DataParserError::HierarchyError(format!("Cyclic parenting detected: {} -> {}.", cycle.join(" -> "), gameobjects[current_index].id())));
+                }
+                path.push(current_index);
+                current = parents[current_index];
+            }
+
+            for &path_index in path.iter().rev() {
+                let local = gameobjects[path_index].transform();
+                let world_transform = match parents[path_index] {
+                    Some(parent_index) => {
+                        world_transforms[parent_index].expect("The world transform of the parent should have been computed").compose(local)
+                    },
+                    None => WorldTransform::from(local),
+                };
+                world_transforms[path_index] = Some(world_transform);
+            }
+        }
+
+        let mut nodes: Vec<HierarchyNode<'a>> = gameobjects
+            .iter()
+            .zip(parents.iter())
+            .zip(world_transforms)
+            .map(|((gameobject, &parent), world_transform)| {
+                HierarchyNode {
+                    gameobject,
+                    parent,
+                    children: Vec::new(),
+                    world_transform: world_transform.expect("All the world transforms should have been computed"),
+                }
+            })
+            .collect();
+
+        for (index, parent) in parents.into_iter().enumerate() {
+            if let Some(parent_index) = parent {
+                nodes[parent_index].children.push(index);
+            }
+        }
+
+        Ok(LevelHierarchy {
+            nodes,
+        })
+    }
+
+    pub fn nodes(&self) -> &[HierarchyNode<'a>] {
+        &self.nodes
+    }
+
+    /// The indices of the nodes without parent.
+    pub fn roots(&self) -> Vec<usize> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|&(_, node)| node.parent.is_none())
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// The first node with the given id.
+    pub fn node(&self, id: &str) -> Option<&HierarchyNode<'a>> {
+        self.nodes.iter().find(|node| node.id() == id)
+    }
+}
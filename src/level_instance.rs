@@ -0,0 +1,84 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use maskerad_gameobject_model::gameobject::GameObject;
+use data_parser_error::DataParserError;
+
+/// What to do with the gameobjects which could not be built when instantiating a level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstantiationMode {
+    /// The instantiation fails if any gameobject could not be built, reporting all the failures.
+    Strict,
+    /// The broken gameobjects are skipped, and reported as failures of the LevelInstance.
+    SkipBroken,
+}
+
+impl Default for InstantiationMode {
+    fn default() -> Self {
+        InstantiationMode::Strict
+    }
+}
+
+/// A runtime gameobject, with its id.
+pub type InstantiatedGameObject = (String, GameObject);
+
+/// A gameobject which could not be built, with its id.
+pub type InstantiationFailure = (String, DataParserError);
+
+/// The runtime gameobjects of a level, with their ids.
+pub struct LevelInstance {
+    title: String,
+    gameobjects: Vec<InstantiatedGameObject>,
+    failures: Vec<InstantiationFailure>,
+}
+
+impl LevelInstance {
+    pub fn new<I: Into<String>>(title: I) -> Self {
+        debug!("Creating a new LevelInstance.");
+        LevelInstance {
+            title: title.into(),
+            gameobjects: Vec::new(),
+            failures: Vec::new(),
+        }
+    }
+
+    pub fn add_gameobject<I: Into<String>>(&mut self, id: I, gameobject: GameObject) -> &mut Self {
+        self.gameobjects.push((id.into(), gameobject));
+        self
+    }
+
+    pub fn add_failure<I: Into<String>>(&mut self, id: I, error: DataParserError) -> &mut Self {
+        self.failures.push((id.into(), error));
+        self
+    }
+
+    pub fn title(&self) -> &str {
+        self.title.as_str()
+    }
+
+    pub fn gameobjects(&self) -> &[InstantiatedGameObject] {
+        debug!("Getting an immutable slice over all the GameObjects in the LevelInstance.");
+        &self.gameobjects
+    }
+
+    pub fn failures(&self) -> &[InstantiationFailure] {
+        debug!("Getting an immutable slice over all the failures of the LevelInstance.");
+        &self.failures
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    pub fn into_gameobjects(self) -> Vec<InstantiatedGameObject> {
+        self.gameobjects
+    }
+
+    pub fn into_parts(self) -> (Vec<InstantiatedGameObject>, Vec<InstantiationFailure>) {
+        (self.gameobjects, self.failures)
+    }
+}
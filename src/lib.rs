@@ -5,18 +5,52 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+// The crate matches on references and implements its defaults by hand.
+#![allow(clippy::match_ref_pats, clippy::needless_borrowed_reference, clippy::derivable_impls, clippy::useless_format)]
+
 extern crate toml;
+extern crate toml_edit;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde;
+extern crate serde_path_to_error;
+extern crate serde_json;
+extern crate ron;
+extern crate serde_ignored;
+extern crate strsim;
+extern crate base64;
 extern crate gltf;
+extern crate zip;
+extern crate crc32fast;
 extern crate maskerad_gameobject_model;
+#[cfg(feature = "maskerad_filesystem")]
+extern crate maskerad_filesystem;
 
 #[macro_use]
 extern crate log;
 
 pub mod gameobject_builder;
 pub mod level_description;
+pub mod level_instance;
+pub mod level_hierarchy;
+pub mod level_diff;
 pub mod data_parser_error;
+pub mod error_location;
+pub mod diagnostic;
+pub mod unknown_key;
+pub mod migration;
+pub mod data_format;
+pub mod canonical;
+mod atomic_file;
+pub mod document_editor;
+pub mod asset_path;
+pub mod virtual_filesystem;
+pub mod pack;
+pub mod binary_format;
+mod gltf_import;
+pub mod gltf_export;
 pub mod transform_description;
-pub mod mesh_description;
\ No newline at end of file
+pub mod rotation_description;
+pub mod mesh_description;
+pub mod component_description;
+mod reference_resolver;
\ No newline at end of file
@@ -6,13 +6,186 @@
 // copied, modified, or distributed except according to those terms.
 
 use maskerad_gameobject_model::properties::mesh::Mesh;
-use data_parser_error::DataParserResult;
+use data_parser_error::{DataParserError, DataParserResult};
 use std::path::Path;
-use gltf::Gltf;
+use std::io;
+use virtual_filesystem::{VirtualFilesystem, NativeFilesystem};
+use gltf::{self, Gltf};
+use gltf::json::{self, validation};
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::de;
+use std::fmt;
+use std::collections::HashSet;
 
-#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+/*
+    Mesh structure:
+    [mesh]
+    path = "path to the glTF asset"
+
+    The mesh can be a part of the asset, selected by name or by index:
+    mesh = "mesh name"      # a mesh,
+    primitive = 0           # optionally, one of its primitives,
+    node = "node name"      # or a node, with its children,
+    scene = 0               # or a scene.
+*/
+
+/// A mesh, node or scene of a glTF asset, by name or by index.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum GltfReference {
+    Index(usize),
+    Name(String),
+}
+
+impl GltfReference {
+    pub(crate) fn matches(&self, index: usize, name: Option<&str>) -> bool {
+        match self {
+            &GltfReference::Index(reference_index) => reference_index == index,
+            &GltfReference::Name(ref reference_name) => name == Some(reference_name.as_str()),
+        }
+    }
+
+    // The path of the referenced object in the glTF document, and the validation error if it doesn't exist.
+    fn missing(&self, field: &str) -> (json::Path, validation::Error) {
+        match self {
+            &GltfReference::Index(index) => (json::Path::new().field(field).index(index), validation::Error::IndexOutOfBounds),
+            &GltfReference::Name(_) => (json::Path::new().field(field), validation::Error::Missing),
+        }
+    }
+}
+
+impl From<usize> for GltfReference {
+    fn from(index: usize) -> Self {
+        GltfReference::Index(index)
+    }
+}
+
+impl<'a> From<&'a str> for GltfReference {
+    fn from(name: &'a str) -> Self {
+        GltfReference::Name(name.to_owned())
+    }
+}
+
+impl From<String> for GltfReference {
+    fn from(name: String) -> Self {
+        GltfReference::Name(name)
+    }
+}
+
+impl fmt::Display for GltfReference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &GltfReference::Index(index) => write!(f, "{}", index),
+            &GltfReference::Name(ref name) => write!(f, "'{}'", name),
+        }
+    }
+}
+
+/// The part of a glTF asset used as the mesh of a gameobject.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeshSelection {
+    /// A mesh, or only one of its primitives.
+    Mesh {
+        mesh: GltfReference,
+        primitive: Option<usize>,
+    },
+    /// The meshes of a node and of its children.
+    Node(GltfReference),
+    /// The meshes of the nodes of a scene.
+    Scene(GltfReference),
+}
+
+impl fmt::Display for MeshSelection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &MeshSelection::Mesh { ref mesh, primitive: Some(primitive) } => write!(f, "the primitive {} of the mesh {}", primitive, mesh),
+            &MeshSelection::Mesh { ref mesh, primitive: None } => write!(f, "the mesh {}", mesh),
+            &MeshSelection::Node(ref node) => write!(f, "the node {}", node),
+            &MeshSelection::Scene(ref scene) => write!(f, "the scene {}", scene),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct MeshDescription {
     path: String,
+    selection: Option<MeshSelection>,
+}
+
+// The mesh, as written in the file.
+#[derive(Deserialize, Serialize, Debug)]
+struct MeshData {
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mesh: Option<GltfReference>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    primitive: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    node: Option<GltfReference>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scene: Option<GltfReference>,
+}
+
+impl Serialize for MeshDescription {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer
+    {
+        let mut mesh_data = MeshData {
+            path: self.path.clone(),
+            mesh: None,
+            primitive: None,
+            node: None,
+            scene: None,
+        };
+
+        match self.selection {
+            Some(MeshSelection::Mesh { ref mesh, primitive }) => {
+                mesh_data.mesh = Some(mesh.clone());
+                mesh_data.primitive = primitive;
+            },
+            Some(MeshSelection::Node(ref node)) => mesh_data.node = Some(node.clone()),
+            Some(MeshSelection::Scene(ref scene)) => mesh_data.scene = Some(scene.clone()),
+            None => {},
+        }
+
+        mesh_data.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MeshDescription {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where
+        D: Deserializer<'de>
+    {
+        let MeshData { path, mesh, primitive, node, scene } = MeshData::deserialize(deserializer)?;
+
+        let selection = match (mesh, node, scene) {
+            (None, None, None) => None,
+            (Some(mesh), None, None) => Some(MeshSelection::Mesh { mesh, primitive }),
+            (None, Some(node), None) => Some(MeshSelection::Node(node)),
+            (None, None, Some(scene)) => Some(MeshSelection::Scene(scene)),
+            _ => return Err(de::Error::custom("Only one of mesh.mesh, mesh.node and mesh.scene can be given.")),
+        };
+
+        match selection {
+            Some(MeshSelection::Mesh { .. }) => {},
+            _ if primitive.is_some() => return Err(de::Error::custom("mesh.primitive can only be given with mesh.mesh.")),
+            _ => {},
+        }
+
+        Ok(MeshDescription {
+            path,
+            selection,
+        })
+    }
+}
+
+// Whether a node, or one of its children, has a mesh. The visited nodes are skipped: the node graph
+// of a malformed asset can have cycles.
+fn node_has_mesh(node: &gltf::Node, visited: &mut HashSet<usize>) -> bool {
+    if !visited.insert(node.index()) {
+        return false;
+    }
+    node.mesh().is_some() || node.children().any(|child| node_has_mesh(&child, visited))
 }
 
 
@@ -21,9 +194,155 @@ impl MeshDescription {
         debug!("Creating a new MeshDescription.");
         MeshDescription {
             path: path.into(),
+            selection: None,
         }
     }
 
+    pub fn add_selection<S: Into<Option<MeshSelection>>>(&mut self, selection: S) -> &mut Self {
+        debug!("Adding a MeshSelection to the MeshDescription.");
+        self.selection = selection.into();
+        self
+    }
+
+    /// Fails if the mesh selects a part of its glTF asset, the runtime Mesh loading whole assets.
+    pub fn build(&self) -> DataParserResult<Mesh> {
+        debug!("Creating a Mesh from the MeshDescription.");
+        if self.path.trim().is_empty() {
+            return Err(DataParserError::BuildError(format!("The mesh.path must not be empty.")));
+        }
+        // The runtime mesh loads the whole asset: building it would silently drop the selection.
+        if let Some(ref selection) = self.selection {
+            return Err(DataParserError::BuildError(format!("The mesh '{}' selects {} of the asset, but only whole glTF assets can be built.", self.path, selection)));
+        }
+
+        Ok(Mesh::new(self.path.as_str()))
+    }
+
+    /// Checks that the mesh file is a glTF asset containing at least one mesh, that the selected part
+    /// of the asset exists and contains a mesh, and that its buffers and images can be found. The path
+    /// of the mesh is relative to the given directory.
+    pub fn check_asset<P: AsRef<Path>>(&self, directory: P) -> DataParserResult<()> {
+        self.check_asset_in(directory, &NativeFilesystem)
+    }
+
+    /// Checks the glTF asset like check_asset, reading the asset and its files from the filesystem.
+    pub fn check_asset_in<P: AsRef<Path>>(&self, directory: P, filesystem: &dyn VirtualFilesystem) -> DataParserResult<()> {
+        let asset_path = directory.as_ref().join(self.path.as_str());
+        debug!("Checking the glTF asset {}.", asset_path.display());
+
+        let asset = filesystem.read_to_end(asset_path.as_path())
+            .map_err(gltf::Error::Io)
+            .and_then(|content| Gltf::from_slice(content.as_slice()))
+            .map_err(|gltf_error| {
+                DataParserError::GltfError(format!("Could not open the glTF asset '{}'.", asset_path.display()), gltf_error)
+            })?;
+
+        if asset.meshes().len() == 0 {
+            let gltf_error = gltf::Error::Validation(vec![(json::Path::new().field("meshes"), validation::Error::Missing)]);
+            return Err(DataParserError::GltfError(format!("The glTF asset '{}' contains no mesh.", asset_path.display()), gltf_error));
+        }
+
+        self.check_selection(&asset, asset_path.as_path())?;
+
+        // The external files are relative to the asset.
+        let asset_directory = asset_path.parent().unwrap_or(Path::new(""));
+        for buffer in asset.buffers() {
+            match buffer.source() {
+                gltf::buffer::Source::Bin => {
+                    if asset.blob.is_none() {
+                        return Err(DataParserError::GltfError(format!("The buffer {} of the glTF asset '{}' is stored in its binary chunk, which is missing.", buffer.index(), asset_path.display()), gltf::Error::MissingBlob));
+                    }
+                },
+                gltf::buffer::Source::Uri(uri) => {
+                    if uri.starts_with("data:") {
+                        continue;
+                    }
+                    let buffer_path = asset_directory.join(uri);
+                    let length = filesystem.open(buffer_path.as_path()).and_then(|mut reader| io::copy(&mut reader, &mut io::sink())).map_err(|io_error| {
+                        DataParserError::GltfError(format!("The buffer {} of the glTF asset '{}' could not be found at '{}'.", buffer.index(), asset_path.display(), buffer_path.display()), gltf::Error::Io(io_error))
+                    })?;
+                    if (length as usize) < buffer.length() {
+                        let gltf_error = gltf::Error::BufferLength {
+                            buffer: buffer.index(),
+                            expected: buffer.length(),
+                            actual: length as usize,
+                        };
+                        return Err(DataParserError::GltfError(format!("The buffer {} of the glTF asset '{}' is truncated.", buffer.index(), asset_path.display()), gltf_error));
+                    }
+                },
+            }
+        }
+
+        for image in asset.images() {
+            if let gltf::image::Source::Uri { uri, .. } = image.source() {
+                if uri.starts_with("data:") {
+                    continue;
+                }
+                let image_path = asset_directory.join(uri);
+                if !filesystem.is_file(image_path.as_path()) {
+                    let io_error = io::Error::new(io::ErrorKind::NotFound, format!("'{}' does not exist", image_path.display()));
+                    return Err(DataParserError::GltfError(format!("The image {} of the glTF asset '{}' could not be found at '{}'.", image.index(), asset_path.display(), image_path.display()), gltf::Error::Io(io_error)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_selection(&self, asset: &Gltf, asset_path: &Path) -> DataParserResult<()> {
+        let missing = |kind: &str, field: &str, reference: &GltfReference| {
+            let gltf_error = gltf::Error::Validation(vec![reference.missing(field)]);
+            DataParserError::GltfError(format!("The glTF asset '{}' has no {} {}.", asset_path.display(), kind, reference), gltf_error)
+        };
+        let no_mesh = |kind: &str, field: &str, reference: &GltfReference, index: usize| {
+            let gltf_error = gltf::Error::Validation(vec![(json::Path::new().field(field).index(index), validation::Error::Invalid)]);
+            DataParserError::GltfError(format!("The {} {} of the glTF asset '{}' contains no mesh.", kind, reference, asset_path.display()), gltf_error)
+        };
+
+        match self.selection {
+            None => {},
+            Some(MeshSelection::Mesh { ref mesh, primitive }) => {
+                let selected_mesh = asset.meshes().find(|candidate| mesh.matches(candidate.index(), candidate.name())).ok_or_else(|| {
+                    missing("mesh", "meshes", mesh)
+                })?;
+                if let Some(primitive) = primitive {
+                    if primitive >= selected_mesh.primitives().len() {
+                        let path = json::Path::new().field("meshes").index(selected_mesh.index()).field("primitives").index(primitive);
+                        let gltf_error = gltf::Error::Validation(vec![(path, validation::Error::IndexOutOfBounds)]);
+                        return Err(DataParserError::GltfError(format!("The mesh {} of the glTF asset '{}' has no primitive {}.", mesh, asset_path.display(), primitive), gltf_error));
+                    }
+                }
+            },
+            Some(MeshSelection::Node(ref node)) => {
+                let selected_node = asset.nodes().find(|candidate| node.matches(candidate.index(), candidate.name())).ok_or_else(|| {
+                    missing("node", "nodes", node)
+                })?;
+                if !node_has_mesh(&selected_node, &mut HashSet::new()) {
+                    return Err(no_mesh("node", "nodes", node, selected_node.index()));
+                }
+            },
+            Some(MeshSelection::Scene(ref scene)) => {
+                let selected_scene = asset.scenes().find(|candidate| scene.matches(candidate.index(), candidate.name())).ok_or_else(|| {
+                    missing("scene", "scenes", scene)
+                })?;
+                let mut visited = HashSet::new();
+                if !selected_scene.nodes().any(|node| node_has_mesh(&node, &mut visited)) {
+                    return Err(no_mesh("scene", "scenes", scene, selected_scene.index()));
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn set_path(&mut self, path: String) {
+        self.path = path;
+    }
+
+    pub fn selection(&self) -> Option<&MeshSelection> {
+        self.selection.as_ref()
+    }
+
     pub fn path(&self) -> &str {
         debug!("Getting the path to the mesh data.");
         self.path.as_str()
@@ -0,0 +1,267 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use toml;
+use toml::value::Table;
+use toml_edit::{self, DocumentMut, Item, Key};
+use serde::Serialize;
+use std::path::Path;
+use std::fs::File;
+use std::io::Read;
+use data_parser_error::{DataParserError, DataParserResult};
+use unknown_key::DocumentKind;
+use data_format::{self, DataFormat};
+use atomic_file;
+
+/*
+    Level and gameobject files declare the version of their format at their root:
+    format_version = 2
+
+    The files without format_version are version 1 files. Before being deserialized, a document
+    is upgraded to the current version by the migration steps of MIGRATIONS, each one upgrading
+    the document from its version to the next one.
+
+    Version history:
+    1: the documents of the first releases, which don't declare their version.
+    2: the documents declare their format_version. Their keys are the keys of the version 1.
+
+    migrate_file upgrades the TOML files in place: each migration step has an edit applied to the
+    toml_edit document, so the comments and formatting of the file are kept. The JSON and RON
+    files, and the TOML files whose edited document doesn't match the migrated one, are rewritten
+    from the migrated document, dropping their comments and formatting.
+*/
+
+/// The version of the format written by this crate.
+pub const CURRENT_FORMAT_VERSION: u32 = 2;
+
+const FORMAT_VERSION_KEY: &str = "format_version";
+
+// A step upgrading a document from the version 'from' to the version 'from + 1'. The edit applies
+// the same change to a toml_edit document, keeping its formatting.
+struct Migration {
+    from: u32,
+    description: &'static str,
+    step: fn(&mut Table, DocumentKind),
+    edit: fn(&mut toml_edit::Table, DocumentKind),
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 1,
+        description: "the keys are unchanged, only the format version is written",
+        step: keep_document,
+        edit: keep_edited_document,
+    },
+];
+
+// Applies the function to the gameobject tables of the document, and to their children.
+pub(crate) fn for_each_gameobject<F>(document: &mut Table, kind: DocumentKind, function: &mut F) where
+    F: FnMut(&mut Table)
+{
+    fn visit<F: FnMut(&mut Table)>(gameobject: &mut Table, function: &mut F) {
+        function(gameobject);
+        if let Some(&mut toml::Value::Array(ref mut children)) = gameobject.get_mut("children") {
+            for child in children.iter_mut() {
+                if let toml::Value::Table(ref mut child) = *child {
+                    visit(child, function);
+                }
+            }
+        }
+    }
+
+    match kind {
+        DocumentKind::GameObject => visit(document, function),
+        DocumentKind::Level => {
+            if let Some(&mut toml::Value::Array(ref mut gameobjects)) = document.get_mut("gameobjects") {
+                for gameobject in gameobjects.iter_mut() {
+                    // The references are strings, or tables with only a 'path' key: they are left untouched.
+                    if let toml::Value::Table(ref mut gameobject) = *gameobject {
+                        visit(gameobject, function);
+                    }
+                }
+            }
+        },
+    }
+}
+
+fn keep_document(_: &mut Table, _: DocumentKind) {}
+
+fn keep_edited_document(_: &mut toml_edit::Table, _: DocumentKind) {}
+
+// Writes the current format version at the root of a toml_edit document, as its first key. The
+// comments heading the document stay on top.
+fn edit_format_version(document: &mut toml_edit::Table) {
+    if let Some(format_version) = document.get_mut(FORMAT_VERSION_KEY) {
+        if let Some(value) = format_version.as_value_mut() {
+            let decor = value.decor().clone();
+            *value = toml_edit::Value::from(i64::from(CURRENT_FORMAT_VERSION));
+            *value.decor_mut() = decor;
+            return;
+        }
+    }
+
+    let keys: Vec<String> = document.iter().map(|(key, _)| key.to_owned()).collect();
+    let mut entries: Vec<(Key, Item)> = keys.iter().filter_map(|key| document.remove_entry(key.as_str())).collect();
+    let mut format_version_key = Key::new(FORMAT_VERSION_KEY);
+    if let Some(&mut (ref mut first_key, ref first_item)) = entries.first_mut() {
+        if first_item.is_value() {
+            *format_version_key.leaf_decor_mut() = first_key.leaf_decor().clone();
+            first_key.leaf_decor_mut().set_prefix("");
+        }
+    }
+    document.insert_formatted(&format_version_key, toml_edit::value(i64::from(CURRENT_FORMAT_VERSION)));
+    for (key, item) in entries {
+        document.insert_formatted(&key, item);
+    }
+}
+
+// Upgrades a TOML document of the given version in place, keeping its comments and formatting.
+// Returns None if the edited document doesn't match the migrated document.
+fn migrate_in_place(content: &str, version: u32, kind: DocumentKind, migrated: &toml::Value) -> Option<String> {
+    let mut document = content.parse::<DocumentMut>().ok()?;
+    for migration in MIGRATIONS.iter().filter(|migration| migration.from >= version) {
+        (migration.edit)(document.as_table_mut(), kind);
+    }
+    edit_format_version(document.as_table_mut());
+
+    let edited = document.to_string();
+    let edited_value = data_format::parse(edited.as_str(), DataFormat::Toml).ok()?;
+    match migrate(edited_value, kind) {
+        Ok((ref edited_value, CURRENT_FORMAT_VERSION)) if edited_value == migrated => Some(edited),
+        _ => None,
+    }
+}
+
+// The kind of a document, found from its keys.
+pub(crate) fn document_kind(document: &toml::Value) -> DocumentKind {
+    match document.get("gameobjects") {
+        Some(_) => DocumentKind::Level,
+        None => DocumentKind::GameObject,
+    }
+}
+
+// The version of a document, format_version being removed from the document.
+fn take_format_version(document: &mut Table) -> DataParserResult<u32> {
+    match document.remove(FORMAT_VERSION_KEY) {
+        None => Ok(1),
+        Some(toml::Value::Integer(version)) if version >= 1 && version <= i64::from(CURRENT_FORMAT_VERSION) => {
+            Ok(version as u32)
+        },
+        Some(toml::Value::Integer(version)) => {
+            Err(DataParserError::MigrationError(format!("The format version {} is not supported, the supported versions are 1 to {}.", version, CURRENT_FORMAT_VERSION)))
+        },
+        Some(other) => {
+            Err(DataParserError::MigrationError(format!("The format version must be an integer, found {}.", other)))
+        },
+    }
+}
+
+// Upgrades a document to the current version. Returns the upgraded document, without its
+// format_version, and the version it had.
+pub(crate) fn migrate(document: toml::Value, kind: DocumentKind) -> DataParserResult<(toml::Value, u32)> {
+    let mut table = match document {
+        toml::Value::Table(table) => table,
+        other => return Ok((other, CURRENT_FORMAT_VERSION)),
+    };
+
+    let original_version = take_format_version(&mut table)?;
+    let mut version = original_version;
+    while version < CURRENT_FORMAT_VERSION {
+        let migration = MIGRATIONS.iter().find(|migration| migration.from == version).ok_or_else(|| {
+            DataParserError::MigrationError(format!("No migration upgrades the format version {}.", version))
+        })?;
+        debug!("Upgrading a document from the format version {}: {}.", version, migration.description);
+        (migration.step)(&mut table, kind);
+        version += 1;
+    }
+
+    Ok((toml::Value::Table(table), original_version))
+}
+
+// Whether the migrations change the content of a document, its format_version aside, with the
+// version it had. The older documents they don't change are read and edited as they are.
+pub(crate) fn migrations_change(document: &toml::Value, kind: DocumentKind) -> DataParserResult<(bool, u32)> {
+    let mut original = document.clone();
+    if let Some(table) = original.as_table_mut() {
+        take_format_version(table)?;
+    }
+    let (migrated, version) = migrate(document.clone(), kind)?;
+    Ok((migrated != original, version))
+}
+
+// A document, serialized with the current format version at its root.
+#[derive(Serialize)]
+pub(crate) struct Versioned<'a, T: 'a + Serialize> {
+    format_version: u32,
+    #[serde(flatten)]
+    document: &'a T,
+}
+
+impl<'a, T: 'a + Serialize> Versioned<'a, T> {
+    pub(crate) fn new(document: &'a T) -> Self {
+        Versioned {
+            format_version: CURRENT_FORMAT_VERSION,
+            document,
+        }
+    }
+}
+
+// Upgrades a data file to the current version, rewriting it only if it had an older version.
+// The TOML files are upgraded in place, the other ones are rewritten without their comments.
+// The references and prefabs of the file are left untouched. Returns whether the file was rewritten.
+pub(crate) fn migrate_file(path: &Path, kind: DocumentKind) -> DataParserResult<bool> {
+    debug!("Upgrading the data file {} to the format version {}.", path.display(), CURRENT_FORMAT_VERSION);
+
+    let mut content = String::new();
+    File::open(path)?.read_to_string(&mut content)?;
+    let format = DataFormat::of_file(path);
+    let document = data_format::parse(content.as_ref(), format).map_err(|error| {
+        error.with_source(Some(path), content.as_ref(), format)
+    })?;
+
+    let (document, version) = migrate(document, kind)?;
+    if version == CURRENT_FORMAT_VERSION {
+        return Ok(false);
+    }
+
+    let in_place = match format {
+        DataFormat::Toml => migrate_in_place(content.as_ref(), version, kind, &document),
+        _ => None,
+    };
+    let document_string = match in_place {
+        Some(document_string) => document_string,
+        None => {
+            warn!("The data file {} is rewritten from its upgraded document, without its comments and formatting.", path.display());
+            data_format::to_string(&Versioned::new(&document), format)?
+        },
+    };
+    atomic_file::save(path, |writer| {
+        writer.write_all(document_string.as_bytes())?;
+        Ok(())
+    })?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod migration_test {
+    use super::*;
+
+    #[test]
+    fn migrations_are_complete() {
+        for version in 1..CURRENT_FORMAT_VERSION {
+            assert_eq!(MIGRATIONS.iter().filter(|migration| migration.from == version).count(), 1);
+        }
+    }
+
+    #[test]
+    fn migrate_inline_tables_in_place() {
+        let content = "title = \"inline\" # the title\ngameobjects = [\n    { id = \"a\", transform = { position = [0.0, 0.0, 0.0], rotation = [0.0, 1.0, 0.0], scale = [1.0, 1.0, 1.0] } },\n]\n";
+        let (migrated, version) = migrate(data_format::parse(content, DataFormat::Toml).unwrap(), DocumentKind::Level).unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(migrate_in_place(content, version, DocumentKind::Level, &migrated).unwrap(), format!("format_version = 2\n{}", content));
+    }
+}
@@ -0,0 +1,216 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::io::{self, Read, Seek, Write, Cursor};
+use std::fs::{self, File};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Component, Path, PathBuf};
+use zip::{ZipArchive, ZipWriter, CompressionMethod};
+use zip::write::FileOptions;
+use gltf::{self, Gltf};
+use data_parser_error::{DataParserError, DataParserResult};
+use gameobject_builder::GameObjectBuilder;
+use reference_resolver::ReferenceResolver;
+use asset_path::{self, PathResolver};
+use virtual_filesystem::{VirtualFilesystem, NativeFilesystem};
+use atomic_file;
+
+/*
+    A pack is a zip archive of data files and assets, read through a PackFilesystem with the same
+    loaders as the loose files (LevelDescription::load_from_filesystem...).
+
+    pack_level writes a level and all of its dependencies in a pack:
+    - the level file, the gameobject and level files it references, and their prefabs,
+    - the glTF assets of the meshes, with their external buffers and images.
+
+    The files are stored with their paths relative to the project root, or to the directory
+    containing all of them without project root, so the relative paths and asset paths written in
+    the files stay valid in the pack. The asset paths are resolved in the pack with an empty
+    project root.
+*/
+
+/// The files of a pack, the paths being relative to the root of the pack.
+pub struct PackFilesystem<R: Read + Seek> {
+    archive: RefCell<ZipArchive<R>>,
+    entries: HashSet<String>,
+}
+
+impl PackFilesystem<File> {
+    /// Opens the pack file at the given path.
+    pub fn open_pack<P: AsRef<Path>>(path: P) -> DataParserResult<Self> {
+        debug!("Opening the pack {}.", path.as_ref().display());
+        let file = File::open(path.as_ref()).map_err(|io_error| {
+            DataParserError::IoError(format!("Could not open the pack '{}'.", path.as_ref().display()), io_error)
+        })?;
+        PackFilesystem::from_reader(file)
+    }
+}
+
+impl<R: Read + Seek> PackFilesystem<R> {
+    pub fn from_reader(reader: R) -> DataParserResult<Self> {
+        let archive = ZipArchive::new(reader).map_err(|zip_error| {
+            DataParserError::IoError(String::from("Could not read the index of the pack."), io::Error::from(zip_error))
+        })?;
+        let entries = archive.file_names().map(|name| name.to_owned()).collect();
+        Ok(PackFilesystem {
+            archive: RefCell::new(archive),
+            entries,
+        })
+    }
+
+    /// The paths of the files of the pack.
+    pub fn entries(&self) -> Vec<&str> {
+        let mut entries: Vec<&str> = self.entries.iter().map(|entry| entry.as_str()).collect();
+        entries.sort();
+        entries
+    }
+
+    fn find(&self, path: &Path) -> io::Result<String> {
+        entry_name(path)
+            .filter(|name| self.entries.contains(name))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("'{}' is not in the pack", path.display())))
+    }
+}
+
+// The name of the entry of a path in a pack: its normalized components, separated by '/'.
+fn entry_name(path: &Path) -> Option<String> {
+    let mut segments = Vec::new();
+    for component in asset_path::normalize(path).components() {
+        match component {
+            Component::Normal(segment) => segments.push(segment.to_string_lossy().into_owned()),
+            Component::RootDir | Component::CurDir => {},
+            // The paths going out of the pack aren't in the pack.
+            Component::ParentDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(segments.join("/"))
+}
+
+impl<R: Read + Seek> VirtualFilesystem for PackFilesystem<R> {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read>> {
+        let name = self.find(path)?;
+        let mut archive = self.archive.borrow_mut();
+        let mut entry = archive.by_name(name.as_str()).map_err(io::Error::from)?;
+        let mut content = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut content)?;
+        Ok(Box::new(Cursor::new(content)))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.find(path).map(PathBuf::from)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.find(path).is_ok()
+    }
+}
+
+fn canonical_dependency(path: &Path, description: &str) -> DataParserResult<PathBuf> {
+    fs::canonicalize(path).map_err(|io_error| {
+        DataParserError::IoError(format!("Could not find {} '{}'.", description, path.display()), io_error)
+    })
+}
+
+// Adds the glTF assets of the meshes of a gameobject and of its children, with their external files.
+fn add_mesh_dependencies(gameobject: &GameObjectBuilder, level_directory: &Path, paths: &PathResolver, dependencies: &mut BTreeSet<PathBuf>) -> DataParserResult<()> {
+    if let Some(mesh) = gameobject.mesh() {
+        if Path::new(mesh.path()).is_absolute() {
+            return Err(DataParserError::ReferenceError(format!("Gameobject '{}': the mesh path '{}' is absolute, and can't be packed.", gameobject.id(), mesh.path())));
+        }
+
+        let asset_path = canonical_dependency(paths.resolve(mesh.path(), level_directory)?.as_path(), "the mesh")?;
+        let mut content = Vec::new();
+        File::open(asset_path.as_path())?.read_to_end(&mut content)?;
+        let asset = Gltf::from_slice(content.as_slice()).map_err(|gltf_error| {
+            DataParserError::GltfError(format!("Could not open the glTF asset '{}'.", asset_path.display()), gltf_error)
+        })?;
+
+        let asset_directory = asset_path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let buffer_uris = asset.buffers().filter_map(|buffer| match buffer.source() {
+            gltf::buffer::Source::Uri(uri) => Some(uri),
+            gltf::buffer::Source::Bin => None,
+        });
+        let image_uris = asset.images().filter_map(|image| match image.source() {
+            gltf::image::Source::Uri { uri, .. } => Some(uri),
+            gltf::image::Source::View { .. } => None,
+        });
+        for uri in buffer_uris.chain(image_uris).filter(|uri| !uri.starts_with("data:")) {
+            dependencies.insert(canonical_dependency(asset_directory.join(uri).as_path(), "the file of the glTF asset")?);
+        }
+        dependencies.insert(asset_path);
+    }
+
+    for child in gameobject.children() {
+        add_mesh_dependencies(child, level_directory, paths, dependencies)?;
+    }
+    Ok(())
+}
+
+// The deepest directory containing all the files.
+fn common_directory(files: &BTreeSet<PathBuf>) -> PathBuf {
+    let mut common: Option<PathBuf> = None;
+    for file in files {
+        let directory = file.parent().unwrap_or(Path::new(""));
+        common = Some(match common {
+            None => directory.to_path_buf(),
+            Some(common) => common
+                .components()
+                .zip(directory.components())
+                .take_while(|&(a, b)| a == b)
+                .map(|(component, _)| component.as_os_str())
+                .collect(),
+        });
+    }
+    common.unwrap_or_default()
+}
+
+/// Writes the level file and all of its dependencies in a pack, at the given path. Returns the path of
+/// the level in the pack.
+pub fn pack_level<P, Q>(level_path: P, pack_path: Q, paths: &PathResolver) -> DataParserResult<PathBuf> where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    debug!("Packing the level {} in {}.", level_path.as_ref().display(), pack_path.as_ref().display());
+
+    let canonical_level_path = canonical_dependency(level_path.as_ref(), "the level")?;
+    let level_directory = canonical_level_path.parent().unwrap_or(Path::new("")).to_path_buf();
+
+    let mut resolver = ReferenceResolver::new(&NativeFilesystem, paths);
+    resolver.resolve_level(canonical_level_path.as_path())?;
+
+    let mut dependencies: BTreeSet<PathBuf> = resolver.sources.keys().cloned().collect();
+    for gameobject in resolver.gameobjects.iter() {
+        add_mesh_dependencies(gameobject, level_directory.as_path(), paths, &mut dependencies)?;
+    }
+
+    let base_directory = match paths.project_root() {
+        Some(project_root) => canonical_dependency(project_root, "the project root")?,
+        None => common_directory(&dependencies),
+    };
+
+    // The archive is written in memory, the zip writer needing to seek, then saved atomically.
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    for dependency in dependencies.iter() {
+        let relative_path = dependency.strip_prefix(base_directory.as_path()).map_err(|_| {
+            DataParserError::ReferenceError(format!("The file '{}' is outside of the project root '{}', and can't be packed.", dependency.display(), base_directory.display()))
+        })?;
+        let name = entry_name(relative_path).unwrap_or_default();
+        trace!("Packing {} as {}.", dependency.display(), name);
+
+        let mut content = Vec::new();
+        File::open(dependency.as_path())?.read_to_end(&mut content)?;
+        writer.start_file(name, options).map_err(io::Error::from)?;
+        writer.write_all(content.as_slice())?;
+    }
+    let archive = writer.finish().map_err(io::Error::from)?.into_inner();
+    atomic_file::save(pack_path.as_ref(), |pack| pack.write_all(archive.as_slice()).map_err(DataParserError::from))?;
+
+    let level_name = canonical_level_path.strip_prefix(base_directory.as_path()).unwrap_or(canonical_level_path.as_path());
+    Ok(PathBuf::from(entry_name(level_name).unwrap_or_default()))
+}
@@ -0,0 +1,370 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use toml;
+use toml::value::Table;
+use data_parser_error::{DataParserError, DataParserResult};
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use serde::de::DeserializeOwned;
+use gameobject_builder::GameObjectBuilder;
+use error_location::{self, ErrorLocation};
+use diagnostic::Diagnostic;
+use unknown_key::{self, DocumentKind};
+use migration;
+use data_format::{self, DataFormat};
+use asset_path::{self, PathResolver};
+use virtual_filesystem::VirtualFilesystem;
+
+/*
+    Prefab structure, a gameobject instantiating a template file:
+    prefab = "path to the template gameobject file"
+    id = "game object name"
+
+    [transform]
+    position = [x, y, z]
+    ...
+
+    The template can itself be an instance of another prefab. The instance is merged over its template:
+    - tables are merged key by key, recursively,
+    - other values (strings, numbers, arrays, including children) replace the values of the template,
+    - the rotation of the transform replaces the rotation of the template as a whole, whatever its form.
+
+    The references and prefabs are resolved by the PathResolver, relative to the file declaring them or
+    to the project root (see asset_path.rs), and read from a VirtualFilesystem. Each file is parsed
+    in the format of its extension (see data_format.rs).
+*/
+
+// The level, as written in the file: the gameobjects can be inline tables or references.
+#[derive(Deserialize, Debug)]
+pub(crate) struct LevelData {
+    pub(crate) title: String,
+    #[serde(default)]
+    pub(crate) gameobjects: Vec<toml::Value>,
+}
+
+#[derive(Debug)]
+pub(crate) enum GameObjectEntry {
+    Inline(toml::Value),
+    Reference(String),
+}
+
+impl GameObjectEntry {
+    pub(crate) fn from_value(value: toml::Value) -> Self {
+        // A reference is either a string, or a table with only a 'path' key.
+        let reference = match value {
+            toml::Value::String(ref path) => Some(path.clone()),
+            toml::Value::Table(ref table) if table.len() == 1 => {
+                table.get("path").and_then(|path| path.as_str()).map(|path| path.to_owned())
+            },
+            _ => None,
+        };
+
+        match reference {
+            Some(path) => GameObjectEntry::Reference(path),
+            None => GameObjectEntry::Inline(value),
+        }
+    }
+}
+
+// Merges the table of an instance over the table of its template.
+fn merge_tables(mut template: Table, instance: Table) -> Table {
+    for (key, value) in instance {
+        let merged_value = match (template.remove(&key), value) {
+            (Some(toml::Value::Table(template_table)), toml::Value::Table(instance_table)) if key != "rotation" => {
+                toml::Value::Table(merge_tables(template_table, instance_table))
+            },
+            (_, value) => value,
+        };
+        template.insert(key, merged_value);
+    }
+    template
+}
+
+// Where a gameobject was found: its file, and its key path in this file.
+pub(crate) struct GameObjectOrigin {
+    pub(crate) file: PathBuf,
+    pub(crate) key_path: String,
+}
+
+// Follows the references of a level file and the prefabs of its gameobjects, keeping track of
+// the files being resolved to detect cyclic includes.
+//
+// When collecting, the errors of the gameobject entries don't stop the resolution: they are
+// stored in 'errors', and the entry is skipped.
+pub(crate) struct ReferenceResolver<'a> {
+    filesystem: &'a dyn VirtualFilesystem,
+    include_stack: Vec<PathBuf>,
+    paths: PathResolver,
+    // The directory of the file being loaded, on which the relative mesh paths are rebased.
+    root_directory: Option<PathBuf>,
+    pub(crate) gameobjects: Vec<GameObjectBuilder>,
+    pub(crate) origins: Vec<GameObjectOrigin>,
+    pub(crate) sources: HashMap<PathBuf, String>,
+    pub(crate) unknown_keys: Vec<Diagnostic>,
+    pub(crate) errors: Option<Vec<DataParserError>>,
+}
+
+impl<'a> ReferenceResolver<'a> {
+    pub(crate) fn new(filesystem: &'a dyn VirtualFilesystem, paths: &PathResolver) -> Self {
+        ReferenceResolver {
+            filesystem,
+            include_stack: Vec::new(),
+            paths: paths.clone(),
+            root_directory: None,
+            gameobjects: Vec::new(),
+            origins: Vec::new(),
+            sources: HashMap::new(),
+            unknown_keys: Vec::new(),
+            errors: None,
+        }
+    }
+
+    pub(crate) fn collecting(filesystem: &'a dyn VirtualFilesystem, paths: &PathResolver) -> Self {
+        let mut resolver = ReferenceResolver::new(filesystem, paths);
+        resolver.errors = Some(Vec::new());
+        resolver
+    }
+
+    fn enter(&mut self, path: &Path) -> DataParserResult<(PathBuf, String)> {
+        let canonical_path = self.filesystem.canonicalize(path).map_err(|io_error| {
+            DataParserError::IoError(format!("Could not find the file '{}'.", path.display()), io_error)
+        })?;
+
+        if self.include_stack.contains(&canonical_path) {
+            let mut cycle: Vec<String> = self.include_stack
+                .iter()
+                .skip_while(|included| **included != canonical_path)
+                .map(|included| included.display().to_string())
+                .collect();
+            cycle.push(canonical_path.display().to_string());
+            return Err(DataParserError::ReferenceError(format!("Cyclic include detected: {}.", cycle.join(" -> "))));
+        }
+
+        trace!("Reading content of {} in string...", canonical_path.display());
+        let content = self.filesystem.read_to_string(canonical_path.as_path()).map_err(|io_error| {
+            DataParserError::IoError(format!("Could not open the file '{}'.", canonical_path.display()), io_error)
+        })?;
+
+        if self.root_directory.is_none() {
+            self.root_directory = Some(directory(canonical_path.as_path()).to_path_buf());
+        }
+        self.include_stack.push(canonical_path.clone());
+        self.sources.insert(canonical_path.clone(), content.clone());
+        Ok((canonical_path, content))
+    }
+
+    // Resolve the content of a file, the file being on the include stack while it is resolved.
+    fn within<T, F>(&mut self, path: &Path, resolve: F) -> DataParserResult<T> where
+        F: FnOnce(&mut Self, &Path, &str) -> DataParserResult<T>
+    {
+        let (canonical_path, content) = self.enter(path)?;
+        let result = resolve(self, canonical_path.as_path(), content.as_ref());
+        self.include_stack.pop();
+        result
+    }
+
+    // Keep the error if collecting, skipping the entry which caused it.
+    fn recover(&mut self, result: DataParserResult<()>) -> DataParserResult<()> {
+        match (result, self.errors.as_mut()) {
+            (Err(error), Some(errors)) => {
+                errors.push(error);
+                Ok(())
+            },
+            (result, _) => result,
+        }
+    }
+
+    fn record_unknown_keys(&mut self, document: DocumentKind, file: &Path, source: &str, unknown_keys: Vec<String>) {
+        for key_path in unknown_keys {
+            let location = ErrorLocation::at_key_path(Some(file), key_path, source, DataFormat::of_file(file));
+            self.unknown_keys.push(unknown_key::diagnostic(document, location));
+        }
+    }
+
+    fn parse(&mut self, file: &Path, source: &str) -> DataParserResult<toml::Value> {
+        data_format::parse(source, DataFormat::of_file(file)).map_err(|error| error.with_source(Some(file), source, DataFormat::of_file(file)))
+    }
+
+    // Upgrades a document to the current format version, and rebases its relative mesh paths
+    // on the directory of the file being loaded.
+    fn migrate(&mut self, value: toml::Value, document: DocumentKind, file: &Path) -> DataParserResult<toml::Value> {
+        let (mut value, _) = migration::migrate(value, document).map_err(|error| {
+            error.with_context(format!("'{}'", file.display()))
+        })?;
+
+        let file_directory = directory(file);
+        let root_directory = match self.root_directory {
+            Some(ref root_directory) if root_directory.as_path() != file_directory => root_directory,
+            _ => return Ok(value),
+        };
+        if let toml::Value::Table(ref mut table) = value {
+            migration::for_each_gameobject(table, document, &mut |gameobject| {
+                let path = match gameobject.get_mut("mesh").and_then(|mesh| mesh.get_mut("path")) {
+                    Some(&mut toml::Value::String(ref mut path)) => path,
+                    _ => return,
+                };
+                if asset_path::is_asset_path(path.as_str()) || Path::new(path.as_str()).is_absolute() {
+                    return;
+                }
+                let mesh_path = asset_path::normalize(file_directory.join(path.as_str()).as_path());
+                if let Some(rebased_path) = asset_path::relative_path(root_directory.as_path(), mesh_path.as_path()) {
+                    trace!("Rebasing the mesh path {} on {}: {}.", path, root_directory.display(), rebased_path);
+                    *path = rebased_path;
+                }
+            });
+        }
+        Ok(value)
+    }
+
+    fn deserialize<T>(&mut self, value: toml::Value, document: DocumentKind, key_path: &str, file: &Path, source: &str) -> DataParserResult<T> where
+        T: DeserializeOwned
+    {
+        let mut unknown_keys = Vec::new();
+        let result = error_location::deserialize_value(value, key_path, &mut unknown_keys).map_err(|error| {
+            error.with_source(Some(file), source, DataFormat::of_file(file))
+        });
+        self.record_unknown_keys(document, file, source, unknown_keys);
+        result
+    }
+
+    fn deserialize_gameobject(&mut self, value: toml::Value, document: DocumentKind, key_path: &str, file: &Path, source: &str) -> DataParserResult<GameObjectBuilder> {
+        let mut unknown_keys = Vec::new();
+        let result = GameObjectBuilder::from_value(value, key_path, &mut unknown_keys).map_err(|error| {
+            error.with_source(Some(file), source, DataFormat::of_file(file))
+        });
+        self.record_unknown_keys(document, file, source, unknown_keys);
+        result
+    }
+
+    fn push(&mut self, gameobject: GameObjectBuilder, file: &Path, key_path: String) {
+        self.gameobjects.push(gameobject);
+        self.origins.push(GameObjectOrigin {
+            file: file.to_path_buf(),
+            key_path,
+        });
+    }
+
+    pub(crate) fn resolve_level(&mut self, path: &Path) -> DataParserResult<String> {
+        self.within(path, |resolver, file, source| {
+            let value = resolver.parse(file, source)?;
+            let value = resolver.migrate(value, DocumentKind::Level, file)?;
+            let level_data: LevelData = resolver.deserialize(value, DocumentKind::Level, "", file, source)?;
+            resolver.resolve_entries(file, source, level_data.gameobjects)?;
+            Ok(level_data.title)
+        })
+    }
+
+    pub(crate) fn resolve_gameobject(&mut self, path: &Path) -> DataParserResult<GameObjectBuilder> {
+        self.within(path, |resolver, file, source| {
+            let value = resolver.parse(file, source)?;
+            let value = resolver.migrate(value, DocumentKind::GameObject, file)?;
+            let value = resolver.resolve_prefab(value, directory(file))?;
+            resolver.deserialize_gameobject(value, DocumentKind::GameObject, "", file, source)
+        })
+    }
+
+    fn resolve_reference(&mut self, path: &Path) -> DataParserResult<()> {
+        self.within(path, |resolver, file, source| {
+            let value = resolver.parse(file, source)?;
+            let document = migration::document_kind(&value);
+            let value = resolver.migrate(value, document, file)?;
+
+            match value.get("gameobjects").cloned() {
+                Some(entries) => {
+                    debug!("Including the gameobjects of the level file {}.", file.display());
+                    let entries = resolver.deserialize(entries, DocumentKind::Level, "gameobjects", file, source)?;
+                    resolver.resolve_entries(file, source, entries)
+                },
+                None => {
+                    debug!("Loading the gameobject file {}.", file.display());
+                    let value = resolver.resolve_prefab(value, directory(file))?;
+                    let gameobject = resolver.deserialize_gameobject(value, DocumentKind::GameObject, "", file, source)?;
+                    resolver.push(gameobject, file, String::new());
+                    Ok(())
+                },
+            }
+        })
+    }
+
+    fn resolve_entries(&mut self, level_path: &Path, source: &str, entries: Vec<toml::Value>) -> DataParserResult<()> {
+        let level_directory = directory(level_path);
+
+        for (index, entry) in entries.into_iter().enumerate() {
+            let result = match GameObjectEntry::from_value(entry) {
+                GameObjectEntry::Inline(value) => {
+                    let key_path = format!("gameobjects[{}]", index);
+                    self.resolve_prefab(value, level_directory)
+                        .and_then(|value| self.deserialize_gameobject(value, DocumentKind::Level, key_path.as_str(), level_path, source))
+                        .map(|gameobject| self.push(gameobject, level_path, key_path))
+                        .map_err(|error| {
+                            error.with_context(format!("Level entry gameobjects[{}] of '{}'", index, level_path.display()))
+                        })
+                },
+                GameObjectEntry::Reference(reference) => {
+                    self.paths.resolve(reference.as_str(), level_directory)
+                        .and_then(|reference_path| self.resolve_reference(reference_path.as_path()))
+                        .map_err(|error| {
+                            error.with_context(format!("Level entry gameobjects[{}] ('{}') of '{}'", index, reference, level_path.display()))
+                        })
+                },
+            };
+            self.recover(result)?;
+        }
+
+        Ok(())
+    }
+
+    // Replaces the prefab of a gameobject, and of its children, by the merge of the template
+    // and the instance. The paths are relative to the given directory.
+    fn resolve_prefab(&mut self, value: toml::Value, directory_path: &Path) -> DataParserResult<toml::Value> {
+        let mut instance = match value {
+            toml::Value::Table(table) => table,
+            other => return Ok(other),
+        };
+
+        if let Some(toml::Value::Array(children)) = instance.remove("children") {
+            let mut resolved_children = Vec::with_capacity(children.len());
+            for child in children {
+                resolved_children.push(self.resolve_prefab(child, directory_path)?);
+            }
+            instance.insert(String::from("children"), toml::Value::Array(resolved_children));
+        }
+
+        let prefab = match instance.remove("prefab") {
+            Some(toml::Value::String(prefab)) => prefab,
+            Some(other) => {
+                return Err(DataParserError::ReferenceError(format!("The prefab must be a path, found {}.", other)));
+            },
+            None => return Ok(toml::Value::Table(instance)),
+        };
+
+        debug!("Instantiating the prefab {}.", prefab);
+        let template = self.paths.resolve(prefab.as_str(), directory_path).and_then(|template_path| {
+            self.resolve_template(template_path.as_path())
+        }).map_err(|error| {
+            error.with_context(format!("Prefab '{}'", prefab))
+        })?;
+
+        Ok(toml::Value::Table(merge_tables(template, instance)))
+    }
+
+    fn resolve_template(&mut self, path: &Path) -> DataParserResult<Table> {
+        self.within(path, |resolver, file, source| {
+            let value = resolver.parse(file, source)?;
+            let value = resolver.migrate(value, DocumentKind::GameObject, file)?;
+            match resolver.resolve_prefab(value, directory(file))? {
+                toml::Value::Table(table) => Ok(table),
+                _ => unreachable!("A parsed document is always a table"),
+            }
+        })
+    }
+}
+
+fn directory(path: &Path) -> &Path {
+    path.parent().unwrap_or(Path::new(""))
+}
@@ -0,0 +1,351 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use serde::{Serialize, Serializer};
+use serde::ser::SerializeStruct;
+use transform_description::vector3;
+
+/*
+    Rotation structure, in the transform of a gameobject file:
+
+    Euler angles, in radians, applied around the X, then Y, then Z axis:
+    rotation = [x, y, z]
+
+    Euler angles, with an explicit unit ("radians" or "degrees") and order (XYZ, XZY, YXZ, YZX, ZXY, ZYX).
+    The order lists the axes in the order the rotations are applied, around the fixed axes of the parent:
+    [transform.rotation]
+    euler = [x, y, z]
+    unit = "degrees"
+    order = "ZYX"
+
+    Quaternion:
+    [transform.rotation]
+    quaternion = [x, y, z, w]
+
+    Axis-angle, the unit being "radians" by default:
+    [transform.rotation]
+    axis = [x, y, z]
+    angle = 90.0
+    unit = "degrees"
+*/
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd)]
+#[serde(rename_all = "lowercase")]
+pub enum AngleUnit {
+    Radians,
+    Degrees,
+}
+
+impl Default for AngleUnit {
+    fn default() -> Self {
+        AngleUnit::Radians
+    }
+}
+
+impl AngleUnit {
+    fn to_radians(self, angle: f64) -> f64 {
+        match self {
+            AngleUnit::Radians => angle,
+            AngleUnit::Degrees => angle.to_radians(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd)]
+pub enum EulerOrder {
+    XYZ,
+    XZY,
+    YXZ,
+    YZX,
+    ZXY,
+    ZYX,
+}
+
+impl Default for EulerOrder {
+    fn default() -> Self {
+        EulerOrder::XYZ
+    }
+}
+
+impl EulerOrder {
+    // The indices of the axes, in the order the rotations are applied.
+    fn axes(&self) -> [usize; 3] {
+        match self {
+            &EulerOrder::XYZ => [0, 1, 2],
+            &EulerOrder::XZY => [0, 2, 1],
+            &EulerOrder::YXZ => [1, 0, 2],
+            &EulerOrder::YZX => [1, 2, 0],
+            &EulerOrder::ZXY => [2, 0, 1],
+            &EulerOrder::ZYX => [2, 1, 0],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum RotationDescription {
+    Quaternion([f64; 4]),
+    Euler {
+        angles: [f64; 3],
+        unit: AngleUnit,
+        order: EulerOrder,
+    },
+    AxisAngle {
+        axis: [f64; 3],
+        angle: f64,
+        unit: AngleUnit,
+    },
+}
+
+impl Default for RotationDescription {
+    fn default() -> Self {
+        RotationDescription::from([0.0, 0.0, 0.0])
+    }
+}
+
+// Euler angles, in radians, applied around the X, then Y, then Z axis.
+impl From<[f64; 3]> for RotationDescription {
+    fn from(angles: [f64; 3]) -> Self {
+        RotationDescription::Euler {
+            angles,
+            unit: AngleUnit::Radians,
+            order: EulerOrder::XYZ,
+        }
+    }
+}
+
+// Quaternion [x, y, z, w].
+impl From<[f64; 4]> for RotationDescription {
+    fn from(quaternion: [f64; 4]) -> Self {
+        RotationDescription::Quaternion(quaternion)
+    }
+}
+
+// Hamilton product of two quaternions [x, y, z, w].
+pub(crate) fn multiply(a: [f64; 4], b: [f64; 4]) -> [f64; 4] {
+    [
+        a[3] * b[0] + a[0] * b[3] + a[1] * b[2] - a[2] * b[1],
+        a[3] * b[1] - a[0] * b[2] + a[1] * b[3] + a[2] * b[0],
+        a[3] * b[2] + a[0] * b[1] - a[1] * b[0] + a[2] * b[3],
+        a[3] * b[3] - a[0] * b[0] - a[1] * b[1] - a[2] * b[2],
+    ]
+}
+
+fn norm(values: &[f64]) -> f64 {
+    values.iter().map(|value| value * value).sum::<f64>().sqrt()
+}
+
+impl RotationDescription {
+    // Check that the rotation can be turned into a quaternion.
+    pub(crate) fn check(&self) -> Result<(), String> {
+        match self {
+            &RotationDescription::Quaternion(ref quaternion) => {
+                if quaternion.iter().any(|value| !value.is_finite()) {
+                    return Err(format!("The transform.rotation quaternion must only contain finite values, found {:?}.", quaternion));
+                }
+                if norm(quaternion) < f64::EPSILON {
+                    return Err(format!("The transform.rotation quaternion must not be null."));
+                }
+            },
+            &RotationDescription::Euler { ref angles, .. } => {
+                if angles.iter().any(|value| !value.is_finite()) {
+                    return Err(format!("The transform.rotation Euler angles must only contain finite values, found {:?}.", angles));
+                }
+            },
+            &RotationDescription::AxisAngle { ref axis, angle, .. } => {
+                if axis.iter().any(|value| !value.is_finite()) || !angle.is_finite() {
+                    return Err(format!("The transform.rotation axis and angle must only contain finite values, found {:?} and {}.", axis, angle));
+                }
+                if norm(axis) < f64::EPSILON {
+                    return Err(format!("The transform.rotation axis must not be null."));
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    /// The canonical representation of the rotation: a unit quaternion [x, y, z, w].
+    pub fn quaternion(&self) -> [f64; 4] {
+        let quaternion = match self {
+            &RotationDescription::Quaternion(quaternion) => {
+                quaternion
+            },
+            &RotationDescription::Euler { angles, unit, order } => {
+                order.axes().iter().fold([0.0, 0.0, 0.0, 1.0], |quaternion, &axis| {
+                    let (sin, cos) = (unit.to_radians(angles[axis]) * 0.5).sin_cos();
+                    let mut axis_rotation = [0.0, 0.0, 0.0, cos];
+                    axis_rotation[axis] = sin;
+                    multiply(axis_rotation, quaternion)
+                })
+            },
+            &RotationDescription::AxisAngle { axis, angle, unit } => {
+                let axis_norm = norm(&axis);
+                let (sin, cos) = (unit.to_radians(angle) * 0.5).sin_cos();
+                [axis[0] / axis_norm * sin, axis[1] / axis_norm * sin, axis[2] / axis_norm * sin, cos]
+            },
+        };
+
+        let quaternion_norm = norm(&quaternion);
+        [
+            quaternion[0] / quaternion_norm,
+            quaternion[1] / quaternion_norm,
+            quaternion[2] / quaternion_norm,
+            quaternion[3] / quaternion_norm,
+        ]
+    }
+
+    // The short form, a bare array of Euler angles, can't be written as a table.
+    pub(crate) fn is_table(&self) -> bool {
+        !matches!(self, &RotationDescription::Euler { unit: AngleUnit::Radians, order: EulerOrder::XYZ, .. })
+    }
+}
+
+impl Serialize for RotationDescription {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer
+    {
+        match self {
+            &RotationDescription::Euler { ref angles, unit: AngleUnit::Radians, order: EulerOrder::XYZ } => {
+                angles.serialize(serializer)
+            },
+            &RotationDescription::Euler { ref angles, ref unit, ref order } => {
+                let mut state = serializer.serialize_struct("RotationDescription", 3)?;
+                state.serialize_field("euler", angles)?;
+                state.serialize_field("unit", unit)?;
+                state.serialize_field("order", order)?;
+                state.end()
+            },
+            &RotationDescription::Quaternion(ref quaternion) => {
+                let mut state = serializer.serialize_struct("RotationDescription", 1)?;
+                state.serialize_field("quaternion", quaternion)?;
+                state.end()
+            },
+            &RotationDescription::AxisAngle { ref axis, ref angle, ref unit } => {
+                let mut state = serializer.serialize_struct("RotationDescription", 3)?;
+                state.serialize_field("axis", axis)?;
+                state.serialize_field("angle", angle)?;
+                state.serialize_field("unit", unit)?;
+                state.end()
+            },
+        }
+    }
+}
+
+// The rotation, as written in the file, before its shape is validated.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub(crate) enum RotationData {
+    Array(Vec<f64>),
+    Table(RotationTable),
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct RotationTable {
+    quaternion: Option<Vec<f64>>,
+    euler: Option<Vec<f64>>,
+    axis: Option<Vec<f64>>,
+    angle: Option<f64>,
+    unit: Option<String>,
+    order: Option<String>,
+}
+
+fn parse_unit(unit: Option<String>) -> Result<AngleUnit, String> {
+    match unit.as_deref() {
+        None | Some("radians") => Ok(AngleUnit::Radians),
+        Some("degrees") => Ok(AngleUnit::Degrees),
+        Some(other) => Err(format!("The transform.rotation.unit must be \"radians\" or \"degrees\", found \"{}\".", other)),
+    }
+}
+
+fn parse_order(order: Option<String>) -> Result<EulerOrder, String> {
+    match order.as_deref() {
+        None | Some("XYZ") => Ok(EulerOrder::XYZ),
+        Some("XZY") => Ok(EulerOrder::XZY),
+        Some("YXZ") => Ok(EulerOrder::YXZ),
+        Some("YZX") => Ok(EulerOrder::YZX),
+        Some("ZXY") => Ok(EulerOrder::ZXY),
+        Some("ZYX") => Ok(EulerOrder::ZYX),
+        Some(other) => Err(format!("The transform.rotation.order must be one of XYZ, XZY, YXZ, YZX, ZXY or ZYX, found \"{}\".", other)),
+    }
+}
+
+impl RotationData {
+    pub(crate) fn validate(self) -> Result<RotationDescription, String> {
+        let table = match self {
+            RotationData::Array(angles) => {
+                return Ok(RotationDescription::from(vector3("transform.rotation", &angles)?));
+            },
+            RotationData::Table(table) => table,
+        };
+
+        match (table.quaternion, table.euler, table.axis) {
+            (Some(quaternion), None, None) => {
+                if table.angle.is_some() || table.unit.is_some() || table.order.is_some() {
+                    return Err(format!("The transform.rotation.quaternion can't be used with an angle, a unit or an order."));
+                }
+                if quaternion.len() != 4 {
+                    return Err(format!("The transform.rotation.quaternion must have 4 components, found {}.", quaternion.len()));
+                }
+                Ok(RotationDescription::Quaternion([quaternion[0], quaternion[1], quaternion[2], quaternion[3]]))
+            },
+            (None, Some(euler), None) => {
+                if table.angle.is_some() {
+                    return Err(format!("The transform.rotation.euler can't be used with an angle."));
+                }
+                Ok(RotationDescription::Euler {
+                    angles: vector3("transform.rotation.euler", &euler)?,
+                    unit: parse_unit(table.unit)?,
+                    order: parse_order(table.order)?,
+                })
+            },
+            (None, None, Some(axis)) => {
+                if table.order.is_some() {
+                    return Err(format!("The transform.rotation.axis can't be used with an order."));
+                }
+                let angle = table.angle.ok_or_else(|| format!("The transform.rotation.axis must come with an angle."))?;
+                Ok(RotationDescription::AxisAngle {
+                    axis: vector3("transform.rotation.axis", &axis)?,
+                    angle,
+                    unit: parse_unit(table.unit)?,
+                })
+            },
+            _ => {
+                Err(format!("The transform.rotation must have exactly one of the 'quaternion', 'euler' or 'axis' keys."))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod rotation_description_test {
+    use super::*;
+
+    fn assert_quaternion_eq(a: [f64; 4], b: [f64; 4]) {
+        // q and -q are the same rotation.
+        let sign = if a.iter().zip(b.iter()).map(|(a, b)| a * b).sum::<f64>() < 0.0 { -1.0 } else { 1.0 };
+        for (a, b) in a.iter().zip(b.iter()) {
+            assert!((a - sign * b).abs() < 1e-9, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn canonical_quaternion() {
+        let half_sqrt2 = 0.5_f64.sqrt();
+        let around_y = [0.0, half_sqrt2, 0.0, half_sqrt2];
+
+        assert_quaternion_eq(RotationDescription::default().quaternion(), [0.0, 0.0, 0.0, 1.0]);
+        assert_quaternion_eq(RotationDescription::Quaternion([0.0, 2.0, 0.0, 2.0]).quaternion(), around_y);
+        assert_quaternion_eq(RotationDescription::AxisAngle { axis: [0.0, 3.0, 0.0], angle: 90.0, unit: AngleUnit::Degrees }.quaternion(), around_y);
+        assert_quaternion_eq(RotationDescription::Euler { angles: [0.0, ::std::f64::consts::FRAC_PI_2, 0.0], unit: AngleUnit::Radians, order: EulerOrder::ZYX }.quaternion(), around_y);
+
+        // 90° around X then 90° around Y is not 90° around Y then 90° around X.
+        let xyz = RotationDescription::Euler { angles: [90.0, 90.0, 0.0], unit: AngleUnit::Degrees, order: EulerOrder::XYZ }.quaternion();
+        let yxz = RotationDescription::Euler { angles: [90.0, 90.0, 0.0], unit: AngleUnit::Degrees, order: EulerOrder::YXZ }.quaternion();
+        assert_quaternion_eq(xyz, [0.5, 0.5, -0.5, 0.5]);
+        assert_quaternion_eq(yxz, [0.5, 0.5, 0.5, 0.5]);
+    }
+}
@@ -6,41 +6,139 @@
 // copied, modified, or distributed except according to those terms.
 
 use maskerad_gameobject_model::properties::transform::Transform;
+use data_parser_error::{DataParserError, DataParserResult};
+use rotation_description::{RotationDescription, RotationData};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::ser::SerializeStruct;
+use serde::de;
 
-#[derive(Debug, Deserialize, Serialize, PartialOrd, PartialEq, Default, Clone)]
+#[derive(Debug, PartialOrd, PartialEq, Clone)]
 pub struct TransformDescription {
+    position: [f64; 3],
+    rotation: RotationDescription,
+    scale: [f64; 3],
+}
+
+impl Default for TransformDescription {
+    fn default() -> Self {
+        TransformDescription {
+            position: [0.0, 0.0, 0.0],
+            rotation: RotationDescription::default(),
+            scale: [1.0, 1.0, 1.0],
+        }
+    }
+}
+
+// The transform, as written in the file, before its shape and values are validated.
+#[derive(Deserialize, Debug)]
+pub(crate) struct TransformData {
     position: Vec<f64>,
-    rotation: Vec<f64>,
+    rotation: RotationData,
     scale: Vec<f64>,
 }
 
+impl TransformData {
+    pub(crate) fn validate(self) -> Result<TransformDescription, String> {
+        let transform = TransformDescription {
+            position: vector3("transform.position", &self.position)?,
+            rotation: self.rotation.validate()?,
+            scale: vector3("transform.scale", &self.scale)?,
+        };
+        transform.check()?;
+        Ok(transform)
+    }
+}
+
+// A rotation written as a table must come after the other values of the transform.
+impl Serialize for TransformDescription {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where
+        S: Serializer
+    {
+        let mut state = serializer.serialize_struct("TransformDescription", 3)?;
+        state.serialize_field("position", &self.position)?;
+        if self.rotation.is_table() {
+            state.serialize_field("scale", &self.scale)?;
+            state.serialize_field("rotation", &self.rotation)?;
+        } else {
+            state.serialize_field("rotation", &self.rotation)?;
+            state.serialize_field("scale", &self.scale)?;
+        }
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for TransformDescription {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where
+        D: Deserializer<'de>
+    {
+        TransformData::deserialize(deserializer)?.validate().map_err(de::Error::custom)
+    }
+}
+
+pub(crate) fn vector3(field: &str, values: &[f64]) -> Result<[f64; 3], String> {
+    if values.len() != 3 {
+        return Err(format!("The {} must have 3 components, found {}.", field, values.len()));
+    }
+
+    Ok([values[0], values[1], values[2]])
+}
+
+fn check_finite(field: &str, values: &[f64; 3]) -> Result<(), String> {
+    if values.iter().any(|value| !value.is_finite()) {
+        return Err(format!("The {} must only contain finite values, found {:?}.", field, values));
+    }
+
+    Ok(())
+}
 
 impl TransformDescription {
-    pub fn new<I, J, K>(position: I, rotation: J, scale: K) -> Self where
-        I: Into<Vec<f64>>,
-        J: Into<Vec<f64>>,
-        K: Into<Vec<f64>>,
+    pub fn new<R>(position: [f64; 3], rotation: R, scale: [f64; 3]) -> Self where
+        R: Into<RotationDescription>,
     {
         debug!("Creating a new TransformDescription.");
         TransformDescription {
-            position: position.into(),
+            position,
             rotation: rotation.into(),
-            scale: scale.into(),
+            scale,
         }
     }
 
-    pub fn position(&self) -> &[f64] {
-        debug!("Getting an immutable slice to the position.");
-        &self.position
+    // Check that the values can be used by the engine: finite values, and no zero scale.
+    pub(crate) fn check(&self) -> Result<(), String> {
+        check_finite("transform.position", &self.position)?;
+        self.rotation.check()?;
+        check_finite("transform.scale", &self.scale)?;
+
+        if self.scale.contains(&0.0) {
+            return Err(format!("The transform.scale must not contain a zero component, found {:?}.", self.scale));
+        }
+
+        Ok(())
+    }
+
+    pub fn build(&self) -> DataParserResult<Transform> {
+        debug!("Creating a Transform from the TransformDescription.");
+        self.check().map_err(DataParserError::BuildError)?;
+        Ok(Transform::new(self.position, self.rotation.quaternion(), self.scale))
     }
 
-    pub fn rotation(&self) -> &[f64] {
-        debug!("Getting an immutable slice to the rotation.");
+    pub fn position(&self) -> [f64; 3] {
+        debug!("Getting the position.");
+        self.position
+    }
+
+    pub fn rotation(&self) -> &RotationDescription {
+        debug!("Getting an immutable reference to the rotation.");
         &self.rotation
     }
 
-    pub fn scale(&self) -> &[f64] {
-        debug!("Getting an immutable slice to the scale.");
-        &self.scale
+    pub fn quaternion(&self) -> [f64; 4] {
+        debug!("Getting the rotation as a unit quaternion.");
+        self.rotation.quaternion()
+    }
+
+    pub fn scale(&self) -> [f64; 3] {
+        debug!("Getting the scale.");
+        self.scale
     }
 }
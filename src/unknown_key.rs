@@ -0,0 +1,287 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use strsim;
+use toml;
+use serde_json;
+use data_parser_error::{DataParserError, DataParserResult};
+use error_location::ErrorLocation;
+use diagnostic::{Diagnostic, DiagnosticCode, Severity};
+
+/// What to do with the keys of a data file which don't match any field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownKeyMode {
+    /// The unknown keys are ignored, and reported as warnings.
+    Lenient,
+    /// The loading fails if the file has any unknown key, reporting all of them.
+    Strict,
+}
+
+impl Default for UnknownKeyMode {
+    fn default() -> Self {
+        UnknownKeyMode::Lenient
+    }
+}
+
+// The kind of document at the root of a data file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DocumentKind {
+    Level,
+    GameObject,
+}
+
+const LEVEL_KEYS: &[&str] = &["title", "gameobjects"];
+const GAMEOBJECT_KEYS: &[&str] = &["id", "parent", "tags", "transform", "mesh", "light", "collider", "scripts", "children", "prefab"];
+const TRANSFORM_KEYS: &[&str] = &["position", "rotation", "scale"];
+const ROTATION_KEYS: &[&str] = &["quaternion", "euler", "axis", "angle", "unit", "order"];
+const MESH_KEYS: &[&str] = &["path", "mesh", "primitive", "node", "scene"];
+const LIGHT_KEYS: &[&str] = &["kind", "color", "intensity", "range"];
+const COLLIDER_KEYS: &[&str] = &["shape", "half_extents", "radius", "height"];
+const SCRIPT_KEYS: &[&str] = &["name", "parameters"];
+
+// The rest of the key path after an element of the given array, e.g. "transform" after
+// "children[0]" in "children[0].transform".
+fn after_element<'a>(key_path: &'a str, array: &str) -> Option<&'a str> {
+    if !key_path.starts_with(array) || !key_path[array.len()..].starts_with('[') {
+        return None;
+    }
+    let rest = &key_path[array.len()..];
+    let close = rest.find(']')?;
+    if close == 1 || !rest[1..close].chars().all(|character| character.is_ascii_digit()) {
+        return None;
+    }
+    let rest = &rest[close + 1..];
+    match rest.chars().next() {
+        None => Some(rest),
+        Some('.') => Some(&rest[1..]),
+        Some(_) => None,
+    }
+}
+
+// The key path of a table relative to the gameobject holding it, if it is in a gameobject.
+fn gameobject_key_path(document: DocumentKind, table_key_path: &str) -> Option<&str> {
+    let mut key_path = match document {
+        DocumentKind::Level => after_element(table_key_path, "gameobjects")?,
+        DocumentKind::GameObject => table_key_path,
+    };
+    while let Some(rest) = after_element(key_path, "children") {
+        key_path = rest;
+    }
+    Some(key_path)
+}
+
+// The keys expected in the table at the given key path.
+fn known_keys(document: DocumentKind, table_key_path: &str) -> &'static [&'static str] {
+    if document == DocumentKind::Level && table_key_path.is_empty() {
+        return LEVEL_KEYS;
+    }
+
+    match gameobject_key_path(document, table_key_path) {
+        Some("") => GAMEOBJECT_KEYS,
+        Some("transform") => TRANSFORM_KEYS,
+        Some("transform.rotation") => ROTATION_KEYS,
+        Some("mesh") => MESH_KEYS,
+        Some("light") => LIGHT_KEYS,
+        Some("collider") => COLLIDER_KEYS,
+        Some(key_path) if after_element(key_path, "scripts") == Some("") => SCRIPT_KEYS,
+        _ => &[],
+    }
+}
+
+// The tables of a gameobject deserialized as untagged or internally tagged enums: serde buffers
+// them first, so serde_ignored doesn't see their unknown keys.
+const BUFFERED_TABLES: &[(&str, &[&str])] = &[("transform.rotation", ROTATION_KEYS), ("collider", COLLIDER_KEYS)];
+
+// Pushes the key paths of the unknown keys of the buffered tables of a gameobject, found at the
+// given key path, and of its children.
+pub(crate) fn buffered_unknown_keys(gameobject: &toml::Value, key_path: &str, unknown_keys: &mut Vec<String>) {
+    let join = |relative_key_path: &str| {
+        if key_path.is_empty() {
+            relative_key_path.to_owned()
+        } else {
+            format!("{}.{}", key_path, relative_key_path)
+        }
+    };
+
+    for &(table_key_path, keys) in BUFFERED_TABLES.iter() {
+        let table = table_key_path.split('.').try_fold(gameobject, |value, key| value.get(key));
+        if let Some(&toml::Value::Table(ref table)) = table {
+            for key in table.keys().filter(|key| !keys.contains(&key.as_str())) {
+                unknown_keys.push(join(format!("{}.{}", table_key_path, key).as_str()));
+            }
+        }
+    }
+
+    if let Some(&toml::Value::Array(ref children)) = gameobject.get("children") {
+        for (index, child) in children.iter().enumerate() {
+            buffered_unknown_keys(child, join(format!("children[{}]", index).as_str()).as_str(), unknown_keys);
+        }
+    }
+}
+
+// The known key closest to the given key, if it is close enough to be a typo.
+fn suggest(key: &str, candidates: &[&'static str]) -> Option<&'static str> {
+    let max_distance = ::std::cmp::max(1, key.chars().count() / 3);
+    candidates
+        .iter()
+        .map(|candidate| (strsim::damerau_levenshtein(key, candidate), *candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+// The description of the first key of an object which isn't a key of the gameobject table at the
+// given key path, like the light and collider objects of the glTF extras.
+pub(crate) fn unknown_object_key(object: &serde_json::Value, table_key_path: &str) -> Option<String> {
+    let keys = known_keys(DocumentKind::GameObject, table_key_path);
+    let key = object.as_object()?.keys().find(|key| !keys.contains(&key.as_str()))?;
+    match suggest(key.as_str(), keys) {
+        Some(suggestion) => Some(format!("unknown key '{}', did you mean '{}'?", key, suggestion)),
+        None => Some(format!("unknown key '{}'.", key)),
+    }
+}
+
+// The warning reporting an unknown key, suggesting the known key it is likely a typo of.
+pub(crate) fn diagnostic(document: DocumentKind, location: ErrorLocation) -> Diagnostic {
+    let message = {
+        let key_path = location.key_path().unwrap_or("");
+        let (table_key_path, key) = match key_path.rfind('.') {
+            Some(index) => (&key_path[..index], &key_path[index + 1..]),
+            None => ("", key_path),
+        };
+
+        match suggest(key, known_keys(document, table_key_path)) {
+            Some(suggestion) => format!("Unknown key '{}', did you mean '{}'?", key_path, suggestion),
+            None => format!("Unknown key '{}'.", key_path),
+        }
+    };
+
+    Diagnostic::new(Severity::Warning, DiagnosticCode::UnknownKey, message, location)
+}
+
+// Fails if some keys are unknown in strict mode, logs them as warnings otherwise. In strict mode,
+// the unknown keys are reported instead of the deserialization error they likely caused.
+pub(crate) fn apply_mode<T>(mode: UnknownKeyMode, result: DataParserResult<T>, mut unknown_keys: Vec<Diagnostic>) -> DataParserResult<(T, Vec<Diagnostic>)> {
+    // The keys are reported in the order of the files, not in the order of the deserialization.
+    unknown_keys.sort_by_key(|diagnostic| {
+        let location = diagnostic.location();
+        (location.file().map(|file| file.to_path_buf()), location.line(), location.column())
+    });
+
+    if mode == UnknownKeyMode::Strict && !unknown_keys.is_empty() {
+        let description = unknown_keys
+            .iter()
+            .map(|diagnostic| diagnostic.message())
+            .collect::<Vec<&str>>()
+            .join(" ");
+        let location = unknown_keys[0].location().clone();
+        return Err(DataParserError::UnknownKeyError(description, Box::new(location)));
+    }
+
+    for diagnostic in unknown_keys.iter() {
+        warn!("{}", diagnostic);
+    }
+    result.map(|value| (value, unknown_keys))
+}
+
+#[cfg(test)]
+mod unknown_key_test {
+    use super::*;
+    use level_description::LevelDescription;
+    use gameobject_builder::GameObjectBuilder;
+    use transform_description::TransformDescription;
+    use rotation_description::{RotationDescription, AngleUnit, EulerOrder};
+    use mesh_description::{MeshDescription, MeshSelection, GltfReference};
+    use component_description::{LightDescription, LightKind, ColliderDescription, ScriptDescription};
+
+    // Pushes the key paths of the keys of a written document which aren't in the tables of known keys.
+    fn unknown_written_keys(document: DocumentKind, value: &toml::Value, key_path: &str, unknown_keys: &mut Vec<String>) {
+        match value {
+            &toml::Value::Table(ref table) => {
+                let keys = known_keys(document, key_path);
+                for (key, child) in table.iter() {
+                    let child_key_path = if key_path.is_empty() { key.clone() } else { format!("{}.{}", key_path, key) };
+                    // The only tables without known keys are the parameters of the scripts.
+                    if !keys.is_empty() && !keys.contains(&key.as_str()) {
+                        unknown_keys.push(child_key_path.clone());
+                    }
+                    unknown_written_keys(document, child, child_key_path.as_str(), unknown_keys);
+                }
+            },
+            &toml::Value::Array(ref array) => {
+                for (index, element) in array.iter().enumerate() {
+                    unknown_written_keys(document, element, format!("{}[{}]", key_path, index).as_str(), unknown_keys);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    #[test]
+    fn known_keys_match_the_written_keys() {
+        let mut light = LightDescription::new(LightKind::Spot);
+        light.add_color([1.0, 0.5, 0.0]).add_intensity(2.0).add_range(8.0);
+        let mut script = ScriptDescription::new("mover");
+        script.add_parameter("speed", 2.0);
+        let mut mesh = MeshDescription::new("kit.gltf");
+        mesh.add_selection(MeshSelection::Mesh { mesh: GltfReference::Name(String::from("rock")), primitive: Some(1) });
+
+        let mut rock = GameObjectBuilder::new("rock");
+        rock.add_parent("root")
+            .add_tag("static")
+            .add_transform(TransformDescription::new([0.0, 0.0, 0.0], RotationDescription::Quaternion([0.0, 0.0, 0.0, 1.0]), [1.0, 1.0, 1.0]))
+            .add_mesh(mesh)
+            .add_light(light)
+            .add_collider(ColliderDescription::Box { half_extents: [1.0, 1.0, 1.0] })
+            .add_script(script);
+
+        let mut tree_mesh = MeshDescription::new("kit.gltf");
+        tree_mesh.add_selection(MeshSelection::Scene(GltfReference::Index(0)));
+        let mut branch = GameObjectBuilder::new("branch");
+        branch.add_transform(TransformDescription::new([0.0, 1.0, 0.0], RotationDescription::AxisAngle { axis: [0.0, 1.0, 0.0], angle: 90.0, unit: AngleUnit::Degrees }, [1.0, 1.0, 1.0]))
+            .add_mesh(tree_mesh)
+            .add_collider(ColliderDescription::Capsule { radius: 0.5, height: 2.0 });
+
+        let mut trunk_mesh = MeshDescription::new("kit.gltf");
+        trunk_mesh.add_selection(MeshSelection::Node(GltfReference::Name(String::from("trunk"))));
+        let mut tree = GameObjectBuilder::new("tree");
+        tree.add_transform(TransformDescription::new([2.0, 0.0, 0.0], RotationDescription::Euler { angles: [0.0, 90.0, 0.0], unit: AngleUnit::Degrees, order: EulerOrder::ZYX }, [1.0, 1.0, 1.0]))
+            .add_mesh(trunk_mesh)
+            .add_collider(ColliderDescription::Sphere { radius: 1.0 })
+            .add_child(branch.clone());
+
+        let mut level = LevelDescription::new("every key");
+        level.add_gameobject(rock.clone()).add_gameobject(tree.clone());
+
+        let mut unknown_keys = Vec::new();
+        unknown_written_keys(DocumentKind::Level, &toml::Value::try_from(&level).unwrap(), "", &mut unknown_keys);
+        unknown_written_keys(DocumentKind::GameObject, &toml::Value::try_from(&tree).unwrap(), "", &mut unknown_keys);
+        assert!(unknown_keys.is_empty(), "{:?}", unknown_keys);
+    }
+
+    #[test]
+    fn suggest_known_keys() {
+        assert_eq!(suggest("postion", TRANSFORM_KEYS), Some("position"));
+        assert_eq!(suggest("trasnform", GAMEOBJECT_KEYS), Some("transform"));
+        assert_eq!(suggest("colour", GAMEOBJECT_KEYS), None);
+        assert_eq!(known_keys(DocumentKind::Level, "gameobjects[2].children[0]"), GAMEOBJECT_KEYS);
+        assert_eq!(known_keys(DocumentKind::GameObject, "transform.rotation"), ROTATION_KEYS);
+        assert_eq!(known_keys(DocumentKind::Level, "gameobjects[0].scripts[1]"), SCRIPT_KEYS);
+        assert_eq!(known_keys(DocumentKind::GameObject, "children[1].children[0].collider"), COLLIDER_KEYS);
+        // The tables of the script parameters are free.
+        assert!(known_keys(DocumentKind::GameObject, "scripts[0].parameters.light").is_empty());
+        assert!(known_keys(DocumentKind::Level, "transform").is_empty());
+    }
+
+    #[test]
+    fn find_buffered_unknown_keys() {
+        let gameobject: toml::Value = toml::from_str("id = \"a\"\n[transform.rotation]\neuler = [0.0, 90.0, 0.0]\nunti = \"degrees\"\n[[children]]\nid = \"b\"\ntransform = { rotation = { quaternion = [0.0, 0.0, 0.0, 1.0], ordre = \"XYZ\" } }\ncollider = { shape = \"sphere\", radus = 2.0 }\n[[scripts]]\nname = \"s\"\nparameters = { collider = { radus = 1.0 } }\n").unwrap();
+        let mut unknown_keys = Vec::new();
+        buffered_unknown_keys(&gameobject, "gameobjects[0]", &mut unknown_keys);
+        assert_eq!(unknown_keys, vec!["gameobjects[0].transform.rotation.unti", "gameobjects[0].children[0].transform.rotation.ordre", "gameobjects[0].children[0].collider.radus"]);
+    }
+}
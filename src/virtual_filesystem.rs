@@ -0,0 +1,153 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use std::io::{self, Read, Cursor};
+use std::fs::{self, File};
+#[cfg(feature = "maskerad_filesystem")]
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+#[cfg(feature = "maskerad_filesystem")]
+use maskerad_filesystem::filesystem::Filesystem;
+#[cfg(feature = "maskerad_filesystem")]
+use maskerad_filesystem::game_directories::RootDir;
+use asset_path;
+
+/*
+    The loaders following references (LevelDescription::load_from_filesystem,
+    GameObjectBuilder::load_from_filesystem, LevelDescription::validate_in_filesystem and
+    MeshDescription::check_asset_in) find the files through a VirtualFilesystem:
+    - NativeFilesystem reads the files of the operating system,
+    - MemoryFilesystem stores the files in memory, to test the loaders without touching the disk,
+    - MaskeradFilesystem reads the files of a maskerad_filesystem::Filesystem, relative to one of its RootDirs.
+      It is only available with the 'maskerad_filesystem' feature.
+*/
+
+/// Finds and reads the files referenced by the data files.
+pub trait VirtualFilesystem {
+    /// Opens a file for reading.
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read>>;
+
+    /// The canonical path of a file, identifying it whatever the path used to reach it.
+    /// Fails if the file doesn't exist.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.open(path).is_ok()
+    }
+
+    fn read_to_end(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut content = Vec::new();
+        self.open(path)?.read_to_end(&mut content)?;
+        Ok(content)
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        let mut content = String::new();
+        self.open(path)?.read_to_string(&mut content)?;
+        Ok(content)
+    }
+}
+
+/// The filesystem of the operating system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NativeFilesystem;
+
+impl VirtualFilesystem for NativeFilesystem {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+}
+
+/// Files stored in memory. The paths are compared once normalized, the relative paths being
+/// relative to the root of the store.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFilesystem {
+    files: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl MemoryFilesystem {
+    pub fn new() -> Self {
+        debug!("Creating a new MemoryFilesystem.");
+        MemoryFilesystem::default()
+    }
+
+    pub fn add_file<P: AsRef<Path>, C: Into<Vec<u8>>>(&mut self, path: P, content: C) -> &mut Self {
+        debug!("Adding the file {} to the MemoryFilesystem.", path.as_ref().display());
+        self.files.insert(asset_path::normalize(path.as_ref()), content.into());
+        self
+    }
+
+    fn find(&self, path: &Path) -> io::Result<(&PathBuf, &Vec<u8>)> {
+        let path = asset_path::normalize(path);
+        self.files.get_key_value(&path).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("'{}' is not in the MemoryFilesystem", path.display()))
+        })
+    }
+}
+
+impl VirtualFilesystem for MemoryFilesystem {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read>> {
+        let (_, content) = self.find(path)?;
+        Ok(Box::new(Cursor::new(content.clone())))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.find(path).map(|(canonical_path, _)| canonical_path.clone())
+    }
+}
+
+/// The files of a maskerad_filesystem::Filesystem, the relative paths being relative to a root directory.
+#[cfg(feature = "maskerad_filesystem")]
+pub struct MaskeradFilesystem<'a> {
+    filesystem: &'a Filesystem,
+    root: RootDir,
+}
+
+#[cfg(feature = "maskerad_filesystem")]
+impl<'a> MaskeradFilesystem<'a> {
+    pub fn new(filesystem: &'a Filesystem, root: RootDir) -> Self {
+        debug!("Creating a new MaskeradFilesystem.");
+        MaskeradFilesystem {
+            filesystem,
+            root,
+        }
+    }
+
+    fn path(&self, path: &Path) -> io::Result<PathBuf> {
+        self.filesystem.construct_path_from_root(self.root, path).map_err(io_error)
+    }
+}
+
+#[cfg(feature = "maskerad_filesystem")]
+fn io_error<E: fmt::Display>(error: E) -> io::Error {
+    io::Error::other(error.to_string())
+}
+
+#[cfg(feature = "maskerad_filesystem")]
+impl<'a> VirtualFilesystem for MaskeradFilesystem<'a> {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read>> {
+        let reader = self.filesystem.open(self.path(path)?).map_err(io_error)?;
+        Ok(Box::new(reader))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(self.path(path)?)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.path(path).map(|path| path.is_file()).unwrap_or(false)
+    }
+}
@@ -0,0 +1,72 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+extern crate maskerad_filesystem;
+extern crate maskerad_data_parser;
+
+use maskerad_filesystem::filesystem::Filesystem;
+use maskerad_filesystem::game_directories::RootDir;
+use maskerad_data_parser::level_description::LevelDescription;
+use maskerad_data_parser::gameobject_builder::GameObjectBuilder;
+use maskerad_data_parser::mesh_description::MeshDescription;
+use maskerad_data_parser::asset_path::PathResolver;
+use maskerad_data_parser::unknown_key::UnknownKeyMode;
+use maskerad_data_parser::diagnostic::DiagnosticCode;
+use maskerad_data_parser::data_parser_error::DataParserError;
+
+#[test]
+fn resolve_asset_paths() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let project_root = fs.construct_path_from_root(RootDir::WorkingDirectory, "").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_asset_paths.toml").unwrap();
+    let mut paths = PathResolver::new();
+    paths.add_project_root(project_root.as_path());
+
+    // The asset paths are kept, the mesh paths of the included files are rebased on the level.
+    let (level_desc, _) = LevelDescription::load_from_file_with_resolver(level_path.as_path(), UnknownKeyMode::Strict, &paths).unwrap();
+    let mesh_paths: Vec<Option<String>> = level_desc.slice().iter().map(|gameobject| gameobject.get_mesh_resource()).collect();
+    assert_eq!(mesh_paths, vec![
+        Some(String::from("asset://resource_test/triangle.gltf")),
+        Some(String::from("../resource_test/triangle.gltf")),
+        Some(String::from("../resource_test/kit.gltf")),
+    ]);
+    assert_eq!(level_desc.slice()[2].transform().position(), [3.0, 0.0, 0.0]);
+    assert!(LevelDescription::validate_file_with_resolver(level_path.as_path(), &paths).is_empty());
+
+    // Without project root, the asset paths can't be resolved.
+    match LevelDescription::load_from_file(level_path.as_path()) {
+        Err(DataParserError::ReferenceError(description)) => assert!(description.contains("no project root"), "{}", description),
+        other => panic!("Expected a ReferenceError, got {:?}", other),
+    }
+    let diagnostics = LevelDescription::validate_file(level_path.as_path());
+    assert!(diagnostics.iter().any(|diagnostic| diagnostic.code() == DiagnosticCode::MissingMesh));
+    assert!(diagnostics.iter().any(|diagnostic| diagnostic.code() == DiagnosticCode::BrokenReference));
+}
+
+#[test]
+fn write_portable_paths() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let project_root = fs.construct_path_from_root(RootDir::WorkingDirectory, "").unwrap();
+    let kit_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "resource_test/kit.gltf").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test/level_portable.toml").unwrap();
+
+    let mut kit = GameObjectBuilder::new("kit");
+    kit.add_mesh(MeshDescription::new(kit_path.to_str().unwrap()));
+    let mut level_desc = LevelDescription::new("level_portable");
+    level_desc.add_gameobject(kit);
+
+    let mut paths = PathResolver::new();
+    let relative_string = level_desc.as_string_toml_portable(level_path.as_path(), &paths).unwrap();
+    assert!(relative_string.contains("path = \"../resource_test/kit.gltf\""), "{}", relative_string);
+
+    paths.add_project_root(project_root.as_path());
+    let asset_string = level_desc.as_string_toml_portable(level_path.as_path(), &paths).unwrap();
+    assert!(asset_string.contains("path = \"asset://resource_test/kit.gltf\""), "{}", asset_string);
+
+    // The description itself is left untouched.
+    assert_eq!(level_desc.slice()[0].get_mesh_resource(), Some(String::from(kit_path.to_str().unwrap())));
+}
@@ -0,0 +1,136 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+extern crate maskerad_filesystem;
+extern crate maskerad_data_parser;
+extern crate toml;
+
+use maskerad_filesystem::filesystem::Filesystem;
+use maskerad_filesystem::game_directories::RootDir;
+use maskerad_data_parser::level_description::LevelDescription;
+use maskerad_data_parser::gameobject_builder::GameObjectBuilder;
+use maskerad_data_parser::transform_description::TransformDescription;
+use maskerad_data_parser::rotation_description::{RotationDescription, AngleUnit, EulerOrder};
+use maskerad_data_parser::mesh_description::{MeshDescription, MeshSelection, GltfReference};
+use maskerad_data_parser::component_description::ScriptDescription;
+use maskerad_data_parser::data_parser_error::DataParserError;
+use maskerad_data_parser::binary_format;
+use std::io::BufReader;
+
+fn binary_format_error<T>(result: Result<T, DataParserError>) -> String {
+    match result {
+        Err(DataParserError::BinaryFormatError(description)) => description,
+        Err(error) => panic!("Expected a binary format error, got {}", error),
+        Ok(_) => panic!("Expected a binary format error"),
+    }
+}
+
+#[test]
+fn binary_round_trip() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_hierarchy.toml").unwrap();
+    let level_desc = LevelDescription::load_from_file(level_path.as_path()).unwrap();
+    let binary = level_desc.as_binary().unwrap();
+    let binary_level = LevelDescription::load_from_binary(&mut binary.as_slice()).unwrap();
+    assert_eq!(binary_level.as_string_toml().unwrap(), level_desc.as_string_toml().unwrap());
+    assert!(binary_level.hierarchy().is_ok());
+
+    let gameobject_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/gameobject_components.toml").unwrap();
+    let mut torch = GameObjectBuilder::load_from_file(gameobject_path.as_path()).unwrap();
+    let mut script = ScriptDescription::new("schedule");
+    script
+        .add_parameter("hours", vec![8, 20])
+        .add_parameter("start", "1979-05-27T07:32:00Z".parse::<toml::value::Datetime>().unwrap());
+    let mut flame = GameObjectBuilder::new("flame");
+    let mut mesh = MeshDescription::new("asset://meshes/flame.gltf");
+    mesh.add_selection(MeshSelection::Mesh { mesh: GltfReference::Name(String::from("flame")), primitive: Some(1) });
+    flame
+        .add_transform(TransformDescription::new([0.0, 0.5, 0.0], RotationDescription::Euler { angles: [0.0, 90.0, 0.0], unit: AngleUnit::Degrees, order: EulerOrder::ZYX }, [1.0, 1.0, 1.0]))
+        .add_mesh(mesh);
+    torch.add_script(script).add_child(flame);
+
+    let binary = torch.as_binary().unwrap();
+    let binary_torch = GameObjectBuilder::load_from_binary(&mut BufReader::new(binary.as_slice())).unwrap();
+    assert_eq!(binary_torch.as_string_toml().unwrap(), torch.as_string_toml().unwrap());
+    assert_eq!(binary_torch.children()[0].mesh().unwrap().selection(), torch.children()[0].mesh().unwrap().selection());
+}
+
+#[test]
+fn reject_invalid_binary_data() {
+    let mut level_desc = LevelDescription::new("binary");
+    level_desc.add_gameobject(GameObjectBuilder::new("rock"));
+    let binary = level_desc.as_binary().unwrap();
+
+    let mut corrupted = binary.clone();
+    let last = corrupted.len() - 1;
+    corrupted[last] ^= 0xff;
+    assert!(binary_format_error(LevelDescription::load_from_binary(&mut corrupted.as_slice())).contains("checksum"));
+
+    let mut future = binary.clone();
+    future[4] = 99;
+    assert!(binary_format_error(LevelDescription::load_from_binary(&mut future.as_slice())).contains("version 99"));
+
+    let truncated = &binary[..binary.len() - 3];
+    assert!(binary_format_error(LevelDescription::load_from_binary(&mut &truncated[..])).contains("declares"));
+
+    assert!(binary_format_error(LevelDescription::load_from_binary(&mut "title = \"toml\"".as_bytes())).contains("header"));
+    assert!(binary_format_error(GameObjectBuilder::load_from_binary(&mut binary.as_slice())).contains("contains a level, not a gameobject"));
+}
+
+#[test]
+fn reject_deeply_nested_binary_data() {
+    let mut nested = toml::Value::Integer(0);
+    for _ in 0..binary_format::MAX_NESTING_DEPTH + 1 {
+        nested = toml::Value::Array(vec![nested]);
+    }
+    let mut script = ScriptDescription::new("deep");
+    script.add_parameter("nested", nested);
+    let mut gameobject = GameObjectBuilder::new("deep");
+    gameobject.add_script(script);
+    let binary = gameobject.as_binary().unwrap();
+    assert!(binary_format_error(GameObjectBuilder::load_from_binary(&mut binary.as_slice())).contains("deeper than"));
+
+    let mut gameobject = GameObjectBuilder::new("leaf");
+    for depth in 0..binary_format::MAX_NESTING_DEPTH + 1 {
+        let mut parent = GameObjectBuilder::new(format!("parent{}", depth));
+        parent.add_child(gameobject);
+        gameobject = parent;
+    }
+    let binary = gameobject.as_binary().unwrap();
+    assert!(binary_format_error(GameObjectBuilder::load_from_binary(&mut binary.as_slice())).contains("deeper than"));
+
+    // The nesting up to the limit is decoded.
+    let mut gameobject = GameObjectBuilder::new("leaf");
+    for depth in 0..binary_format::MAX_NESTING_DEPTH {
+        let mut parent = GameObjectBuilder::new(format!("parent{}", depth));
+        parent.add_child(gameobject);
+        gameobject = parent;
+    }
+    assert!(GameObjectBuilder::load_from_binary(&mut gameobject.as_binary().unwrap().as_slice()).is_ok());
+}
+
+#[test]
+fn convert_between_toml_and_binary() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_prefabs.toml").unwrap();
+    let binary_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test/level_prefabs.bin").unwrap();
+    let toml_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test/level_prefabs.toml").unwrap();
+
+    // The references and prefabs are resolved in the binary file.
+    binary_format::convert_toml_to_binary(level_path.as_path(), binary_path.as_path()).unwrap();
+    binary_format::convert_binary_to_toml(binary_path.as_path(), toml_path.as_path()).unwrap();
+    let level_desc = LevelDescription::load_from_file(level_path.as_path()).unwrap();
+    let converted_level = LevelDescription::load_from_file(toml_path.as_path()).unwrap();
+    assert_eq!(converted_level.as_string_toml().unwrap(), level_desc.as_string_toml().unwrap());
+
+    let gameobject_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/gameobject1.toml").unwrap();
+    let gameobject_binary_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test/gameobject1.bin").unwrap();
+    binary_format::convert_toml_to_binary(gameobject_path.as_path(), gameobject_binary_path.as_path()).unwrap();
+    let mut binary_file = fs.open(gameobject_binary_path.as_path()).unwrap();
+    let gameobject = GameObjectBuilder::load_from_binary(&mut binary_file).unwrap();
+    assert_eq!(gameobject.as_string_toml().unwrap(), GameObjectBuilder::load_from_file(gameobject_path.as_path()).unwrap().as_string_toml().unwrap());
+}
@@ -0,0 +1,109 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+extern crate maskerad_data_parser;
+
+use maskerad_data_parser::gameobject_builder::GameObjectBuilder;
+use maskerad_data_parser::transform_description::TransformDescription;
+use maskerad_data_parser::mesh_description::{MeshDescription, MeshSelection};
+use maskerad_data_parser::component_description::{LightDescription, LightKind};
+use maskerad_data_parser::data_parser_error::DataParserError;
+use maskerad_data_parser::level_description::LevelDescription;
+use maskerad_data_parser::level_instance::InstantiationMode;
+
+#[test]
+fn build_gameobject() {
+    let mut go_desc = GameObjectBuilder::new("gameobject1");
+    go_desc
+        .add_transform(TransformDescription::new([1.0, 2.0, 3.0], [0.0, 1.57, 0.0], [1.0, 1.0, 1.0]))
+        .add_mesh(MeshDescription::new("resource_test/untitled.gltf"));
+    assert!(go_desc.build().is_ok());
+
+    // The default transform is the identity.
+    assert!(GameObjectBuilder::new("gameobject2").build().is_ok());
+}
+
+#[test]
+fn build_invalid_gameobject() {
+    let mut go_desc = GameObjectBuilder::new("gameobject1");
+    go_desc.add_transform(TransformDescription::new([f64::NAN, 0.0, 0.0], [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]));
+    match go_desc.build() {
+        Err(DataParserError::BuildError(description)) => {
+            assert!(description.contains("gameobject1"));
+            assert!(description.contains("transform.position"));
+        },
+        other => panic!("Expected a BuildError, got {:?}", other),
+    }
+
+    go_desc.add_transform(TransformDescription::new([0.0, 0.0, 0.0], [0.0, 0.0, 0.0], [1.0, 0.0, 1.0]));
+    match go_desc.build() {
+        Err(DataParserError::BuildError(description)) => assert!(description.contains("transform.scale")),
+        other => panic!("Expected a BuildError, got {:?}", other),
+    }
+
+    go_desc
+        .add_transform(TransformDescription::default())
+        .add_mesh(MeshDescription::new(""));
+    match go_desc.build() {
+        Err(DataParserError::BuildError(description)) => assert!(description.contains("mesh.path")),
+        other => panic!("Expected a BuildError, got {:?}", other),
+    }
+
+    // The runtime mesh can't select a part of the asset.
+    let mut mesh = MeshDescription::new("resource_test/kit.gltf");
+    mesh.add_selection(MeshSelection::Mesh { mesh: "tree".into(), primitive: Some(1) });
+    go_desc.add_mesh(mesh);
+    match go_desc.build() {
+        Err(DataParserError::BuildError(description)) => {
+            assert!(description.contains("the primitive 1 of the mesh 'tree'"), "{}", description);
+        },
+        other => panic!("Expected a BuildError, got {:?}", other),
+    }
+
+    // The runtime gameobject has no components.
+    let mut go_desc = GameObjectBuilder::new("lamp");
+    go_desc
+        .add_tag("decor")
+        .add_light(LightDescription::new(LightKind::Point));
+    match go_desc.build() {
+        Err(DataParserError::BuildError(description)) => {
+            assert!(description.contains("'lamp' has tags, a light,"), "{}", description);
+        },
+        other => panic!("Expected a BuildError, got {:?}", other),
+    }
+}
+
+#[test]
+fn instantiate_level() {
+    let mut level_desc = LevelDescription::new("level1");
+    let mut go1_desc = GameObjectBuilder::new("gameobject1");
+    go1_desc.add_transform(TransformDescription::new([1.0, 2.0, 3.0], [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]));
+    let mut go2_desc = GameObjectBuilder::new("gameobject2");
+    go2_desc.add_transform(TransformDescription::new([f64::NAN, 0.0, 0.0], [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]));
+    let mut go3_desc = GameObjectBuilder::new("gameobject3");
+    go3_desc.add_mesh(MeshDescription::new(""));
+    let go4_desc = GameObjectBuilder::new("gameobject4");
+
+    level_desc
+        .add_gameobject(go1_desc)
+        .add_gameobject(go2_desc)
+        .add_gameobject(go3_desc)
+        .add_gameobject(go4_desc);
+
+    match level_desc.instantiate(InstantiationMode::Strict) {
+        Err(DataParserError::InstantiationError(_, errors)) => assert_eq!(errors.len(), 2),
+        Err(other) => panic!("Expected an InstantiationError, got {:?}", other),
+        Ok(_) => panic!("The instantiation of a level with broken gameobjects should fail in strict mode."),
+    }
+
+    let instance = level_desc.instantiate(InstantiationMode::SkipBroken).unwrap();
+    assert!(!instance.is_complete());
+    let built_ids: Vec<&str> = instance.gameobjects().iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(built_ids, vec!["gameobject1", "gameobject4"]);
+    let failed_ids: Vec<&str> = instance.failures().iter().map(|(id, _)| id.as_str()).collect();
+    assert_eq!(failed_ids, vec!["gameobject2", "gameobject3"]);
+}
@@ -0,0 +1,168 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+extern crate maskerad_filesystem;
+extern crate maskerad_data_parser;
+
+use maskerad_filesystem::filesystem::Filesystem;
+use maskerad_filesystem::game_directories::RootDir;
+use maskerad_data_parser::level_description::LevelDescription;
+use maskerad_data_parser::gameobject_builder::GameObjectBuilder;
+use maskerad_data_parser::transform_description::TransformDescription;
+use maskerad_data_parser::mesh_description::MeshDescription;
+use maskerad_data_parser::component_description::{LightDescription, LightKind, ScriptDescription};
+use maskerad_data_parser::canonical::SerializationMode;
+use maskerad_data_parser::data_format::DataFormat;
+use std::fs;
+
+fn lamp(shade_height: f64) -> GameObjectBuilder {
+    let mut bulb = GameObjectBuilder::new("bulb");
+    bulb.add_transform(TransformDescription::new([0.0, 0.5, 0.0], [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]));
+    bulb.add_light(LightDescription::new(LightKind::Point));
+    let mut shade = GameObjectBuilder::new("shade");
+    shade.add_transform(TransformDescription::new([0.0, shade_height, 0.0], [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]));
+    let mut flicker = ScriptDescription::new("flicker");
+    flicker.add_parameter("speed", 2.5).add_parameter("colors", vec!["red", "orange"]);
+
+    let mut lamp = GameObjectBuilder::new("lamp");
+    lamp.add_transform(TransformDescription::new([1.0, 0.0, 0.0], [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]));
+    lamp.add_script(flicker);
+    lamp.add_child(shade);
+    lamp.add_child(bulb);
+    lamp
+}
+
+fn crate_gameobject(x: f64, z: f64) -> GameObjectBuilder {
+    let mut crate_gameobject = GameObjectBuilder::new("crate");
+    crate_gameobject.add_transform(TransformDescription::new([x, 0.0, z], [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]));
+    crate_gameobject.add_mesh(MeshDescription::new("crate.gltf"));
+    crate_gameobject
+}
+
+#[test]
+fn canonical_level() {
+    // The same level, built in another order, with float noise.
+    let mut level = LevelDescription::new("canonical");
+    level.add_gameobject(lamp(0.75)).add_gameobject(crate_gameobject(0.3, 0.0));
+    let mut noisy_level = LevelDescription::new("canonical");
+    noisy_level.add_gameobject(crate_gameobject(0.1 + 0.2, -0.0000001)).add_gameobject(lamp(0.75f32 as f64 + 0.0000000001));
+
+    let canonical = level.as_string_toml_with_mode(SerializationMode::Canonical).unwrap();
+    assert_eq!(noisy_level.as_string_toml_with_mode(SerializationMode::Canonical).unwrap(), canonical);
+    assert_eq!(canonical, "\
+format_version = 2
+title = \"canonical\"
+
+[[gameobjects]]
+id = \"crate\"
+
+[gameobjects.mesh]
+path = \"crate.gltf\"
+
+[gameobjects.transform]
+position = [0.3, 0.0, 0.0]
+rotation = [0.0, 0.0, 0.0]
+scale = [1.0, 1.0, 1.0]
+
+[[gameobjects]]
+id = \"lamp\"
+
+[[gameobjects.scripts]]
+name = \"flicker\"
+
+[gameobjects.scripts.parameters]
+colors = [\"red\", \"orange\"]
+speed = 2.5
+
+[gameobjects.transform]
+position = [1.0, 0.0, 0.0]
+rotation = [0.0, 0.0, 0.0]
+scale = [1.0, 1.0, 1.0]
+
+[[gameobjects]]
+id = \"bulb\"
+parent = \"lamp\"
+
+[gameobjects.light]
+color = [1.0, 1.0, 1.0]
+intensity = 1.0
+kind = \"point\"
+
+[gameobjects.transform]
+position = [0.0, 0.5, 0.0]
+rotation = [0.0, 0.0, 0.0]
+scale = [1.0, 1.0, 1.0]
+
+[[gameobjects]]
+id = \"shade\"
+parent = \"lamp\"
+
+[gameobjects.transform]
+position = [0.0, 0.75, 0.0]
+rotation = [0.0, 0.0, 0.0]
+scale = [1.0, 1.0, 1.0]
+");
+
+    // The verbatim mode keeps the order of the level.
+    let verbatim = noisy_level.as_string_toml().unwrap();
+    assert!(verbatim.contains("position = [0.30000000000000004, 0.0, -0.0000001]"), "{}", verbatim);
+
+    // Saving an unchanged level gives the same bytes.
+    let reloaded = LevelDescription::load_from_toml(&mut canonical.as_bytes()).unwrap();
+    assert_eq!(reloaded.as_string_toml_with_mode(SerializationMode::Canonical).unwrap(), canonical);
+
+    let json = level.as_string_with_mode(DataFormat::Json, SerializationMode::Canonical).unwrap();
+    assert_eq!(noisy_level.as_string_with_mode(DataFormat::Json, SerializationMode::Canonical).unwrap(), json);
+}
+
+#[test]
+fn canonical_gameobject_file() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let lamp_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test/gameobject_canonical.toml").unwrap();
+
+    lamp(0.75).save_to_file_with_mode(lamp_path.as_path(), SerializationMode::Canonical).unwrap();
+    let saved = fs::read_to_string(lamp_path.as_path()).unwrap();
+    let bulb = saved.find("id = \"bulb\"").unwrap();
+    let shade = saved.find("id = \"shade\"").unwrap();
+    assert!(bulb < shade, "{}", saved);
+
+    let reloaded = GameObjectBuilder::load_from_file(lamp_path.as_path()).unwrap();
+    reloaded.save_to_file_with_mode(lamp_path.as_path(), SerializationMode::Canonical).unwrap();
+    assert_eq!(fs::read_to_string(lamp_path.as_path()).unwrap(), saved);
+}
+
+#[test]
+fn canonical_small_rotations_and_scales() {
+    // Rounded, the rotation and the scale would be null.
+    let mut speck = GameObjectBuilder::new("speck");
+    speck.add_transform(TransformDescription::new([0.0000001, 0.0, 0.0], [0.0000001, 0.0, 0.0, 0.0], [0.0000001, 1.0, 1.0]));
+    let mut level = LevelDescription::new("small");
+    level.add_gameobject(speck);
+
+    let canonical = level.as_string_toml_with_mode(SerializationMode::Canonical).unwrap();
+    let reloaded = LevelDescription::load_from_toml(&mut canonical.as_bytes()).unwrap();
+    let transform = reloaded.slice()[0].transform();
+    assert_eq!(transform.position(), [0.0, 0.0, 0.0]);
+    assert_eq!(transform.quaternion(), [1.0, 0.0, 0.0, 0.0]);
+    assert_eq!(transform.scale(), [0.0000001, 1.0, 1.0]);
+}
+
+#[test]
+fn canonical_deep_hierarchy() {
+    // Each gameobject is the parent of the next one.
+    let mut level = LevelDescription::new("deep");
+    for index in 0..100_000 {
+        let mut gameobject = GameObjectBuilder::new(format!("gameobject{}", index));
+        if index > 0 {
+            gameobject.add_parent(format!("gameobject{}", index - 1));
+        }
+        level.add_gameobject(gameobject);
+    }
+
+    let canonical = level.as_string_toml_with_mode(SerializationMode::Canonical).unwrap();
+    assert!(canonical.find("id = \"gameobject99999\"").unwrap() > canonical.find("id = \"gameobject99998\"").unwrap());
+}
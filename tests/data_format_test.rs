@@ -0,0 +1,122 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+extern crate maskerad_filesystem;
+extern crate maskerad_data_parser;
+
+use maskerad_filesystem::filesystem::Filesystem;
+use maskerad_filesystem::game_directories::RootDir;
+use maskerad_data_parser::level_description::LevelDescription;
+use maskerad_data_parser::gameobject_builder::GameObjectBuilder;
+use maskerad_data_parser::transform_description::TransformDescription;
+use maskerad_data_parser::data_format::DataFormat;
+use maskerad_data_parser::data_parser_error::DataParserError;
+use maskerad_data_parser::unknown_key::UnknownKeyMode;
+use maskerad_data_parser::diagnostic::DiagnosticCode;
+
+#[test]
+fn load_every_format() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let toml_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_formats.toml").unwrap();
+    let json_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_formats.json").unwrap();
+    let lamp_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/gameobject_lamp.ron").unwrap();
+
+    // The JSON level references a RON gameobject and a TOML prefab.
+    let toml_level = LevelDescription::load_from_file(toml_path.as_path()).unwrap();
+    let (json_level, warnings) = LevelDescription::load_from_file_with_mode(json_path.as_path(), UnknownKeyMode::Strict).unwrap();
+    assert!(warnings.is_empty());
+    assert_eq!(json_level.as_string_toml().unwrap(), toml_level.as_string_toml().unwrap());
+    assert_eq!(json_level.slice()[1].get_mesh_resource(), Some(String::from("rock.gltf")));
+
+    let lamp = GameObjectBuilder::load_from_file(lamp_path.as_path()).unwrap();
+    assert_eq!(lamp.light().unwrap().range(), Some(12.5));
+    assert_eq!(lamp.as_string_toml().unwrap(), toml_level.slice()[0].as_string_toml().unwrap());
+}
+
+#[test]
+fn save_every_format() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let toml_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_formats.toml").unwrap();
+    let level_desc = LevelDescription::load_from_file(toml_path.as_path()).unwrap();
+
+    for &format in [DataFormat::Toml, DataFormat::Json, DataFormat::Ron].iter() {
+        let document = level_desc.as_string(format).unwrap();
+        let (level, warnings) = LevelDescription::load_from_reader_with_mode(&mut document.as_bytes(), format, UnknownKeyMode::Strict).unwrap();
+        assert!(warnings.is_empty(), "{:?}", format);
+        assert_eq!(level.as_string_toml().unwrap(), level_desc.as_string_toml().unwrap(), "{:?}:\n{}", format, document);
+
+        let lamp_document = level_desc.slice()[0].as_string(format).unwrap();
+        let lamp = GameObjectBuilder::load_from_reader(&mut lamp_document.as_bytes(), format).unwrap();
+        assert_eq!(lamp.as_string_toml().unwrap(), level_desc.slice()[0].as_string_toml().unwrap());
+    }
+
+    let ron_document = level_desc.slice()[0].as_string(DataFormat::Ron).unwrap();
+    assert!(ron_document.contains("    light: (\n        color: [1.0, 1.0, 1.0],\n        intensity: 3.0,\n        kind: \"point\",\n        range: 12.5,\n    ),\n"), "{}", ron_document);
+
+    // JSON has no infinite floats.
+    let mut far = GameObjectBuilder::new("far");
+    far.add_transform(TransformDescription::new([f64::INFINITY, 0.0, 0.0], [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]));
+    match far.as_string(DataFormat::Json) {
+        Err(DataParserError::JsonError(description, json_error)) => {
+            assert!(description.contains("JSON document"), "{}", description);
+            assert!(json_error.to_string().contains("inf"), "{}", json_error);
+        },
+        other => panic!("Expected a JSON error, got {:?}", other),
+    }
+}
+
+#[test]
+fn locate_syntax_errors() {
+    let json = "{\n  \"title\": \"broken\",\n  \"gameobjects\": [,]\n}\n";
+    match LevelDescription::load_from_reader(&mut json.as_bytes(), DataFormat::Json) {
+        Err(DataParserError::SyntaxError(description, location)) => {
+            assert!(description.starts_with("Could not parse the JSON document"), "{}", description);
+            assert_eq!(location.line(), Some(3));
+            assert_eq!(location.snippet(), Some("  \"gameobjects\": [,]"));
+        },
+        other => panic!("Expected a syntax error, got {:?}", other),
+    }
+
+    // The values are validated the same way, whatever the format.
+    let ron = "(id: \"rock\", transform: (position: [0.0], rotation: [0.0, 0.0, 0.0], scale: [1.0, 1.0, 1.0]))";
+    let error = GameObjectBuilder::load_from_reader(&mut ron.as_bytes(), DataFormat::Ron).unwrap_err();
+    assert!(error.render().contains("transform.position"), "{}", error.render());
+
+    let ron_level = "[\"not\", \"a\", \"level\"]";
+    assert!(LevelDescription::load_from_reader(&mut ron_level.as_bytes(), DataFormat::Ron).unwrap_err().to_string().contains("must be a table"));
+
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test/level_broken.ron").unwrap();
+    {
+        use std::io::Write;
+        let mut level_file = fs.create(level_path.as_path()).unwrap();
+        level_file.write_all(b"(\n    title: \"broken\",\n    gameobjects: [\"gameobject1.toml\" \"gameobject2.toml\"],\n)\n").unwrap();
+    }
+    let diagnostics = LevelDescription::validate_file(level_path.as_path());
+    assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+    assert_eq!(diagnostics[0].code(), DiagnosticCode::InvalidToml);
+    assert_eq!(diagnostics[0].location().line(), Some(3));
+}
+
+#[test]
+fn locate_errors_in_json_and_ron() {
+    let json = "{\n  \"id\": \"rock\",\n  \"transform\": {\n    \"position\": \"origin\",\n    \"rotation\": [0.0, 0.0, 0.0],\n    \"scale\": [1.0, 1.0, 1.0]\n  }\n}\n";
+    let error = GameObjectBuilder::load_from_reader(&mut json.as_bytes(), DataFormat::Json).unwrap_err();
+    let location = error.location().unwrap();
+    assert_eq!(location.key_path(), Some("transform.position"));
+    assert_eq!(location.line(), Some(4));
+    assert_eq!(location.snippet(), Some("    \"position\": \"origin\","));
+
+    let ron = "(\n    title: \"typos\",\n    gameobjects: [\n        (\n            id: \"rock\",\n            transfrom: (scale: [1.0, 1.0, 1.0]),\n        ),\n    ],\n)\n";
+    match LevelDescription::load_from_reader_with_mode(&mut ron.as_bytes(), DataFormat::Ron, UnknownKeyMode::Strict) {
+        Err(DataParserError::UnknownKeyError(_, location)) => {
+            assert_eq!(location.key_path(), Some("gameobjects[0].transfrom"));
+            assert_eq!(location.line(), Some(6));
+        },
+        other => panic!("Expected an unknown key error, got {:?}", other),
+    }
+}
@@ -0,0 +1,157 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+extern crate maskerad_filesystem;
+extern crate maskerad_data_parser;
+
+use maskerad_filesystem::filesystem::Filesystem;
+use maskerad_filesystem::game_directories::RootDir;
+use maskerad_data_parser::document_editor::DocumentEditor;
+use maskerad_data_parser::level_description::LevelDescription;
+use maskerad_data_parser::gameobject_builder::GameObjectBuilder;
+use maskerad_data_parser::transform_description::TransformDescription;
+use maskerad_data_parser::mesh_description::MeshDescription;
+use maskerad_data_parser::data_parser_error::DataParserError;
+use std::fs;
+
+#[test]
+fn edit_level_preserving_comments() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_commented.toml").unwrap();
+    let edited_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test/level_commented.toml").unwrap();
+    let original = fs::read_to_string(level_path.as_path()).unwrap();
+
+    let mut editor = DocumentEditor::load_from_file(level_path.as_path()).unwrap();
+    assert_eq!(editor.ids(), vec!["fountain", "fish", "bubble", "gate"]);
+    assert_eq!(editor.as_string(), original);
+
+    let mut bench = GameObjectBuilder::new("bench");
+    bench.add_transform(TransformDescription::new([2.0, 0.0, 3.0], [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]));
+    bench.add_mesh(MeshDescription::new("bench.gltf"));
+    editor
+        .set_position("fountain", [5.0, 0.0, -2.5]).unwrap()
+        .set_mesh_path("fountain", "fountain_v2.gltf").unwrap()
+        .set_position("bubble", [0.0, 0.75, 0.0]).unwrap()
+        .set_transform("gate", &TransformDescription::new([0.0, 0.0, 12.0], [0.0, 0.0, 0.0, 1.0], [2.0, 2.0, 2.0])).unwrap()
+        .add_gameobject(&bench).unwrap();
+    editor.save_to_file(edited_path.as_path()).unwrap();
+    let edited = fs::read_to_string(edited_path.as_path()).unwrap();
+    assert_eq!(edited, editor.as_string());
+
+    // Only the edited values changed, keeping their comments.
+    assert!(edited.starts_with("# The courtyard, first level of the demo.\nformat_version = 2\ntitle = \"courtyard\"\n\n# The fountain, in the middle of the courtyard.\n"), "{}", edited);
+    assert!(edited.contains("position = [5.0, 0.0, -2.5]   # centered on the pavement\nrotation = [0.0, 0.0, 0.0]\nscale    = [1.0, 1.0, 1.0]\n"), "{}", edited);
+    assert!(edited.contains("path = \"fountain_v2.gltf\"  # TODO: the new model\nnode = \"basin\"\n"), "{}", edited);
+    assert!(edited.contains("{ id = \"bubble\", transform = { position = [0.0, 0.75, 0.0], rotation = [0.0, 0.0, 0.0], scale = [0.1, 0.1, 0.1] } },"), "{}", edited);
+    assert!(edited.contains("[gameobjects.transform.rotation]\nquaternion = [0.0, 0.0, 0.0, 1.0]\n"), "{}", edited);
+    assert!(!edited.contains("facing the fountain"), "{}", edited);
+
+    // The new gameobject comes after the last one.
+    let gate = edited.find("id = \"gate\"").unwrap();
+    let new_bench = edited.find("[[gameobjects]]\nid = \"bench\"").unwrap();
+    assert!(gate < new_bench, "{}", edited);
+    assert!(edited.contains("[gameobjects.mesh]\npath = \"bench.gltf\"\n"), "{}", edited);
+
+    let level = LevelDescription::load_from_toml(&mut edited.as_bytes()).unwrap();
+    let ids: Vec<&str> = level.slice().iter().map(|gameobject| gameobject.id()).collect();
+    assert_eq!(ids, vec!["fountain", "fish", "bubble", "gate", "bench"]);
+    assert_eq!(level.slice()[0].transform().position(), [5.0, 0.0, -2.5]);
+    assert_eq!(level.slice()[0].get_mesh_resource(), Some(String::from("fountain_v2.gltf")));
+    assert_eq!(level.slice()[2].transform().position(), [0.0, 0.75, 0.0]);
+    assert_eq!(level.slice()[3].transform().position(), [0.0, 0.0, 12.0]);
+    assert_eq!(level.slice()[4].transform().position(), [2.0, 0.0, 3.0]);
+}
+
+#[test]
+fn edit_inline_gameobjects() {
+    let source = "title = \"inline\"\ngameobjects = [\n    { id = \"crate\", transform = { position = [0.0, 0.0, 0.0], rotation = [0.0, 0.0, 0.0], scale = [1.0, 1.0, 1.0] } },\n]\n";
+    let mut editor = DocumentEditor::load_from_toml(&mut source.as_bytes()).unwrap();
+
+    let mut barrel = GameObjectBuilder::new("barrel");
+    barrel.add_transform(TransformDescription::new([1.0, 0.0, 0.0], [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]));
+    editor
+        .set_mesh_path("crate", "crate.gltf").unwrap()
+        .add_gameobject(&barrel).unwrap();
+
+    let edited = editor.as_string();
+    assert!(edited.starts_with("title = \"inline\"\ngameobjects = [\n    { id = \"crate\", transform = { position = [0.0, 0.0, 0.0], rotation = [0.0, 0.0, 0.0], scale = [1.0, 1.0, 1.0] }, mesh = { path = \"crate.gltf\" } },\n    { id = \"barrel\", "), "{}", edited);
+    assert!(!edited.contains("[[gameobjects]]"), "{}", edited);
+
+    let level = LevelDescription::load_from_toml(&mut edited.as_bytes()).unwrap();
+    assert_eq!(level.slice()[0].get_mesh_resource(), Some(String::from("crate.gltf")));
+    assert_eq!(level.slice()[1].id(), "barrel");
+    assert_eq!(level.slice()[1].transform().position(), [1.0, 0.0, 0.0]);
+}
+
+#[test]
+fn edit_gameobject_file() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let go1_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/gameobject1.toml").unwrap();
+
+    let mut editor = DocumentEditor::load_from_file(go1_path.as_path()).unwrap();
+    editor.set_position("gameobject1", [1.0, 2.0, 3.0]).unwrap();
+    assert!(editor.as_string().contains("[transform]\nposition = [1.0, 2.0, 3.0]\n"), "{}", editor.as_string());
+
+    let gameobject = GameObjectBuilder::load_from_toml(&mut editor.as_string().as_bytes()).unwrap();
+    assert_eq!(gameobject.transform().position(), [1.0, 2.0, 3.0]);
+
+    // The gameobjects are added to levels only.
+    assert!(editor.add_gameobject(&GameObjectBuilder::new("child")).is_err());
+}
+
+#[test]
+fn reject_invalid_edits() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_commented.toml").unwrap();
+    let v1_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_v1.toml").unwrap();
+
+    let mut editor = DocumentEditor::load_from_file(level_path.as_path()).unwrap();
+    let original = editor.as_string();
+    match editor.set_position("statue", [0.0, 0.0, 0.0]) {
+        Err(DataParserError::HierarchyError(description)) => assert!(description.contains("'statue'"), "{}", description),
+        other => panic!("Expected a hierarchy error, found {:?}", other.map(|_| ())),
+    }
+    match editor.add_gameobject(&GameObjectBuilder::new("fish")) {
+        Err(DataParserError::HierarchyError(_)) => {},
+        other => panic!("Expected a hierarchy error, found {:?}", other.map(|_| ())),
+    }
+    assert!(editor.set_position("gate", [0.0, f64::NAN, 0.0]).is_err());
+    assert_eq!(editor.as_string(), original);
+
+    // The keys of the version 1 are the keys of the current version: the older documents are edited as they are.
+    assert!(DocumentEditor::load_from_file(v1_path.as_path()).is_ok());
+
+    let mut editor = DocumentEditor::load_from_toml(&mut "title = \"broken\"\ngameobjects = 3\n".as_bytes()).unwrap();
+    match editor.add_gameobject(&GameObjectBuilder::new("a")) {
+        Err(DataParserError::DeserializationError(description, _, _)) => assert!(description.contains("not an array"), "{}", description),
+        other => panic!("Expected a deserialization error, found {:?}", other.map(|_| ())),
+    }
+
+    match DocumentEditor::load_from_toml(&mut "title = \"broken\"\n\n[[gameobjects]\nid = \"a\"\n".as_bytes()) {
+        Err(error) => assert_eq!(error.location().and_then(|location| location.line()), Some(3)),
+        Ok(_) => panic!("The document is not valid TOML."),
+    }
+}
+
+#[test]
+fn edit_older_documents_as_they_are() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let v1_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_v1.toml").unwrap();
+
+    // The version 1 documents without format_version don't get one.
+    let mut editor = DocumentEditor::load_from_file(v1_path.as_path()).unwrap();
+    editor.set_position("parent_v1", [1.0, 0.0, 0.0]).unwrap();
+    assert!(editor.as_string().starts_with("# A level written in the first version of the format.\ntitle = \"level_v1\"\n"), "{}", editor.as_string());
+    assert!(!editor.as_string().contains("format_version"), "{}", editor.as_string());
+
+    // The declared format_version line is kept as written.
+    let source = "format_version = 1   # the first release\ntitle = \"declared\"\n\n[[gameobjects]]\nid = \"crate\"\n\n[gameobjects.transform]\nposition = [0.0, 0.0, 0.0]\nrotation = [0.0, 0.0, 0.0]\nscale = [1.0, 1.0, 1.0]\n";
+    let mut editor = DocumentEditor::load_from_toml(&mut source.as_bytes()).unwrap();
+    editor.set_position("crate", [1.0, 2.0, 3.0]).unwrap();
+    assert!(editor.as_string().starts_with("format_version = 1   # the first release\ntitle = \"declared\"\n"), "{}", editor.as_string());
+    assert!(editor.as_string().contains("position = [1.0, 2.0, 3.0]\n"), "{}", editor.as_string());
+}
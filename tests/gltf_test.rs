@@ -0,0 +1,367 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+extern crate maskerad_filesystem;
+extern crate maskerad_data_parser;
+extern crate gltf;
+
+use maskerad_filesystem::filesystem::Filesystem;
+use maskerad_filesystem::game_directories::RootDir;
+use maskerad_data_parser::mesh_description::{MeshDescription, MeshSelection, GltfReference};
+use maskerad_data_parser::gameobject_builder::GameObjectBuilder;
+use maskerad_data_parser::level_description::LevelDescription;
+use maskerad_data_parser::level_instance::InstantiationMode;
+use maskerad_data_parser::diagnostic::DiagnosticCode;
+use maskerad_data_parser::data_parser_error::DataParserError;
+use maskerad_data_parser::gltf_export::GltfExportMode;
+use maskerad_data_parser::transform_description::TransformDescription;
+use maskerad_data_parser::component_description::{ExtrasMapping, ComponentKind, LightKind, ColliderDescription};
+use maskerad_data_parser::asset_path::PathResolver;
+
+fn assert_gltf_error(mesh: &str, expected: &str) {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let resource_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "resource_test").unwrap();
+
+    match MeshDescription::new(mesh).check_asset(resource_path.as_path()) {
+        Err(DataParserError::GltfError(description, _)) => assert!(description.contains(expected), "{}", description),
+        other => panic!("Expected a glTF error for {}, got {:?}", mesh, other),
+    }
+}
+
+#[test]
+fn check_mesh_assets() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let resource_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "resource_test").unwrap();
+    assert!(MeshDescription::new("triangle.gltf").check_asset(resource_path.as_path()).is_ok());
+
+    assert_gltf_error("does_not_exist.gltf", "Could not open");
+    assert_gltf_error("no_mesh.gltf", "contains no mesh");
+    assert_gltf_error("untitled.gltf", "untitled.bin");
+    assert_gltf_error("missing_image.gltf", "missing.png");
+}
+
+#[test]
+fn validate_level_meshes() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_diagnostics.toml").unwrap();
+    let diagnostics = LevelDescription::validate_file(level_path.as_path());
+
+    // The second gameobject uses untitled.gltf, whose buffer is missing.
+    let invalid_meshes: Vec<_> = diagnostics.iter().filter(|diagnostic| diagnostic.code() == DiagnosticCode::InvalidMesh).collect();
+    assert_eq!(invalid_meshes.len(), 1, "{:?}", diagnostics);
+    assert_eq!(invalid_meshes[0].location().key_path(), Some("gameobjects[1].mesh.path"));
+    assert!(invalid_meshes[0].message().contains("untitled.bin"));
+}
+
+fn check_selection(selection: MeshSelection) -> Result<(), DataParserError> {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let resource_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "resource_test").unwrap();
+    let mut mesh = MeshDescription::new("kit.gltf");
+    mesh.add_selection(selection);
+    mesh.check_asset(resource_path.as_path())
+}
+
+#[test]
+fn check_mesh_selections() {
+    assert!(check_selection(MeshSelection::Mesh { mesh: "rock".into(), primitive: None }).is_ok());
+    assert!(check_selection(MeshSelection::Mesh { mesh: 1.into(), primitive: Some(1) }).is_ok());
+    assert!(check_selection(MeshSelection::Node("tree_node".into())).is_ok());
+    assert!(check_selection(MeshSelection::Scene("kit".into())).is_ok());
+    assert!(check_selection(MeshSelection::Scene(0.into())).is_ok());
+
+    assert!(check_selection(MeshSelection::Mesh { mesh: "boulder".into(), primitive: None }).is_err());
+    assert!(check_selection(MeshSelection::Mesh { mesh: 2.into(), primitive: None }).is_err());
+    assert!(check_selection(MeshSelection::Mesh { mesh: "rock".into(), primitive: Some(1) }).is_err());
+    assert!(check_selection(MeshSelection::Node(7.into())).is_err());
+
+    match check_selection(MeshSelection::Node("empty".into())) {
+        Err(DataParserError::GltfError(description, _)) => assert!(description.contains("contains no mesh"), "{}", description),
+        other => panic!("Expected a glTF error, got {:?}", other),
+    }
+    assert!(check_selection(MeshSelection::Scene("empty_scene".into())).is_err());
+}
+
+#[test]
+fn check_selections_with_cyclic_nodes() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let resource_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "resource_test").unwrap();
+
+    // The nodes loop_a and loop_b are children of each other.
+    for selection in [MeshSelection::Node("loop_a".into()), MeshSelection::Scene("loops".into())].iter().cloned() {
+        let mut mesh = MeshDescription::new("cyclic_nodes.gltf");
+        mesh.add_selection(selection);
+        match mesh.check_asset(resource_path.as_path()) {
+            Err(DataParserError::GltfError(description, _)) => assert!(description.contains("contains no mesh"), "{}", description),
+            other => panic!("Expected a glTF error, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn deserialize_mesh_selection() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let go_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/gameobject_kit_tree.toml").unwrap();
+    let go_desc = GameObjectBuilder::load_from_file(go_path.as_path()).unwrap();
+
+    let expected = MeshSelection::Mesh { mesh: GltfReference::Name(String::from("tree")), primitive: Some(1) };
+    assert_eq!(go_desc.mesh().unwrap().selection(), Some(&expected));
+
+    // The selection is written back with the mesh.
+    let go_string = go_desc.as_string_toml().unwrap();
+    assert!(go_string.contains("mesh = \"tree\"\nprimitive = 1"), "{}", go_string);
+    let go_desc = GameObjectBuilder::load_from_toml(&mut go_string.as_bytes()).unwrap();
+    assert_eq!(go_desc.mesh().unwrap().selection(), Some(&expected));
+
+    let invalid = "id = \"a\"\n[transform]\nposition = [0.0, 0.0, 0.0]\nrotation = [0.0, 0.0, 0.0]\nscale = [1.0, 1.0, 1.0]\n[mesh]\npath = \"kit.gltf\"\nnode = 0\nscene = 0\n";
+    match GameObjectBuilder::load_from_toml(&mut invalid.as_bytes()) {
+        Err(error) => assert!(error.render().contains("Only one of"), "{}", error.render()),
+        Ok(_) => panic!("A mesh can't select a node and a scene."),
+    }
+}
+
+fn assert_vector_eq(a: [f64; 3], b: [f64; 3]) {
+    for (a, b) in a.iter().zip(b.iter()) {
+        assert!((a - b).abs() < 1e-6, "{:?} != {:?}", a, b);
+    }
+}
+
+#[test]
+fn import_gltf_scene() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let kit_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "resource_test/kit.gltf").unwrap();
+    let resource_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "resource_test").unwrap();
+    let level_desc = LevelDescription::import_gltf(kit_path.as_path(), None, resource_path.as_path()).unwrap();
+
+    assert_eq!(level_desc.title(), "kit");
+    let ids: Vec<&str> = level_desc.slice().iter().map(|gameobject| gameobject.id()).collect();
+    assert_eq!(ids, vec!["rock_node", "tree_node", "branch"]);
+    assert_eq!(level_desc.slice()[2].parent(), Some("tree_node"));
+
+    // The matrix of rock_node is decomposed.
+    assert_vector_eq(level_desc.slice()[0].transform().position(), [4.0, 0.0, 0.0]);
+    assert_vector_eq(level_desc.slice()[1].transform().position(), [1.0, 2.0, 3.0]);
+    assert_vector_eq(level_desc.slice()[2].transform().scale(), [2.0, 2.0, 2.0]);
+    let rotation = level_desc.slice()[2].transform().quaternion();
+    assert!((rotation[1] - 0.5f64.sqrt()).abs() < 1e-6, "{:?}", rotation);
+
+    let mesh = level_desc.slice()[1].mesh().unwrap();
+    assert_eq!(mesh.path(), "kit.gltf");
+    assert_eq!(mesh.selection(), Some(&MeshSelection::Mesh { mesh: "tree".into(), primitive: None }));
+    assert!(mesh.check_asset(resource_path.as_path()).is_ok());
+
+    // The mesh paths are relative to the level directory.
+    let levels_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test").unwrap();
+    let level_desc = LevelDescription::import_gltf(kit_path.as_path(), None, levels_path.as_path()).unwrap();
+    assert_eq!(level_desc.slice()[1].get_mesh_resource(), Some(String::from("../resource_test/kit.gltf")));
+
+    let hierarchy = level_desc.hierarchy().unwrap();
+    assert_vector_eq(hierarchy.node("branch").unwrap().world_transform().position(), [1.0, 2.0, 3.0]);
+}
+
+#[test]
+fn import_gltf_scene_by_reference() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let kit_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "resource_test/kit.gltf").unwrap();
+
+    let level_desc = LevelDescription::import_gltf(kit_path.as_path(), GltfReference::from("empty_scene"), "").unwrap();
+    assert_eq!(level_desc.title(), "empty_scene");
+    assert_eq!(level_desc.slice().len(), 1);
+    assert!(level_desc.slice()[0].mesh().is_none());
+
+    match LevelDescription::import_gltf(kit_path.as_path(), GltfReference::from(5), "") {
+        Err(DataParserError::GltfError(description, _)) => assert!(description.contains("no scene 5"), "{}", description),
+        other => panic!("Expected a glTF error, got {:?}", other),
+    }
+}
+
+#[test]
+fn import_gltf_ids_and_transforms() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let names_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "resource_test/duplicate_names.gltf").unwrap();
+    let flat_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "resource_test/zero_scale.gltf").unwrap();
+
+    // The suffixed id of the second Cube is the name of the third node, and the generated id of
+    // the unnamed node is the name of the fourth one.
+    let level_desc = LevelDescription::import_gltf(names_path.as_path(), None, "").unwrap();
+    let ids: Vec<&str> = level_desc.slice().iter().map(|gameobject| gameobject.id()).collect();
+    assert_eq!(ids, vec!["Cube", "Cube_1", "Cube_1_2", "node4", "node4_4"]);
+
+    match LevelDescription::import_gltf(flat_path.as_path(), None, "") {
+        Err(DataParserError::GltfError(description, _)) => {
+            assert!(description.contains("node 1"), "{}", description);
+            assert!(description.contains("transform.scale"), "{}", description);
+        },
+        other => panic!("Expected a glTF error, got {:?}", other),
+    }
+}
+
+#[test]
+fn instantiate_imported_gltf_scene() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let untitled_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "resource_test/untitled.gltf").unwrap();
+
+    // The only mesh of the asset is the whole asset, which can be built.
+    let level_desc = LevelDescription::import_gltf(untitled_path.as_path(), None, "").unwrap();
+    let cube = level_desc.slice().iter().find(|gameobject| gameobject.id() == "Cube").unwrap();
+    assert!(cube.mesh().unwrap().selection().is_none());
+
+    let instance = level_desc.instantiate(InstantiationMode::Strict).unwrap();
+    assert!(instance.is_complete());
+    assert_eq!(instance.gameobjects().len(), 3);
+}
+
+#[test]
+fn export_level_to_gltf() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let kit_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "resource_test/kit.gltf").unwrap();
+    let export_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test/kit_export.gltf").unwrap();
+
+    let resource_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "resource_test").unwrap();
+
+    let level_desc = LevelDescription::import_gltf(kit_path.as_path(), None, resource_path.as_path()).unwrap();
+    level_desc.export_gltf(export_path.as_path(), resource_path.as_path(), GltfExportMode::Embed).unwrap();
+
+    // The export is a valid glTF document, whose nodes mirror the gameobjects.
+    let exported = gltf::Gltf::open(export_path.as_path()).unwrap();
+    assert_eq!(exported.scenes().next().unwrap().name(), Some("kit"));
+    assert_eq!(exported.meshes().count(), 2);
+    assert!(exported.buffers().all(|buffer| match buffer.source() {
+        gltf::buffer::Source::Uri(uri) => uri.starts_with("data:"),
+        gltf::buffer::Source::Bin => false,
+    }));
+
+    let reimported = LevelDescription::import_gltf(export_path.as_path(), None, "").unwrap();
+    let ids: Vec<&str> = reimported.slice().iter().map(|gameobject| gameobject.id()).collect();
+    assert_eq!(ids, vec!["rock_node", "tree_node", "branch"]);
+    assert_eq!(reimported.slice()[2].parent(), Some("tree_node"));
+    assert_vector_eq(reimported.slice()[1].transform().position(), [1.0, 2.0, 3.0]);
+    assert_eq!(reimported.slice()[1].mesh().unwrap().selection(), Some(&MeshSelection::Mesh { mesh: "tree".into(), primitive: None }));
+}
+
+#[test]
+fn export_level_with_referenced_buffers() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let resource_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "resource_test").unwrap();
+    let export_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test/level_export.gltf").unwrap();
+
+    let mut triangle = GameObjectBuilder::new("triangle");
+    triangle
+        .add_transform(TransformDescription::new([1.0, 0.0, 0.0], [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]))
+        .add_mesh(MeshDescription::new("external.gltf"));
+    let mut kit = GameObjectBuilder::new("kit");
+    let mut kit_mesh = MeshDescription::new("kit.gltf");
+    kit_mesh.add_selection(MeshSelection::Node("tree_node".into()));
+    kit.add_mesh(kit_mesh);
+    let mut level_desc = LevelDescription::new("level_export");
+    level_desc.add_gameobject(triangle).add_gameobject(kit);
+
+    level_desc.export_gltf(export_path.as_path(), resource_path.as_path(), GltfExportMode::Reference).unwrap();
+
+    let exported = gltf::Gltf::open(export_path.as_path()).unwrap();
+    let uris: Vec<String> = exported.buffers().map(|buffer| match buffer.source() {
+        gltf::buffer::Source::Uri(uri) => uri.to_owned(),
+        gltf::buffer::Source::Bin => String::new(),
+    }).collect();
+    assert!(uris[0].starts_with("../") && uris[0].ends_with("resource_test/external.bin"), "{}", uris[0]);
+    assert!(uris[1].starts_with("data:"));
+
+    // The whole scene of external.gltf, and the tree_node of kit.gltf with its child, are copied.
+    let names: Vec<Option<&str>> = exported.nodes().map(|node| node.name()).collect();
+    assert_eq!(names, vec![Some("triangle"), Some("kit"), Some("triangle"), Some("branch"), Some("tree_node")]);
+    assert!(gltf::import(export_path.as_path()).is_ok());
+
+    let mut broken = LevelDescription::new("broken");
+    let mut broken_gameobject = GameObjectBuilder::new("broken");
+    broken_gameobject.add_mesh(MeshDescription::new("untitled.gltf"));
+    broken.add_gameobject(broken_gameobject);
+    match broken.export_gltf(export_path.as_path(), resource_path.as_path(), GltfExportMode::Embed) {
+        Err(DataParserError::GltfError(description, _)) => assert!(description.contains("Gameobject 'broken'"), "{}", description),
+        other => panic!("Expected a glTF error, got {:?}", other),
+    }
+}
+
+#[test]
+fn export_level_with_asset_paths() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let resource_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "resource_test").unwrap();
+    let export_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test/asset_export.gltf").unwrap();
+    let mut paths = PathResolver::new();
+    paths.add_project_root(resource_path.as_path());
+
+    let mut triangle = GameObjectBuilder::new("triangle");
+    triangle.add_mesh(MeshDescription::new("asset://triangle.gltf"));
+    let mut level_desc = LevelDescription::new("asset_export");
+    level_desc.add_gameobject(triangle);
+
+    assert!(level_desc.export_gltf(export_path.as_path(), "data_serialization_test", GltfExportMode::Embed).is_err());
+    level_desc.export_gltf_with_resolver(export_path.as_path(), "data_serialization_test", GltfExportMode::Embed, &paths).unwrap();
+    let exported = gltf::Gltf::open(export_path.as_path()).unwrap();
+    assert_eq!(exported.meshes().count(), 1);
+
+    // The nodes loop_a and loop_b of the asset are children of each other.
+    let mut loops = GameObjectBuilder::new("loops");
+    let mut loops_mesh = MeshDescription::new("asset://cyclic_mesh_nodes.gltf");
+    loops_mesh.add_selection(MeshSelection::Node("loop_a".into()));
+    loops.add_mesh(loops_mesh);
+    let mut level_desc = LevelDescription::new("cyclic_export");
+    level_desc.add_gameobject(loops);
+    match level_desc.export_gltf_with_resolver(export_path.as_path(), "", GltfExportMode::Embed, &paths) {
+        Err(DataParserError::GltfError(description, _)) => assert!(description.contains("one of its own descendants"), "{}", description),
+        other => panic!("Expected a glTF error, got {:?}", other),
+    }
+}
+
+#[test]
+fn import_gltf_extras_as_components() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let props_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "resource_test/props.gltf").unwrap();
+
+    // With the default mapping, the tags, lights and colliders are imported.
+    let level_desc = LevelDescription::import_gltf(props_path.as_path(), None, "").unwrap();
+    let lamp = &level_desc.slice()[0];
+    let crate_desc = &level_desc.slice()[1];
+    assert_eq!(lamp.tags(), ["light", "decor"]);
+    assert_eq!(lamp.light().unwrap().kind(), LightKind::Point);
+    assert_eq!(lamp.light().unwrap().color(), [1.0, 0.5, 0.0]);
+    assert_eq!(lamp.light().unwrap().range(), Some(10.0));
+    assert!(lamp.scripts().is_empty());
+    assert_eq!(crate_desc.tags(), ["pushable"]);
+    assert_eq!(crate_desc.collider(), Some(&ColliderDescription::Box { half_extents: [0.5, 0.5, 0.5] }));
+
+    // The keys mapped to scripts name the scripts, their values are the parameters.
+    let mut mapping = ExtrasMapping::new();
+    mapping
+        .add_key("door", ComponentKind::Script)
+        .add_key("breakable", ComponentKind::Script);
+    let level_desc = LevelDescription::import_gltf_with_mapping(props_path.as_path(), None, "", &mapping, &PathResolver::default()).unwrap();
+    let lamp = &level_desc.slice()[0];
+    let crate_desc = &level_desc.slice()[1];
+    assert!(lamp.tags().is_empty());
+    assert!(lamp.light().is_none());
+    assert_eq!(lamp.scripts()[0].name(), "door");
+    assert_eq!(lamp.scripts()[0].parameters().get("opens").and_then(|opens| opens.as_str()), Some("north"));
+    assert_eq!(crate_desc.scripts()[0].name(), "breakable");
+    assert_eq!(crate_desc.scripts()[0].parameters().get("health").and_then(|health| health.as_integer()), Some(3));
+
+    let invalid_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "resource_test/invalid_extras.gltf").unwrap();
+    match LevelDescription::import_gltf(invalid_path.as_path(), None, "") {
+        Err(DataParserError::GltfError(description, _)) => {
+            assert!(description.contains("node 1"), "{}", description);
+            assert!(description.contains("collider.radius"), "{}", description);
+        },
+        other => panic!("Expected a glTF error, got {:?}", other),
+    }
+
+    // The unknown keys of the components are reported, as in the data files.
+    let misspelled_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "resource_test/misspelled_light.gltf").unwrap();
+    match LevelDescription::import_gltf(misspelled_path.as_path(), None, "") {
+        Err(DataParserError::GltfError(description, _)) => {
+            assert!(description.contains("extras.light: unknown key 'rnage', did you mean 'range'?"), "{}", description);
+        },
+        other => panic!("Expected a glTF error, got {:?}", other),
+    }
+}
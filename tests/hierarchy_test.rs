@@ -0,0 +1,71 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+extern crate maskerad_filesystem;
+extern crate maskerad_data_parser;
+
+use maskerad_filesystem::filesystem::Filesystem;
+use maskerad_filesystem::game_directories::RootDir;
+use maskerad_data_parser::level_description::LevelDescription;
+use maskerad_data_parser::data_parser_error::DataParserError;
+
+fn assert_vector_eq(a: [f64; 3], b: [f64; 3]) {
+    for (a, b) in a.iter().zip(b.iter()) {
+        assert!((a - b).abs() < 1e-9, "{:?} != {:?}", a, b);
+    }
+}
+
+#[test]
+fn deserialize_level_hierarchy() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_hierarchy.toml").unwrap();
+    let level_desc = LevelDescription::load_from_file(level_path.as_path()).unwrap();
+
+    let ids: Vec<&str> = level_desc.slice().iter().map(|gameobject| gameobject.id()).collect();
+    assert_eq!(ids, vec!["car", "front_wheel", "back_wheel", "hubcap"]);
+    assert_eq!(level_desc.slice()[1].parent(), Some("car"));
+
+    let hierarchy = level_desc.hierarchy().unwrap();
+    assert_eq!(hierarchy.roots(), vec![0]);
+    assert_eq!(hierarchy.nodes()[0].children(), &[1, 2]);
+    assert_eq!(hierarchy.nodes()[3].parent(), Some(1));
+
+    // The car is rotated by 90° around Y and scaled by 2: its local X axis points to the world -Z axis.
+    let front_wheel = hierarchy.node("front_wheel").unwrap();
+    assert_vector_eq(front_wheel.local_transform().position(), [1.0, 0.0, 0.0]);
+    assert_vector_eq(front_wheel.world_transform().position(), [10.0, 0.0, -2.0]);
+    assert_vector_eq(front_wheel.world_transform().scale(), [2.0, 2.0, 2.0]);
+
+    let hubcap = hierarchy.node("hubcap").unwrap();
+    assert_vector_eq(hubcap.world_transform().position(), [11.0, 0.0, -2.0]);
+
+    // The nested children are saved as gameobjects declaring their parent.
+    let level_string = level_desc.as_string_toml().unwrap();
+    let level_reloaded = LevelDescription::load_from_toml(&mut level_string.as_bytes()).unwrap();
+    assert_eq!(level_reloaded.slice().len(), 4);
+    assert_eq!(level_reloaded.slice()[2].parent(), Some("car"));
+}
+
+#[test]
+fn deserialize_invalid_level_hierarchy() {
+    let transform = "[gameobjects.transform]\nposition = [0.0, 0.0, 0.0]\nrotation = [0.0, 0.0, 0.0]\nscale = [1.0, 1.0, 1.0]\n";
+
+    let missing_parent = format!("title = \"missing_parent\"\n[[gameobjects]]\nid = \"a\"\nparent = \"b\"\n{}", transform);
+    match LevelDescription::load_from_toml(&mut missing_parent.as_bytes()) {
+        Err(DataParserError::HierarchyError(description)) => assert!(description.contains("'b'")),
+        other => panic!("Expected a HierarchyError, got {:?}", other),
+    }
+
+    let cyclic = format!("title = \"cyclic\"\n[[gameobjects]]\nid = \"a\"\nparent = \"b\"\n{}[[gameobjects]]\nid = \"b\"\nparent = \"a\"\n{}", transform, transform);
+    match LevelDescription::load_from_toml(&mut cyclic.as_bytes()) {
+        Err(DataParserError::HierarchyError(description)) => assert!(description.contains("Cyclic")),
+        other => panic!("Expected a HierarchyError, got {:?}", other),
+    }
+
+    let conflicting_parent = "title = \"conflicting_parent\"\n[[gameobjects]]\nid = \"a\"\n[gameobjects.transform]\nposition = [0.0, 0.0, 0.0]\nrotation = [0.0, 0.0, 0.0]\nscale = [1.0, 1.0, 1.0]\n[[gameobjects.children]]\nid = \"b\"\nparent = \"c\"\n[gameobjects.children.transform]\nposition = [0.0, 0.0, 0.0]\nrotation = [0.0, 0.0, 0.0]\nscale = [1.0, 1.0, 1.0]\n";
+    assert!(LevelDescription::load_from_toml(&mut conflicting_parent.as_bytes()).is_err());
+}
@@ -0,0 +1,142 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+extern crate maskerad_filesystem;
+extern crate maskerad_data_parser;
+extern crate serde_json;
+
+use maskerad_filesystem::filesystem::Filesystem;
+use maskerad_filesystem::game_directories::RootDir;
+use maskerad_data_parser::level_description::LevelDescription;
+use maskerad_data_parser::gameobject_builder::GameObjectBuilder;
+use maskerad_data_parser::transform_description::TransformDescription;
+use maskerad_data_parser::rotation_description::{RotationDescription, AngleUnit, EulerOrder};
+use maskerad_data_parser::mesh_description::MeshDescription;
+use maskerad_data_parser::component_description::{LightDescription, LightKind, ScriptDescription};
+use maskerad_data_parser::data_parser_error::DataParserError;
+
+fn gameobject(id: &str, position: [f64; 3], mesh: &str) -> GameObjectBuilder {
+    let mut gameobject = GameObjectBuilder::new(id);
+    gameobject.add_transform(TransformDescription::new(position, [0.0, 0.0, 0.0], [1.0, 1.0, 1.0]));
+    gameobject.add_mesh(MeshDescription::new(mesh));
+    gameobject
+}
+
+fn courtyard() -> LevelDescription {
+    let mut level = LevelDescription::new("courtyard");
+    level
+        .add_gameobject(gameobject("statue", [0.0, 0.0, 0.0], "statue.gltf"))
+        .add_gameobject(gameobject("fountain", [4.0, 0.0, -2.5], "fountain.gltf"))
+        .add_gameobject(gameobject("gate", [0.0, 0.0, 10.0], "gate.gltf"));
+    level
+}
+
+fn courtyard_at_night() -> LevelDescription {
+    let mut fountain = GameObjectBuilder::new("fountain");
+    let rotation = RotationDescription::Euler {
+        angles: [0.0, 90.0, 0.0],
+        unit: AngleUnit::Degrees,
+        order: EulerOrder::XYZ,
+    };
+    fountain.add_transform(TransformDescription::new([5.0, 0.0, -2.5], rotation, [1.0, 1.0, 1.0]));
+    fountain.add_mesh(MeshDescription::new("fountain_v2.gltf"));
+    fountain.add_light(LightDescription::new(LightKind::Point));
+
+    let mut level = LevelDescription::new("courtyard at night");
+    level
+        .add_gameobject(gameobject("gate", [0.0, 0.0, 10.0], "gate.gltf"))
+        .add_gameobject(gameobject("bench", [2.0, 0.0, 3.0], "bench.gltf"))
+        .add_gameobject(fountain);
+    level
+}
+
+#[test]
+fn diff_levels() {
+    let before = courtyard();
+    let after = courtyard_at_night();
+    let diff = before.diff(&after).unwrap();
+
+    assert_eq!(diff.title(), Some(("courtyard", "courtyard at night")));
+    assert_eq!(diff.added().iter().map(|gameobject| gameobject.id()).collect::<Vec<_>>(), vec!["bench"]);
+    assert_eq!(diff.removed().iter().map(|gameobject| gameobject.id()).collect::<Vec<_>>(), vec!["statue"]);
+    assert_eq!(diff.modified().len(), 1);
+    assert!(diff.gameobject("gate").is_none());
+
+    let fountain = diff.gameobject("fountain").unwrap();
+    assert_eq!(fountain.change("transform.position").unwrap().delta(), Some(vec![1.0, 0.0, 0.0]));
+    assert_eq!(fountain.change("mesh.path").unwrap().after().and_then(|path| path.as_str()), Some("fountain_v2.gltf"));
+    assert!(fountain.change("light").unwrap().before().is_none());
+    assert!(fountain.change("transform.scale").is_none());
+    assert!((fountain.rotation_angle().unwrap() - 90.0).abs() < 1e-9);
+
+    assert_eq!(diff.to_string(), "\
+title: \"courtyard\" -> \"courtyard at night\"
++ bench
+- statue
+~ fountain
+    light: none -> { color = [1.0, 1.0, 1.0], intensity = 1.0, kind = \"point\" }
+    mesh.path: \"fountain.gltf\" -> \"fountain_v2.gltf\"
+    transform.position: [4.0, 0.0, -2.5] -> [5.0, 0.0, -2.5] (delta [1.0, 0.0, 0.0])
+    transform.rotation: [0.0, 0.0, 0.0] -> { euler = [0.0, 90.0, 0.0], order = \"XYZ\", unit = \"degrees\" } (rotated by 90°)");
+
+    let json: serde_json::Value = serde_json::from_str(diff.as_string_json().unwrap().as_str()).unwrap();
+    assert_eq!(json, diff.as_json().unwrap());
+    assert_eq!(json["title"]["after"], "courtyard at night");
+    assert_eq!(json["added"][0]["id"], "bench");
+    assert_eq!(json["added"][0]["mesh"]["path"], "bench.gltf");
+    assert_eq!(json["removed"][0]["id"], "statue");
+    assert_eq!(json["modified"][0]["id"], "fountain");
+    assert_eq!(json["modified"][0]["rotation_angle"].as_f64().map(|angle| angle.round()), Some(90.0));
+    assert_eq!(json["modified"][0]["changes"][0]["path"], "light");
+    assert!(json["modified"][0]["changes"][0]["before"].is_null());
+    assert_eq!(json["modified"][0]["changes"][2]["delta"], serde_json::json!([1.0, 0.0, 0.0]));
+}
+
+#[test]
+fn diff_unchanged_level() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_commented.toml").unwrap();
+
+    let level = LevelDescription::load_from_file(level_path.as_path()).unwrap();
+    let reloaded = LevelDescription::load_from_toml(&mut level.as_string_toml().unwrap().as_bytes()).unwrap();
+    let diff = level.diff(&reloaded).unwrap();
+    assert!(diff.is_empty());
+    assert_eq!(diff.to_string(), "No difference.");
+    assert_eq!(diff.as_json().unwrap(), serde_json::json!({ "title": null, "added": [], "removed": [], "modified": [] }));
+}
+
+#[test]
+fn diff_scripts_and_duplicates() {
+    let mut slow = ScriptDescription::new("rotate");
+    slow.add_parameter("speed", 1.0);
+    let mut fast = slow.clone();
+    fast.add_parameter("speed", 2.5).add_parameter("axis", "y");
+    let mut slow_crate = gameobject("crate", [0.0, 0.0, 0.0], "crate.gltf");
+    slow_crate.add_script(slow);
+    let mut fast_crate = gameobject("crate", [0.0, 0.0, 0.0], "crate.gltf");
+    fast_crate.add_script(fast);
+
+    let mut slow_level = LevelDescription::new("scripts");
+    slow_level.add_gameobject(slow_crate);
+    let mut fast_level = LevelDescription::new("scripts");
+    fast_level.add_gameobject(fast_crate);
+    let diff = slow_level.diff(&fast_level).unwrap();
+    assert_eq!(diff.to_string(), "\
+~ crate
+    scripts[0].parameters.axis: none -> \"y\"
+    scripts[0].parameters.speed: 1.0 -> 2.5 (delta [1.5])");
+
+    let mut before = LevelDescription::new("duplicates");
+    before.add_gameobject(gameobject("crate", [0.0, 0.0, 0.0], "crate.gltf"));
+    let mut after = before.clone();
+    after.add_gameobject(gameobject("crate", [1.0, 0.0, 0.0], "crate.gltf"));
+
+    match before.diff(&after) {
+        Err(DataParserError::HierarchyError(description)) => assert!(description.contains("'crate'"), "{}", description),
+        other => panic!("Expected a hierarchy error, found {:?}", other.map(|diff| diff.to_string())),
+    }
+}
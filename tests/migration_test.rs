@@ -0,0 +1,96 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+extern crate maskerad_filesystem;
+extern crate maskerad_data_parser;
+
+use maskerad_filesystem::filesystem::Filesystem;
+use maskerad_filesystem::game_directories::RootDir;
+use maskerad_data_parser::gameobject_builder::GameObjectBuilder;
+use maskerad_data_parser::level_description::LevelDescription;
+use maskerad_data_parser::migration::CURRENT_FORMAT_VERSION;
+use maskerad_data_parser::unknown_key::UnknownKeyMode;
+use maskerad_data_parser::data_parser_error::DataParserError;
+use std::fs::{self, File};
+use std::io::Read;
+
+#[test]
+fn upgrade_gameobject_from_version_1() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let go_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/gameobject_v1.toml").unwrap();
+
+    let (go_desc, warnings) = GameObjectBuilder::load_from_file_with_mode(go_path.as_path(), UnknownKeyMode::Strict).unwrap();
+    assert!(warnings.is_empty());
+    assert_eq!(go_desc.transform().position(), [1.0, 2.0, 3.0]);
+
+    let mut go_file = File::open(go_path.as_path()).unwrap();
+    assert!(GameObjectBuilder::load_from_toml_with_mode(&mut go_file, UnknownKeyMode::Strict).is_ok());
+}
+
+#[test]
+fn upgrade_level_from_version_1() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_v1.toml").unwrap();
+
+    // The inline gameobjects, their children and the referenced gameobject files are upgraded.
+    let (level_desc, warnings) = LevelDescription::load_from_file_with_mode(level_path.as_path(), UnknownKeyMode::Strict).unwrap();
+    assert!(warnings.is_empty());
+    let ids: Vec<&str> = level_desc.slice().iter().map(|gameobject| gameobject.id()).collect();
+    assert_eq!(ids, vec!["gameobject_v1", "parent_v1", "child_v1"]);
+    let rotation = level_desc.slice()[1].transform().quaternion();
+    assert!((rotation[1] - 0.5f64.sqrt()).abs() < 1e-9, "{:?}", rotation);
+}
+
+#[test]
+fn reject_unsupported_format_version() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_future.toml").unwrap();
+
+    match LevelDescription::load_from_file(level_path.as_path()) {
+        Err(DataParserError::MigrationError(description)) => assert!(description.contains("99"), "{}", description),
+        other => panic!("Expected a migration error, got {:?}", other),
+    }
+}
+
+#[test]
+fn save_with_format_version() {
+    let level_desc = LevelDescription::new("level_versioned");
+    let level_string = level_desc.as_string_toml().unwrap();
+    assert!(level_string.starts_with(format!("format_version = {}\n", CURRENT_FORMAT_VERSION).as_str()), "{}", level_string);
+
+    let level_desc = LevelDescription::load_from_toml_with_mode(&mut level_string.as_bytes(), UnknownKeyMode::Strict).unwrap().0;
+    assert_eq!(level_desc.title(), "level_versioned");
+}
+
+#[test]
+fn rewrite_migrated_files() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let level_v1_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_v1.toml").unwrap();
+    let go_v1_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/gameobject_v1.toml").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test/level_migrated.toml").unwrap();
+    let go_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test/gameobject_v1.toml").unwrap();
+    fs::copy(level_v1_path.as_path(), level_path.as_path()).unwrap();
+    fs::copy(go_v1_path.as_path(), go_path.as_path()).unwrap();
+
+    assert!(LevelDescription::migrate_file(level_path.as_path()).unwrap());
+    assert!(!LevelDescription::migrate_file(level_path.as_path()).unwrap());
+    assert!(GameObjectBuilder::migrate_file(go_path.as_path()).unwrap());
+
+    let mut content = String::new();
+    File::open(level_path.as_path()).unwrap().read_to_string(&mut content).unwrap();
+    assert!(content.contains(format!("format_version = {}", CURRENT_FORMAT_VERSION).as_str()));
+    // The reference is kept as is.
+    assert!(content.contains("path = \"gameobject_v1.toml\""));
+    // The file is upgraded in place, keeping its comments and formatting.
+    assert!(content.starts_with(format!("# A level written in the first version of the format.\nformat_version = {}\ntitle = \"level_v1\"\n", CURRENT_FORMAT_VERSION).as_str()), "{}", content);
+    assert!(content.contains("position = [0.0, 0.0, 0.0]\nrotation = [0.0, 1.5707963267948966, 0.0]   # a quarter turn\nscale = [1.0, 1.0, 1.0]\n"), "{}", content);
+    assert!(content.contains("# The child keeps its own transform.\n[[gameobjects.children]]\n"), "{}", content);
+
+    let (level_desc, warnings) = LevelDescription::load_from_file_with_mode(level_path.as_path(), UnknownKeyMode::Strict).unwrap();
+    assert!(warnings.is_empty());
+    assert_eq!(level_desc.slice().len(), 3);
+}
@@ -0,0 +1,74 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+extern crate maskerad_filesystem;
+extern crate maskerad_data_parser;
+
+use maskerad_filesystem::filesystem::Filesystem;
+use maskerad_filesystem::game_directories::RootDir;
+use maskerad_data_parser::level_description::LevelDescription;
+use maskerad_data_parser::asset_path::PathResolver;
+use maskerad_data_parser::unknown_key::UnknownKeyMode;
+use maskerad_data_parser::data_parser_error::DataParserError;
+use maskerad_data_parser::pack::{self, PackFilesystem};
+use std::path::Path;
+use std::io::Write;
+
+#[test]
+fn load_level_from_pack() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let project_root = fs.construct_path_from_root(RootDir::WorkingDirectory, "").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_asset_paths.toml").unwrap();
+    let pack_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test/level_asset_paths.zip").unwrap();
+    let mut paths = PathResolver::new();
+    paths.add_project_root(project_root.as_path());
+
+    // The level, its references, its prefabs and its meshes are packed, relative to the project root.
+    let packed_level_path = pack::pack_level(level_path.as_path(), pack_path.as_path(), &paths).unwrap();
+    assert_eq!(packed_level_path, Path::new("data_deserialization_test/level_asset_paths.toml"));
+    let pack = PackFilesystem::open_pack(pack_path.as_path()).unwrap();
+    assert_eq!(pack.entries(), vec![
+        "data_deserialization_test/level_asset_paths.toml",
+        "data_deserialization_test/props/prefab_kit.toml",
+        "data_deserialization_test/props/prop_lamp.toml",
+        "resource_test/kit.gltf",
+        "resource_test/triangle.gltf",
+    ]);
+
+    // The pack is loaded through the same API as the loose files, with an empty project root.
+    let (loose_level, _) = LevelDescription::load_from_file_with_resolver(level_path.as_path(), UnknownKeyMode::Strict, &paths).unwrap();
+    let mut pack_paths = PathResolver::new();
+    pack_paths.add_project_root("");
+    let (packed_level, _) = LevelDescription::load_from_filesystem(&pack, packed_level_path.as_path(), UnknownKeyMode::Strict, &pack_paths).unwrap();
+    assert_eq!(packed_level.as_string_toml().unwrap(), loose_level.as_string_toml().unwrap());
+    assert!(LevelDescription::validate_in_filesystem(&pack, packed_level_path.as_path(), &pack_paths).is_empty());
+}
+
+#[test]
+fn pack_external_buffers() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test/level_external.toml").unwrap();
+    let pack_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test/level_external.zip").unwrap();
+    let transform = "[gameobjects.transform]\nposition = [0.0, 0.0, 0.0]\nrotation = [0.0, 0.0, 0.0]\nscale = [1.0, 1.0, 1.0]\n";
+    let mut level_file = fs.create(level_path.as_path()).unwrap();
+    write!(level_file, "title = \"level_external\"\n[[gameobjects]]\nid = \"external\"\n{}[gameobjects.mesh]\npath = \"../resource_test/external.gltf\"\n", transform).unwrap();
+    level_file.flush().unwrap();
+
+    let packed_level_path = pack::pack_level(level_path.as_path(), pack_path.as_path(), &PathResolver::new()).unwrap();
+    let pack = PackFilesystem::open_pack(pack_path.as_path()).unwrap();
+    assert!(pack.entries().iter().any(|entry| entry.ends_with("resource_test/external.bin")));
+    assert!(LevelDescription::validate_in_filesystem(&pack, packed_level_path.as_path(), &PathResolver::new()).is_empty());
+
+    // A level whose mesh is missing can't be packed.
+    let mut level_file = fs.create(level_path.as_path()).unwrap();
+    write!(level_file, "title = \"level_external\"\n[[gameobjects]]\nid = \"missing\"\n{}[gameobjects.mesh]\npath = \"missing.gltf\"\n", transform).unwrap();
+    level_file.flush().unwrap();
+    match pack::pack_level(level_path.as_path(), pack_path.as_path(), &PathResolver::new()) {
+        Err(DataParserError::IoError(description, _)) => assert!(description.contains("missing.gltf"), "{}", description),
+        other => panic!("Expected an IoError, got {:?}", other),
+    }
+}
@@ -0,0 +1,63 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+extern crate maskerad_filesystem;
+extern crate maskerad_data_parser;
+
+use maskerad_filesystem::filesystem::Filesystem;
+use maskerad_filesystem::game_directories::RootDir;
+use maskerad_data_parser::gameobject_builder::GameObjectBuilder;
+use maskerad_data_parser::level_description::LevelDescription;
+use maskerad_data_parser::data_parser_error::DataParserError;
+
+#[test]
+fn deserialize_level_with_prefabs() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_prefabs.toml").unwrap();
+    let level_desc = LevelDescription::load_from_file(level_path.as_path()).unwrap();
+    assert_eq!(level_desc.slice().len(), 2);
+
+    // The overridden values replace the values of the template, the others are kept.
+    let rock1 = &level_desc.slice()[0];
+    assert_eq!(rock1.id(), "rock1");
+    assert_eq!(rock1.transform().position(), [1.0, 0.0, 0.0]);
+    assert_eq!(rock1.transform().scale(), [1.0, 1.0, 1.0]);
+    assert_eq!(rock1.get_mesh_resource(), Some(String::from("rock.gltf")));
+
+    // Nested prefabs: rock2 -> prefab_mossy_rock.toml -> prefab_rock.toml.
+    let rock2 = &level_desc.slice()[1];
+    assert_eq!(rock2.id(), "rock2");
+    assert_eq!(rock2.transform().position(), [0.0, 0.0, 0.0]);
+    assert_eq!(rock2.transform().scale(), [2.0, 2.0, 2.0]);
+    assert_eq!(rock2.get_mesh_resource(), Some(String::from("mossy_rock.gltf")));
+    let sin_22_5 = 22.5_f64.to_radians().sin();
+    assert!((rock2.transform().quaternion()[1] - sin_22_5).abs() < 1e-9);
+
+    // The flattened level doesn't reference the prefabs anymore.
+    let level_string = level_desc.as_string_toml().unwrap();
+    assert!(!level_string.contains("prefab ="));
+    assert_eq!(LevelDescription::load_from_toml(&mut level_string.as_bytes()).unwrap().slice().len(), 2);
+}
+
+#[test]
+fn deserialize_gameobject_with_prefab() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let mossy_rock_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/prefab_mossy_rock.toml").unwrap();
+    let mossy_rock = GameObjectBuilder::load_from_file(mossy_rock_path.as_path()).unwrap();
+    assert_eq!(mossy_rock.id(), "mossy_rock");
+    assert_eq!(mossy_rock.transform().scale(), [2.0, 2.0, 2.0]);
+
+    // The prefab can't be resolved without the path of the file.
+    let mut mossy_rock_content = fs.open(mossy_rock_path.as_path()).unwrap();
+    assert!(GameObjectBuilder::load_from_toml(&mut mossy_rock_content).is_err());
+
+    let cyclic_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/prefab_cyclic.toml").unwrap();
+    match GameObjectBuilder::load_from_file(cyclic_path.as_path()) {
+        Err(DataParserError::ReferenceError(description)) => assert!(description.contains("Cyclic")),
+        other => panic!("Expected a ReferenceError, got {:?}", other),
+    }
+}
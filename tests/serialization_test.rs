@@ -15,8 +15,10 @@ use maskerad_data_parser::gameobject_builder::GameObjectBuilder;
 use maskerad_data_parser::level_description::LevelDescription;
 use maskerad_data_parser::transform_description::TransformDescription;
 use maskerad_data_parser::mesh_description::MeshDescription;
+use maskerad_data_parser::data_parser_error::DataParserError;
+use maskerad_data_parser::component_description::{LightKind, ColliderDescription, ScriptDescription};
+use maskerad_data_parser::data_format::DataFormat;
 use std::io::Write;
-use std::io::Read;
 
 #[test]
 fn test_serialization() {
@@ -53,9 +55,9 @@ fn deserialize_gameobject_builder() {
 
     assert!(go2_desc.get_mesh_resource().is_some());
     assert_eq!(go2_desc.id(), "gameobject2");
-    assert_eq!(go2_desc.transform().scale(), vec![1.0, 1.0, 1.0].as_slice());
-    assert_eq!(go2_desc.transform().position(), vec![0.0, 0.0, 0.0].as_slice());
-    assert_eq!(go2_desc.transform().rotation(), vec![0.0, 0.0, 0.0].as_slice());
+    assert_eq!(go2_desc.transform().scale(), [1.0, 1.0, 1.0]);
+    assert_eq!(go2_desc.transform().position(), [0.0, 0.0, 0.0]);
+    assert_eq!(go2_desc.transform().quaternion(), [0.0, 0.0, 0.0, 1.0]);
 
     //gameobject1.toml -> no mesh
     let go1_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/gameobject1.toml").unwrap();
@@ -64,9 +66,9 @@ fn deserialize_gameobject_builder() {
 
     assert!(go1_desc.get_mesh_resource().is_none());
     assert_eq!(go1_desc.id(), "gameobject1");
-    assert_eq!(go1_desc.transform().scale(), vec![1.0, 1.0, 1.0].as_slice());
-    assert_eq!(go1_desc.transform().position(), vec![0.0, 0.0, 0.0].as_slice());
-    assert_eq!(go1_desc.transform().rotation(), vec![0.0, 0.0, 0.0].as_slice());
+    assert_eq!(go1_desc.transform().scale(), [1.0, 1.0, 1.0]);
+    assert_eq!(go1_desc.transform().position(), [0.0, 0.0, 0.0]);
+    assert_eq!(go1_desc.transform().quaternion(), [0.0, 0.0, 0.0, 1.0]);
 
 }
 
@@ -75,11 +77,10 @@ fn deserialize_gameobject_builder() {
 fn serialize_gameobjectbuilder() {
     let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
     let go4_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test/gameobject4.toml").expect("Could not construct go4 path");
-    let mut go4_content = fs.open(go4_path.as_path()).unwrap();
 
-    let pos = vec![1.0, 2.0, 3.0];
-    let rot = vec![0.0, 0.0, 0.0];
-    let scale = vec![2.0, 2.0, 2.0];
+    let pos = [1.0, 2.0, 3.0];
+    let rot = [0.0, 0.0, 0.0];
+    let scale = [2.0, 2.0, 2.0];
     let transform_desc = TransformDescription::new(pos, rot, scale);
     let mesh_desc = MeshDescription::new("path_test_mesh");
 
@@ -88,15 +89,13 @@ fn serialize_gameobjectbuilder() {
         .add_transform(transform_desc)
         .add_mesh(mesh_desc);
 
-    let mut writer = fs.create(go4_path.as_path()).unwrap();
-    writer.write_all(go4_desc.as_string_toml().unwrap().as_ref());
-
+    go4_desc.save_to_file(go4_path.as_path()).unwrap();
 
     assert!(go4_path.as_path().exists());
 
-    let pos = vec![5.0, 7.0, 11.0];
-    let rot = vec![0.8, 5.2, 1.0];
-    let scale = vec![2.4, 2.2, 2.9];
+    let pos = [5.0, 7.0, 11.0];
+    let rot = [0.8, 5.2, 1.0];
+    let scale = [2.4, 2.2, 2.9];
     let transform_desc = TransformDescription::new(pos, rot, scale);
 
     let go5_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test/gameobject5.toml").expect("Could not construct go5 path");
@@ -104,7 +103,199 @@ fn serialize_gameobjectbuilder() {
     go5_desc.add_transform(transform_desc);
 
     let mut writer = fs.create(go5_path.as_path()).unwrap();
-    writer.write_all(go5_desc.as_string_toml().unwrap().as_ref());
+    go5_desc.save(&mut writer, DataFormat::Toml).unwrap();
 
     assert!(go5_path.as_path().exists());
-}
\ No newline at end of file
+}
+
+#[test]
+fn deserialize_level_with_references() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let level1_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level1.toml").unwrap();
+    let level1_desc = LevelDescription::load_from_file(level1_path.as_path()).unwrap();
+
+    assert_eq!(level1_desc.title(), "level1");
+    assert_eq!(level1_desc.slice().iter().count(), 2);
+    assert_eq!(level1_desc.slice()[0].id(), "gameobject1");
+    assert_eq!(level1_desc.slice()[1].id(), "gameobject2");
+
+    // level_mixed.toml -> a reference, an inline gameobject, and an included level.
+    let mixed_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_mixed.toml").unwrap();
+    let mixed_desc = LevelDescription::load_from_file(mixed_path.as_path()).unwrap();
+    let ids: Vec<&str> = mixed_desc.slice().iter().map(|gameobject| gameobject.id()).collect();
+    assert_eq!(ids, vec!["gameobject1", "gameobject3", "gameobject1", "gameobject2"]);
+
+    // References can't be resolved without the path of the level file.
+    let mut level1_content = fs.open(level1_path.as_path()).unwrap();
+    assert!(LevelDescription::load_from_toml(&mut level1_content).is_err());
+}
+
+#[test]
+fn deserialize_level_with_invalid_references() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+
+    let missing_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_missing_reference.toml").unwrap();
+    match LevelDescription::load_from_file(missing_path.as_path()) {
+        Err(DataParserError::IoError(description, _)) => {
+            assert!(description.contains("gameobjects[1]"));
+            assert!(description.contains("does_not_exist.toml"));
+        },
+        other => panic!("Expected an IoError, got {:?}", other),
+    }
+
+    let cyclic_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_cyclic1.toml").unwrap();
+    match LevelDescription::load_from_file(cyclic_path.as_path()) {
+        Err(DataParserError::ReferenceError(description)) => {
+            assert!(description.contains("gameobjects[1]"));
+            assert!(description.contains("level_cyclic2.toml"));
+        },
+        other => panic!("Expected a ReferenceError, got {:?}", other),
+    }
+}
+
+#[test]
+fn deserialize_invalid_transform() {
+    let invalid_gameobjects = vec![
+        ("id = \"go_position\"\n[transform]\nposition = [1.0]\nrotation = [0.0, 0.0, 0.0]\nscale = [1.0, 1.0, 1.0]", "go_position", "transform.position"),
+        ("id = \"go_rotation\"\n[transform]\nposition = [0.0, 0.0, 0.0]\nrotation = [0.0, nan, 0.0]\nscale = [1.0, 1.0, 1.0]", "go_rotation", "transform.rotation"),
+        ("id = \"go_scale\"\n[transform]\nposition = [0.0, 0.0, 0.0]\nrotation = [0.0, 0.0, 0.0]\nscale = [1.0, 0.0, 1.0]", "go_scale", "transform.scale"),
+    ];
+
+    for (content, id, field) in invalid_gameobjects {
+        match GameObjectBuilder::load_from_toml(&mut content.as_bytes()) {
+            Err(DataParserError::DeserializationError(_, deserialization_error, _)) => {
+                let message = deserialization_error.to_string();
+                assert!(message.contains(id), "'{}' should name the gameobject '{}'", message, id);
+                assert!(message.contains(field), "'{}' should name the field '{}'", message, field);
+            },
+            other => panic!("Expected a DeserializationError, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn rotation_representations() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let go3_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/gameobject3.toml").unwrap();
+    let mut go3_content = fs.open(go3_path.as_path()).unwrap();
+    let go3_desc = GameObjectBuilder::load_from_toml(&mut go3_content).unwrap();
+
+    let quaternion = go3_desc.transform().quaternion();
+    let half_sqrt2 = 0.5_f64.sqrt();
+    assert!((quaternion[1] - half_sqrt2).abs() < 1e-9 && (quaternion[3] - half_sqrt2).abs() < 1e-9);
+
+    // The rotation is written back in the form chosen by the author.
+    let go3_string = go3_desc.as_string_toml().unwrap();
+    assert!(go3_string.contains("unit = \"degrees\""));
+    assert!(go3_string.contains("order = \"ZYX\""));
+    let go3_reloaded = GameObjectBuilder::load_from_toml(&mut go3_string.as_bytes()).unwrap();
+    assert_eq!(go3_reloaded.transform(), go3_desc.transform());
+
+    let quaternion_go = "id = \"go_quaternion\"\n[transform]\nposition = [0.0, 0.0, 0.0]\nscale = [1.0, 1.0, 1.0]\n[transform.rotation]\nquaternion = [0.0, 0.0, 0.0, 2.0]";
+    let quaternion_desc = GameObjectBuilder::load_from_toml(&mut quaternion_go.as_bytes()).unwrap();
+    assert_eq!(quaternion_desc.transform().quaternion(), [0.0, 0.0, 0.0, 1.0]);
+
+    let axis_angle_go = "id = \"go_axis_angle\"\n[transform]\nposition = [0.0, 0.0, 0.0]\nscale = [1.0, 1.0, 1.0]\n[transform.rotation]\naxis = [0.0, 0.0, 0.0]\nangle = 1.0";
+    assert!(GameObjectBuilder::load_from_toml(&mut axis_angle_go.as_bytes()).is_err());
+
+    let ambiguous_go = "id = \"go_ambiguous\"\n[transform]\nposition = [0.0, 0.0, 0.0]\nscale = [1.0, 1.0, 1.0]\n[transform.rotation]\neuler = [0.0, 0.0, 0.0]\nquaternion = [0.0, 0.0, 0.0, 1.0]";
+    assert!(GameObjectBuilder::load_from_toml(&mut ambiguous_go.as_bytes()).is_err());
+}
+
+#[test]
+fn locate_deserialization_errors() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_invalid.toml").unwrap();
+
+    let error = LevelDescription::load_from_file(level_path.as_path()).unwrap_err();
+    {
+        let location = error.location().expect("The error should be located");
+        assert!(location.file().unwrap().ends_with("level_invalid.toml"));
+        assert_eq!(location.key_path(), Some("gameobjects[1].transform.scale"));
+        assert_eq!(location.line(), Some(17));
+        assert_eq!(location.column(), Some(1));
+        assert_eq!(location.snippet(), Some("scale = \"big\""));
+    }
+    let report = error.render();
+    assert!(report.contains("level_invalid.toml:17:1"));
+    assert!(report.contains("17 | scale = \"big\"\n     | ^"));
+
+    // Without a file, the errors are still located in the content.
+    let mut level_content = fs.open(level_path.as_path()).unwrap();
+    let error = LevelDescription::load_from_toml(&mut level_content).unwrap_err();
+    let location = error.location().expect("The error should be located");
+    assert!(location.file().is_none());
+    assert_eq!(location.line(), Some(17));
+
+    let mut go_content = "id = \"go\"\n[transform]\nposition = [0.0, 0.0, 0.0]\nrotation = [0.0, 0.0, 0.0]\nscale = [1.0, 1.0, 1.0]\n[mesh]\npath = 3\n".as_bytes();
+    let error = GameObjectBuilder::load_from_toml(&mut go_content).unwrap_err();
+    let location = error.location().expect("The error should be located");
+    assert_eq!(location.key_path(), Some("mesh.path"));
+    assert_eq!(location.line(), Some(7));
+}
+
+#[test]
+fn gameobject_components() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let torch_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/gameobject_components.toml").unwrap();
+    let mut torch_content = fs.open(torch_path.as_path()).unwrap();
+    let torch_desc = GameObjectBuilder::load_from_toml(&mut torch_content).unwrap();
+
+    assert_eq!(torch_desc.tags(), ["light", "flammable"]);
+    let light = torch_desc.light().unwrap();
+    assert_eq!(light.kind(), LightKind::Spot);
+    assert_eq!(light.color(), [1.0, 1.0, 1.0]);
+    assert_eq!(light.intensity(), 2.5);
+    assert_eq!(light.range(), None);
+    assert_eq!(torch_desc.collider(), Some(&ColliderDescription::Capsule { radius: 0.1, height: 0.5 }));
+    assert_eq!(torch_desc.scripts().len(), 2);
+    assert_eq!(torch_desc.scripts()[0].name(), "flicker");
+    assert_eq!(torch_desc.scripts()[0].parameters().get("speed").and_then(|speed| speed.as_float()), Some(4.0));
+    assert!(torch_desc.scripts()[1].parameters().is_empty());
+
+    // The components survive a round trip.
+    let toml_string = torch_desc.as_string_toml().unwrap();
+    let reloaded = GameObjectBuilder::load_from_toml(&mut toml_string.as_bytes()).unwrap();
+    assert_eq!(reloaded.tags(), torch_desc.tags());
+    assert_eq!(reloaded.light(), torch_desc.light());
+    assert_eq!(reloaded.collider(), torch_desc.collider());
+    assert_eq!(reloaded.scripts(), torch_desc.scripts());
+
+    let invalid_collider = "id = \"crate\"\n[transform]\nposition = [0.0, 0.0, 0.0]\nrotation = [0.0, 0.0, 0.0]\nscale = [1.0, 1.0, 1.0]\n[collider]\nshape = \"sphere\"\nradius = 0.0\n";
+    match GameObjectBuilder::load_from_toml(&mut invalid_collider.as_bytes()) {
+        Err(error) => assert!(error.render().contains("collider.radius must be strictly positive"), "{}", error.render()),
+        Ok(gameobject) => panic!("Expected an error, got {:?}", gameobject),
+    }
+}
+
+#[test]
+fn save_atomically() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let torch_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/gameobject_components.toml").unwrap();
+    let saved_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test/gameobject_atomic.json").unwrap();
+    let torch_desc = GameObjectBuilder::load_from_file(torch_path.as_path()).unwrap();
+
+    // The format is given by the extension.
+    torch_desc.save_to_file(saved_path.as_path()).unwrap();
+    let saved_content = std::fs::read_to_string(saved_path.as_path()).unwrap();
+    assert!(saved_content.starts_with("{\n"), "{}", saved_content);
+    assert_eq!(GameObjectBuilder::load_from_file(saved_path.as_path()).unwrap().as_string_toml().unwrap(), torch_desc.as_string_toml().unwrap());
+
+    // A failed save leaves the previous file untouched, without temporary file.
+    let mut script = ScriptDescription::new("broken");
+    script.add_parameter("speed", f64::NAN);
+    let mut broken_desc = torch_desc.clone();
+    broken_desc.add_script(script);
+    assert!(broken_desc.save_to_file(saved_path.as_path()).is_err());
+    assert_eq!(std::fs::read_to_string(saved_path.as_path()).unwrap(), saved_content);
+    let temporary_files = std::fs::read_dir(saved_path.parent().unwrap()).unwrap()
+        .filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().starts_with(".gameobject_atomic.json"))
+        .count();
+    assert_eq!(temporary_files, 0);
+
+    let missing_directory_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_serialization_test/missing/gameobject.toml").unwrap();
+    match torch_desc.save_to_file(missing_directory_path.as_path()) {
+        Err(DataParserError::IoError(description, _)) => assert!(description.contains("Could not save the file"), "{}", description),
+        other => panic!("Expected an I/O error, got {:?}", other),
+    }
+}
@@ -0,0 +1,146 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+extern crate maskerad_filesystem;
+extern crate maskerad_data_parser;
+
+use maskerad_filesystem::filesystem::Filesystem;
+use maskerad_filesystem::game_directories::RootDir;
+use maskerad_data_parser::level_description::LevelDescription;
+use maskerad_data_parser::gameobject_builder::GameObjectBuilder;
+use maskerad_data_parser::diagnostic::{DiagnosticCode, Severity};
+use maskerad_data_parser::unknown_key::UnknownKeyMode;
+use maskerad_data_parser::data_parser_error::DataParserError;
+use maskerad_data_parser::data_format::DataFormat;
+use std::fs::File;
+
+#[test]
+fn validate_valid_level() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_hierarchy.toml").unwrap();
+    let diagnostics = LevelDescription::validate_file(level_path.as_path());
+    assert!(diagnostics.iter().all(|diagnostic| !diagnostic.is_error()), "{:?}", diagnostics);
+}
+
+#[test]
+fn validate_invalid_level() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_diagnostics.toml").unwrap();
+    let diagnostics = LevelDescription::validate_file(level_path.as_path());
+    let codes: Vec<&str> = diagnostics.iter().map(|diagnostic| diagnostic.code().code()).collect();
+
+    // The bad transform and the missing reference.
+    assert_eq!(codes.iter().filter(|code| **code == "E002").count(), 1, "{:?}", codes);
+    assert_eq!(codes.iter().filter(|code| **code == "E005").count(), 1, "{:?}", codes);
+    assert!(codes.contains(&"E003"), "{:?}", codes);
+
+    // The duplicate id is located at its second occurrence.
+    let duplicate_id = diagnostics.iter().find(|diagnostic| diagnostic.code() == DiagnosticCode::DuplicateId).unwrap();
+    assert_eq!(duplicate_id.location().key_path(), Some("gameobjects[1].id"));
+    assert_eq!(duplicate_id.location().line(), Some(16));
+    assert!(codes.contains(&"E006"), "{:?}", codes);
+
+    let unknown_keys: Vec<&str> = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.code() == DiagnosticCode::UnknownKey)
+        .map(|diagnostic| diagnostic.location().key_path().unwrap())
+        .collect();
+    assert_eq!(unknown_keys, vec!["author", "gameobjects[1].colour"]);
+    assert!(diagnostics.iter().filter(|diagnostic| diagnostic.code() == DiagnosticCode::UnknownKey).all(|diagnostic| diagnostic.severity() == Severity::Warning));
+
+    let missing_meshes: Vec<_> = diagnostics.iter().filter(|diagnostic| diagnostic.code() == DiagnosticCode::MissingMesh).collect();
+    assert_eq!(missing_meshes.len(), 1);
+    assert_eq!(missing_meshes[0].location().key_path(), Some("gameobjects[0].mesh.path"));
+    assert_eq!(missing_meshes[0].location().line(), Some(13));
+    assert!(missing_meshes[0].to_string().starts_with("error[E004]"));
+}
+
+#[test]
+fn unknown_keys_modes() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let level_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/level_typos.toml").unwrap();
+
+    let (level_desc, warnings) = LevelDescription::load_from_file_with_mode(level_path.as_path(), UnknownKeyMode::Lenient).unwrap();
+    assert!(level_desc.slice()[0].get_mesh_resource().is_none());
+    let messages: Vec<&str> = warnings.iter().map(|warning| warning.message()).collect();
+    assert_eq!(messages, vec![
+        "Unknown key 'gameobjects[0].transform.postion', did you mean 'position'?",
+        "Unknown key 'gameobjects[0].mseh', did you mean 'mesh'?",
+    ]);
+    assert_eq!(warnings[1].location().line(), Some(12));
+    assert!(LevelDescription::load_from_file(level_path.as_path()).is_ok());
+
+    match LevelDescription::load_from_file_with_mode(level_path.as_path(), UnknownKeyMode::Strict) {
+        Err(DataParserError::UnknownKeyError(description, location)) => {
+            assert!(description.contains("did you mean 'position'?"), "{}", description);
+            assert!(description.contains("did you mean 'mesh'?"), "{}", description);
+            assert_eq!(location.line(), Some(7));
+        },
+        other => panic!("Expected an unknown key error, got {:?}", other),
+    }
+
+    let mut level_file = File::open(level_path.as_path()).unwrap();
+    assert!(LevelDescription::load_from_toml_with_mode(&mut level_file, UnknownKeyMode::Strict).is_err());
+}
+
+#[test]
+fn unknown_keys_suggestions_replace_missing_fields() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let go_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "data_deserialization_test/gameobject_typos.toml").unwrap();
+
+    // The misspelled transform is reported, instead of the missing transform.
+    match GameObjectBuilder::load_from_file_with_mode(go_path.as_path(), UnknownKeyMode::Strict) {
+        Err(DataParserError::UnknownKeyError(description, _)) => {
+            assert_eq!(description, "Unknown key 'colour'. Unknown key 'trasnform', did you mean 'transform'?");
+        },
+        other => panic!("Expected an unknown key error, got {:?}", other),
+    }
+
+    match GameObjectBuilder::load_from_file_with_mode(go_path.as_path(), UnknownKeyMode::Lenient) {
+        Err(DataParserError::DeserializationError(..)) => {},
+        other => panic!("Expected a deserialization error, got {:?}", other),
+    }
+}
+
+#[test]
+fn unknown_keys_in_rotations() {
+    // The misspelled unit would read the angles as radians.
+    let source = "title = \"rotation\"\n\n[[gameobjects]]\nid = \"door\"\n\n[gameobjects.transform]\nposition = [0.0, 0.0, 0.0]\nscale = [1.0, 1.0, 1.0]\n\n[gameobjects.transform.rotation]\neuler = [0.0, 90.0, 0.0]\nunti = \"degrees\"\n";
+
+    match LevelDescription::load_from_toml_with_mode(&mut source.as_bytes(), UnknownKeyMode::Strict) {
+        Err(DataParserError::UnknownKeyError(description, location)) => {
+            assert_eq!(description, "Unknown key 'gameobjects[0].transform.rotation.unti', did you mean 'unit'?");
+            assert_eq!(location.line(), Some(12));
+        },
+        other => panic!("Expected an unknown key error, got {:?}", other.map(|_| ())),
+    }
+
+    let (_, warnings) = LevelDescription::load_from_toml_with_mode(&mut source.as_bytes(), UnknownKeyMode::Lenient).unwrap();
+    assert_eq!(warnings.len(), 1);
+
+    let gameobject_source = "id = \"door\"\n\n[transform]\nposition = [0.0, 0.0, 0.0]\nscale = [1.0, 1.0, 1.0]\nrotation = { axis = [0.0, 1.0, 0.0], angle = 90.0, unti = \"degrees\" }\n";
+    match GameObjectBuilder::load_from_reader_with_mode(&mut gameobject_source.as_bytes(), DataFormat::Toml, UnknownKeyMode::Strict) {
+        Err(DataParserError::UnknownKeyError(description, _)) => {
+            assert_eq!(description, "Unknown key 'transform.rotation.unti', did you mean 'unit'?");
+        },
+        other => panic!("Expected an unknown key error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn unknown_keys_in_colliders() {
+    let source = "id = \"ball\"\ncollider = { shape = \"sphere\", radius = 1.0 }\n\n[transform]\nposition = [0.0, 0.0, 0.0]\nrotation = [0.0, 0.0, 0.0]\nscale = [1.0, 1.0, 1.0]\n\n[[children]]\nid = \"bumper\"\ntransform = { position = [0.0, 0.0, 0.0], rotation = [0.0, 0.0, 0.0], scale = [1.0, 1.0, 1.0] }\ncollider = { shape = \"sphere\", radius = 1.0, radus = 2.0 }\n\n[[scripts]]\nname = \"bounce\"\nparameters = { light = { colour = \"red\" } }\n";
+
+    // The tables of the script parameters are free, the misspelled radius is reported.
+    match GameObjectBuilder::load_from_reader_with_mode(&mut source.as_bytes(), DataFormat::Toml, UnknownKeyMode::Strict) {
+        Err(DataParserError::UnknownKeyError(description, location)) => {
+            assert_eq!(description, "Unknown key 'children[0].collider.radus', did you mean 'radius'?");
+            assert_eq!(location.line(), Some(12));
+        },
+        other => panic!("Expected an unknown key error, got {:?}", other.map(|_| ())),
+    }
+}
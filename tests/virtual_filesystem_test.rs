@@ -0,0 +1,74 @@
+// Copyright 2017-2018 Maskerad Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+extern crate maskerad_filesystem;
+extern crate maskerad_data_parser;
+
+use maskerad_filesystem::filesystem::Filesystem;
+use maskerad_filesystem::game_directories::RootDir;
+use maskerad_data_parser::level_description::LevelDescription;
+use maskerad_data_parser::gameobject_builder::GameObjectBuilder;
+use maskerad_data_parser::asset_path::PathResolver;
+use maskerad_data_parser::unknown_key::UnknownKeyMode;
+use maskerad_data_parser::diagnostic::DiagnosticCode;
+use maskerad_data_parser::virtual_filesystem::{VirtualFilesystem, MemoryFilesystem};
+#[cfg(feature = "maskerad_filesystem")]
+use maskerad_data_parser::virtual_filesystem::MaskeradFilesystem;
+use std::path::Path;
+use std::fs::File;
+use std::io::Read;
+
+const TRANSFORM: &str = "[transform]\nposition = [0.0, 0.0, 0.0]\nrotation = [0.0, 0.0, 0.0]\nscale = [1.0, 1.0, 1.0]\n";
+
+#[test]
+fn load_from_memory_filesystem() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let triangle_path = fs.construct_path_from_root(RootDir::WorkingDirectory, "resource_test/triangle.gltf").unwrap();
+    let mut triangle = Vec::new();
+    File::open(triangle_path.as_path()).unwrap().read_to_end(&mut triangle).unwrap();
+
+    let mut memory = MemoryFilesystem::new();
+    memory
+        .add_file("levels/level.toml", "title = \"memory\"\ngameobjects = [\"../objects/rock.toml\", \"asset://objects/tree.toml\"]\n")
+        .add_file("objects/rock.toml", format!("id = \"rock\"\n{}[mesh]\npath = \"../meshes/triangle.gltf\"\n", TRANSFORM))
+        .add_file("objects/tree.toml", format!("id = \"tree\"\nprefab = \"prefabs/prefab_tree.toml\"\n{}", TRANSFORM))
+        .add_file("objects/prefabs/prefab_tree.toml", format!("id = \"prefab_tree\"\n{}[mesh]\npath = \"../../meshes/missing.gltf\"\n", TRANSFORM))
+        .add_file("meshes/triangle.gltf", triangle);
+    assert!(memory.is_file(Path::new("levels/../meshes/./triangle.gltf")));
+
+    let mut paths = PathResolver::new();
+    paths.add_project_root("");
+    let (level_desc, _) = LevelDescription::load_from_filesystem(&memory, "levels/level.toml", UnknownKeyMode::Strict, &paths).unwrap();
+    let ids: Vec<&str> = level_desc.slice().iter().map(|gameobject| gameobject.id()).collect();
+    assert_eq!(ids, vec!["rock", "tree"]);
+    assert_eq!(level_desc.slice()[0].get_mesh_resource(), Some(String::from("../meshes/triangle.gltf")));
+    assert_eq!(level_desc.slice()[1].get_mesh_resource(), Some(String::from("../meshes/missing.gltf")));
+
+    // The meshes are checked in the filesystem too: only the mesh of the prefab is missing.
+    let diagnostics = LevelDescription::validate_in_filesystem(&memory, "levels/level.toml", &paths);
+    assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+    assert_eq!(diagnostics[0].code(), DiagnosticCode::MissingMesh);
+    assert!(diagnostics[0].message().contains("missing.gltf"), "{}", diagnostics[0].message());
+
+    let (tree, _) = GameObjectBuilder::load_from_filesystem(&memory, "objects/tree.toml", UnknownKeyMode::Strict, &paths).unwrap();
+    assert_eq!(tree.id(), "tree");
+    assert_eq!(tree.get_mesh_resource(), Some(String::from("../meshes/missing.gltf")));
+
+    assert!(LevelDescription::load_from_filesystem(&memory, "levels/missing.toml", UnknownKeyMode::Lenient, &paths).is_err());
+}
+
+#[test]
+#[cfg(feature = "maskerad_filesystem")]
+fn load_from_maskerad_filesystem() {
+    let fs = Filesystem::new("gameobject_file_test", "malkaviel").unwrap();
+    let working_directory = MaskeradFilesystem::new(&fs, RootDir::WorkingDirectory);
+
+    let (level_desc, _) = LevelDescription::load_from_filesystem(&working_directory, "data_deserialization_test/level1.toml", UnknownKeyMode::Strict, &PathResolver::new()).unwrap();
+    assert_eq!(level_desc.title(), "level1");
+    assert_eq!(level_desc.slice().len(), 2);
+    assert!(working_directory.is_file(Path::new("resource_test/triangle.gltf")));
+}